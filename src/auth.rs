@@ -0,0 +1,404 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to handle the path-scoped authentication rules of the config file.
+//!
+
+use headers::{authorization::Bearer, Authorization, HeaderMap, HeaderMapExt, HeaderValue};
+use hyper::{Method, StatusCode, Uri};
+use std::net::IpAddr;
+
+#[cfg(feature = "basic-auth")]
+use crate::basic_auth::UserStore;
+#[cfg(feature = "jwt-auth")]
+use crate::jwt_auth::{self, JwtVerifier};
+#[cfg(feature = "signed-urls")]
+use crate::signed_urls::UrlSigner;
+use crate::{
+    handler::RequestHandlerOpts, helpers::constant_time_eq, settings::file::AuthKind,
+    settings::Auth, transport::ClientIdentity,
+};
+
+/// Default authentication realm.
+pub const DEFAULT_REALM: &str = "Static Web Server";
+
+/// It defines an authentication failure.
+#[derive(Debug)]
+pub struct AuthError {
+    /// Status code of the response.
+    pub status: StatusCode,
+    /// Optional `WWW-Authenticate` header value of the response.
+    pub www_authenticate: Option<HeaderValue>,
+    /// Optional `Location` header value of the response.
+    pub location: Option<HeaderValue>,
+}
+
+impl AuthError {
+    /// Creates a new authentication failure with the given status code only.
+    pub fn from_status(status: StatusCode) -> Self {
+        Self {
+            status,
+            www_authenticate: None,
+            location: None,
+        }
+    }
+
+    fn unauthorized(challenge: String) -> Self {
+        Self {
+            www_authenticate: HeaderValue::from_str(&challenge).ok(),
+            ..Self::from_status(StatusCode::UNAUTHORIZED)
+        }
+    }
+
+    fn forbidden() -> Self {
+        Self::from_status(StatusCode::FORBIDDEN)
+    }
+}
+
+/// It returns the first authentication rule matching the request uri path if any.
+pub fn get_rule<'a>(uri_path: &str, rules: &'a Option<Vec<Auth>>) -> Option<&'a Auth> {
+    rules
+        .as_ref()?
+        .iter()
+        .find(|rule| rule.source.is_match(uri_path))
+}
+
+/// Checks the authentication requirement of a rule for an incoming request.
+/// It returns the authenticated user id if the requirement identifies one.
+/// Headers of the authorization service to copy onto the response are added to `copied_headers`.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(
+    not(all(feature = "signed-urls", feature = "forward-auth")),
+    allow(unused_variables)
+)]
+pub async fn check_request(
+    rule: &Auth,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    remote_ip: Option<IpAddr>,
+    client_identity: Option<&ClientIdentity>,
+    opts: &RequestHandlerOpts,
+    copied_headers: &mut HeaderMap,
+) -> Result<Option<String>, AuthError> {
+    match rule.kind {
+        AuthKind::None => Ok(None),
+        #[cfg(feature = "basic-auth")]
+        AuthKind::Basic => {
+            check_basic(
+                rule,
+                headers,
+                opts.basic_auth.as_ref(),
+                session_user(headers, opts),
+            )
+            .await
+        }
+        AuthKind::Bearer => check_bearer(rule, headers),
+        #[cfg(feature = "jwt-auth")]
+        AuthKind::Jwt => check_jwt(&rule.realm, &rule.claims, headers, opts.jwt_auth.as_ref()),
+        #[cfg(not(feature = "basic-auth"))]
+        AuthKind::Basic => Err(AuthError::forbidden()),
+        #[cfg(not(feature = "jwt-auth"))]
+        AuthKind::Jwt => Err(AuthError::forbidden()),
+        #[cfg(feature = "signed-urls")]
+        AuthKind::SignedUrl => check_signed_url(uri, remote_ip, opts.signed_urls.as_ref()),
+        #[cfg(not(feature = "signed-urls"))]
+        AuthKind::SignedUrl => Err(AuthError::forbidden()),
+        AuthKind::ClientCert => check_client_cert(rule, client_identity),
+        #[cfg(feature = "forward-auth")]
+        AuthKind::Forward => match &opts.forward_auth {
+            Some(forward_auth) => {
+                copied_headers.extend(forward_auth.check(method, uri, headers, remote_ip).await?);
+                Ok(None)
+            }
+            None => Err(AuthError::forbidden()),
+        },
+        #[cfg(not(feature = "forward-auth"))]
+        AuthKind::Forward => Err(AuthError::forbidden()),
+    }
+}
+
+/// Returns the user of a valid form login session of a request if any.
+#[cfg(feature = "basic-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+#[cfg_attr(not(feature = "form-login"), allow(unused_variables))]
+pub fn session_user(headers: &HeaderMap, opts: &RequestHandlerOpts) -> Option<String> {
+    #[cfg(feature = "form-login")]
+    return opts
+        .form_login
+        .as_ref()?
+        .session_user(headers, opts.basic_auth.as_ref()?);
+    #[cfg(not(feature = "form-login"))]
+    None
+}
+
+/// Checks the `Basic` requirement of a rule against the server users.
+/// The user of a form login `session` is accepted in place of the credentials.
+#[cfg(feature = "basic-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+pub async fn check_basic(
+    rule: &Auth,
+    headers: &HeaderMap,
+    users: Option<&UserStore>,
+    session: Option<String>,
+) -> Result<Option<String>, AuthError> {
+    let challenge = format!("Basic realm=\"{}\", charset=\"UTF-8\"", rule.realm);
+    let userid = match session {
+        Some(userid) => userid,
+        None => users
+            .ok_or_else(|| AuthError::unauthorized(challenge.clone()))?
+            .check_request(headers)
+            .await
+            .map_err(|_| AuthError::unauthorized(challenge))?,
+    };
+
+    if !rule.users.is_empty() && !rule.users.contains(&userid) {
+        tracing::warn!("user `{}` is not allowed by the auth rule", userid);
+        return Err(AuthError::forbidden());
+    }
+    Ok(Some(userid))
+}
+
+/// Checks the `Bearer` requirement of a rule against its static tokens.
+pub fn check_bearer(rule: &Auth, headers: &HeaderMap) -> Result<Option<String>, AuthError> {
+    let token = match headers.typed_get::<Authorization<Bearer>>() {
+        Some(credentials) => credentials,
+        None => {
+            return Err(AuthError::unauthorized(format!(
+                "Bearer realm=\"{}\"",
+                rule.realm
+            )))
+        }
+    };
+    let token = token.0.token().as_bytes();
+    if rule
+        .tokens
+        .iter()
+        .any(|t| constant_time_eq(t.as_bytes(), token))
+    {
+        return Ok(None);
+    }
+    Err(AuthError::unauthorized(format!(
+        "Bearer realm=\"{}\", error=\"invalid_token\"",
+        rule.realm
+    )))
+}
+
+/// Checks a JSON Web Token requirement including the given `(name, value)` claims.
+/// It returns the `sub` claim of the token if any.
+#[cfg(feature = "jwt-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+pub fn check_jwt(
+    realm: &str,
+    claims: &[(String, String)],
+    headers: &HeaderMap,
+    verifier: Option<&JwtVerifier>,
+) -> Result<Option<String>, AuthError> {
+    let challenge = format!("Bearer realm=\"{realm}\"");
+    let verifier = verifier.ok_or_else(|| AuthError::unauthorized(challenge.clone()))?;
+    let token = verifier
+        .token(headers)
+        .ok_or_else(|| AuthError::unauthorized(challenge.clone()))?;
+
+    let token_claims = verifier.verify(&token).map_err(|err| {
+        tracing::debug!("jwt verification failed: {}", err);
+        AuthError::unauthorized(format!(
+            "{challenge}, error=\"invalid_token\", error_description=\"{err}\""
+        ))
+    })?;
+    let sub = token_claims
+        .get("sub")
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_owned());
+
+    if !jwt_auth::has_claims(&token_claims, claims) {
+        tracing::warn!(
+            "token of `{:?}` is missing the claims of the auth rule",
+            sub
+        );
+        return Err(AuthError {
+            www_authenticate: HeaderValue::from_str(&format!(
+                "{challenge}, error=\"insufficient_scope\""
+            ))
+            .ok(),
+            ..AuthError::forbidden()
+        });
+    }
+    Ok(sub)
+}
+
+/// Checks the verified TLS client certificate of a connection against the identities of a rule.
+/// Every identity of the rule must match the certificate. It returns the certificate subject.
+pub fn check_client_cert(
+    rule: &Auth,
+    identity: Option<&ClientIdentity>,
+) -> Result<Option<String>, AuthError> {
+    let identity = identity.ok_or_else(AuthError::forbidden)?;
+    if !rule.identities.is_empty() && !rule.identities.iter().all(|v| identity.matches(v)) {
+        tracing::warn!(
+            "client certificate `{}` is not allowed by the auth rule",
+            identity.subject
+        );
+        return Err(AuthError::forbidden());
+    }
+    Ok(Some(identity.subject.clone()))
+}
+
+/// Checks the signature and expiration of a signed URL.
+#[cfg(feature = "signed-urls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+pub fn check_signed_url(
+    uri: &Uri,
+    remote_ip: Option<IpAddr>,
+    signer: Option<&UrlSigner>,
+) -> Result<Option<String>, AuthError> {
+    let signer = signer.ok_or_else(AuthError::forbidden)?;
+    signer
+        .verify(uri.path(), uri.query(), remote_ip)
+        .map_err(|err| {
+            tracing::debug!("signed url verification failed: {}", err);
+            AuthError::forbidden()
+        })?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use globset::Glob;
+
+    fn rule(source: &str, kind: AuthKind) -> Auth {
+        Auth {
+            source: Glob::new(source).unwrap().compile_matcher(),
+            kind,
+            users: vec![],
+            tokens: vec!["s3cr3t".to_owned()],
+            claims: vec![],
+            identities: vec![],
+            realm: "Internal".to_owned(),
+        }
+    }
+
+    #[test]
+    fn rules_matching() {
+        let rules = Some(vec![
+            rule("/internal/**", AuthKind::Bearer),
+            rule("/**", AuthKind::None),
+        ]);
+        assert_eq!(
+            get_rule("/internal/a.html", &rules).unwrap().kind,
+            AuthKind::Bearer
+        );
+        assert_eq!(
+            get_rule("/public/a.html", &rules).unwrap().kind,
+            AuthKind::None
+        );
+        assert!(get_rule("/a.html", &None).is_none());
+    }
+
+    #[test]
+    fn bearer_token() {
+        let rule = rule("/internal/**", AuthKind::Bearer);
+        let mut headers = HeaderMap::new();
+
+        let err = check_bearer(&rule, &headers).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(err.www_authenticate.unwrap(), "Bearer realm=\"Internal\"");
+
+        headers.insert("authorization", "Bearer other".parse().unwrap());
+        let err = check_bearer(&rule, &headers).unwrap_err();
+        assert_eq!(
+            err.www_authenticate.unwrap(),
+            "Bearer realm=\"Internal\", error=\"invalid_token\""
+        );
+
+        headers.insert("authorization", "Bearer s3cr3t".parse().unwrap());
+        assert!(check_bearer(&rule, &headers).is_ok());
+    }
+
+    #[cfg(feature = "basic-auth")]
+    #[tokio::test]
+    async fn basic_users() {
+        let users = UserStore::new(
+            Some("jq:$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q"),
+            None,
+        )
+        .unwrap();
+        let mut rule = rule("/internal/**", AuthKind::Basic);
+        let mut headers = HeaderMap::new();
+
+        let err = check_basic(&rule, &headers, Some(&users), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            err.www_authenticate.unwrap(),
+            "Basic realm=\"Internal\", charset=\"UTF-8\""
+        );
+
+        headers.insert("authorization", "Basic anE6anE=".parse().unwrap());
+        assert_eq!(
+            check_basic(&rule, &headers, Some(&users), None)
+                .await
+                .unwrap(),
+            Some("jq".to_owned())
+        );
+
+        rule.users = vec!["abc".to_owned()];
+        let err = check_basic(&rule, &headers, Some(&users), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+    }
+
+    #[cfg(feature = "jwt-auth")]
+    #[test]
+    fn jwt_challenges() {
+        let verifier = JwtVerifier::new(
+            std::path::Path::new("tests/fixtures/jwt/jwks.json"),
+            "",
+            "",
+            "",
+            jwt_auth::DEFAULT_LEEWAY,
+        )
+        .unwrap();
+        let mut headers = HeaderMap::new();
+
+        let err = check_jwt("Internal", &[], &headers, Some(&verifier)).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(err.www_authenticate.unwrap(), "Bearer realm=\"Internal\"");
+
+        headers.insert("authorization", "Bearer a.b.c".parse().unwrap());
+        let err = check_jwt("Internal", &[], &headers, Some(&verifier)).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            err.www_authenticate.unwrap(),
+            "Bearer realm=\"Internal\", error=\"invalid_token\", error_description=\"malformed token header\""
+        );
+    }
+
+    #[test]
+    fn client_certificates() {
+        let mut rule = rule("/internal/**", AuthKind::ClientCert);
+        let identity = ClientIdentity {
+            subject: "CN=alice,O=Example".to_owned(),
+            attributes: vec!["O=Example".to_owned(), "CN=alice".to_owned()],
+            sans: vec!["DNS:alice.local".to_owned()],
+        };
+
+        let err = check_client_cert(&rule, None).unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            check_client_cert(&rule, Some(&identity)).unwrap(),
+            Some("CN=alice,O=Example".to_owned())
+        );
+
+        rule.identities = vec!["O=Example".to_owned(), "DNS:alice.local".to_owned()];
+        assert!(check_client_cert(&rule, Some(&identity)).is_ok());
+        rule.identities = vec!["O=Example".to_owned(), "CN=bob".to_owned()];
+        let err = check_client_cert(&rule, Some(&identity)).unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to temporarily ban the clients repeatedly failing to authenticate.
+//!
+//! Once a client IP address reaches the maximum number of consecutive failures,
+//! it's banned for a while. Every further failure doubles the ban duration
+//! up to a maximum.
+//!
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::client_map::ClientMap;
+
+/// Maximum number of tracked client addresses.
+const MAX_CLIENTS: usize = 10_000;
+
+/// Authentication failures of a client.
+struct Failures {
+    count: u32,
+    last: Instant,
+    banned_until: Option<Instant>,
+}
+
+/// It defines the authentication failure counters of the clients.
+pub struct AuthThrottle {
+    max_failures: u32,
+    ban: Duration,
+    max_ban: Duration,
+    clients: ClientMap<IpAddr, Failures>,
+}
+
+impl AuthThrottle {
+    /// Creates a new throttle banning the clients after `max_failures` consecutive failures.
+    /// The first ban lasts `ban` and the following ones double up to `max_ban`.
+    pub fn new(max_failures: u32, ban: Duration, max_ban: Duration) -> Self {
+        Self {
+            max_failures: max_failures.max(1),
+            ban,
+            max_ban: max_ban.max(ban),
+            clients: ClientMap::new(MAX_CLIENTS),
+        }
+    }
+
+    /// Returns the remaining ban duration of a client if it's banned.
+    pub fn retry_after(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let until = self.clients.get_with(&ip, |f| f.banned_until)??;
+        until.checked_duration_since(now).filter(|d| !d.is_zero())
+    }
+
+    /// Records an authentication failure of a client.
+    /// It returns the ban duration if the client gets banned.
+    pub fn failure(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let init = || Failures {
+            count: 0,
+            last: now,
+            banned_until: None,
+        };
+        self.clients
+            .with(ip, init, |failures| self.record_failure(failures, now))
+    }
+
+    /// Updates the failures of a client and returns the ban duration if it gets banned.
+    fn record_failure(&self, failures: &mut Failures, now: Instant) -> Option<Duration> {
+        if self.is_expired(failures, now) {
+            failures.count = 0;
+            failures.banned_until = None;
+        }
+        failures.count = failures.count.saturating_add(1);
+        failures.last = now;
+        if failures.count < self.max_failures {
+            return None;
+        }
+
+        let exp = (failures.count - self.max_failures).min(31);
+        let ban = self
+            .ban
+            .checked_mul(1 << exp)
+            .map_or(self.max_ban, |ban| ban.min(self.max_ban));
+        failures.banned_until = Some(now + ban);
+        Some(ban)
+    }
+
+    /// Forgets the failures of a client after a successful authentication.
+    pub fn success(&self, ip: IpAddr) {
+        self.clients.remove(&ip);
+    }
+
+    /// Failures are forgotten once the client doesn't fail during the maximum ban duration.
+    fn is_expired(&self, failures: &Failures, now: Instant) -> bool {
+        let since = failures
+            .banned_until
+            .map_or(failures.last, |until| until.max(failures.last));
+        now.saturating_duration_since(since) >= self.max_ban
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_bans() {
+        let throttle = AuthThrottle::new(3, Duration::from_secs(10), Duration::from_secs(35));
+        let ip: IpAddr = "192.168.1.10".parse().unwrap();
+        let other: IpAddr = "192.168.1.11".parse().unwrap();
+        let now = Instant::now();
+
+        assert_eq!(throttle.failure(ip, now), None);
+        assert_eq!(throttle.failure(ip, now), None);
+        assert_eq!(throttle.retry_after(ip, now), None);
+        assert_eq!(throttle.failure(ip, now), Some(Duration::from_secs(10)));
+        assert_eq!(throttle.retry_after(ip, now), Some(Duration::from_secs(10)));
+        assert_eq!(throttle.retry_after(other, now), None);
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(throttle.retry_after(ip, later), None);
+        assert_eq!(throttle.failure(ip, later), Some(Duration::from_secs(20)));
+        assert_eq!(throttle.failure(ip, later), Some(Duration::from_secs(35)));
+
+        // Failures are forgotten after a quiet period or a success
+        let much_later = later + Duration::from_secs(70);
+        assert_eq!(throttle.failure(ip, much_later), None);
+        throttle.failure(other, now);
+        throttle.failure(other, now);
+        throttle.success(other);
+        assert_eq!(throttle.failure(other, now), None);
+    }
+
+    #[test]
+    fn bounded_clients() {
+        let throttle = AuthThrottle::new(2, Duration::from_secs(10), Duration::from_secs(60));
+        let now = Instant::now();
+        for i in 0..MAX_CLIENTS as u32 * 2 {
+            throttle.failure(
+                IpAddr::from(i.to_be_bytes()),
+                now + Duration::from_millis(i as u64),
+            );
+        }
+        assert!(throttle.clients.len() <= MAX_CLIENTS);
+    }
+}
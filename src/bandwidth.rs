@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to limit the bandwidth of the file responses.
+//!
+//! Bandwidth limits are byte buckets which can be shared by the responses of a connection
+//! or by all the responses of the server. File response bodies are delayed, once compressed
+//! or rewritten, so that the bytes they send don't exceed the rate of any of their limits.
+//!
+
+use bytes::Bytes;
+use futures_util::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+use crate::settings::BandwidthRule;
+
+/// It defines a bandwidth limit in bytes per second.
+#[derive(Debug)]
+pub struct Bandwidth {
+    rate: u64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl Bandwidth {
+    /// Creates a new bandwidth limit of `rate` bytes per second.
+    /// Up to one second worth of bytes can be sent at once.
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            bucket: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    /// Takes `len` bytes from the bucket and returns how long to wait before sending them.
+    pub fn reserve(&self, len: usize, now: Instant) -> Duration {
+        let rate = self.rate as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let (available, last) = *bucket;
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        let available = (available + elapsed * rate).min(rate) - len as f64;
+        *bucket = (available, now.max(last));
+        if available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-available / rate)
+        }
+    }
+}
+
+/// It defines the bandwidth limit of the responses of a connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionBandwidth(pub Arc<Bandwidth>);
+
+/// It defines the bandwidth limits applying to a response body.
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    limits: Vec<Arc<Bandwidth>>,
+    after: u64,
+}
+
+impl Throttle {
+    /// Creates a throttle sending the first `after` bytes without limits.
+    pub fn new(after: u64) -> Self {
+        Self {
+            limits: Vec::new(),
+            after,
+        }
+    }
+
+    /// Adds a bandwidth limit to the throttle.
+    pub fn with_limit(mut self, limit: Arc<Bandwidth>) -> Self {
+        self.limits.push(limit);
+        self
+    }
+
+    /// Checks whether the throttle has no limits.
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Wraps a body stream to throttle it.
+    pub fn wrap<S>(self, stream: S) -> ThrottledStream<S> {
+        ThrottledStream {
+            stream,
+            throttle: self,
+            sleep: None,
+            pending: None,
+        }
+    }
+}
+
+/// It defines a body stream delayed by its bandwidth limits.
+pub struct ThrottledStream<S> {
+    stream: S,
+    throttle: Throttle,
+    sleep: Option<Pin<Box<Sleep>>>,
+    pending: Option<Bytes>,
+}
+
+impl<S, E> Stream for ThrottledStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        loop {
+            if let Some(sleep) = this.sleep.as_mut() {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.sleep = None;
+                if let Some(chunk) = this.pending.take() {
+                    return Poll::Ready(Some(Ok(chunk)));
+                }
+            }
+
+            let chunk = match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => chunk,
+                other => return other,
+            };
+
+            // The first bytes of the body are sent without limits
+            let free = this.throttle.after.min(chunk.len() as u64);
+            this.throttle.after -= free;
+            let limited = chunk.len() - free as usize;
+            if limited == 0 {
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+
+            let now = Instant::now();
+            let delay = this
+                .throttle
+                .limits
+                .iter()
+                .map(|limit| limit.reserve(limited, now))
+                .max()
+                .unwrap_or_default();
+            if delay.is_zero() {
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+            this.sleep = Some(Box::pin(tokio::time::sleep(delay)));
+            this.pending = Some(chunk);
+        }
+    }
+}
+
+/// It returns the first bandwidth rule matching the request uri path if any.
+pub fn get_rule<'a>(
+    uri_path: &str,
+    rules: &'a Option<Vec<BandwidthRule>>,
+) -> Option<&'a BandwidthRule> {
+    rules
+        .as_ref()?
+        .iter()
+        .find(|rule| rule.source.is_match(uri_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{stream, StreamExt};
+
+    #[test]
+    fn byte_buckets() {
+        let limit = Bandwidth::new(1000);
+        let now = Instant::now();
+        assert_eq!(limit.reserve(600, now), Duration::ZERO);
+        assert_eq!(limit.reserve(400, now), Duration::ZERO);
+        assert_eq!(limit.reserve(500, now), Duration::from_millis(500));
+        assert_eq!(
+            limit.reserve(500, now + Duration::from_millis(500)),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttled_stream() {
+        let chunks = (0..10).map(|_| Ok::<_, std::io::Error>(Bytes::from(vec![0u8; 1000])));
+        let limit = Arc::new(Bandwidth::new(2000));
+        let throttle = Throttle::new(3000).with_limit(limit);
+        let start = Instant::now();
+        let mut body = throttle.wrap(stream::iter(chunks));
+        let mut len = 0;
+        while let Some(chunk) = body.next().await {
+            len += chunk.unwrap().len();
+        }
+        assert_eq!(len, 10_000);
+        // 3000 free bytes, 2000 bytes of the initial bucket then 5000 bytes at 2000 bytes/s
+        assert_eq!(start.elapsed().as_millis(), 2500);
+    }
+}
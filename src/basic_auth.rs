@@ -9,38 +9,401 @@
 use bcrypt::verify as bcrypt_verify;
 use headers::{authorization::Basic, Authorization, HeaderMapExt};
 use hyper::StatusCode;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Semaphore;
+
+use crate::{helpers::constant_time_eq, Context, Result};
+
+/// Interval between two checks for changes of the htpasswd file.
+pub const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default lifetime of the verified credentials cache entries.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Default maximum number of concurrent password verifications.
+pub const DEFAULT_MAX_VERIFICATIONS: usize = 4;
+
+/// Maximum number of entries of the verified credentials cache.
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+/// Magic prefix of the Apache MD5 (`apr1`) password hashes.
+const APR1_MAGIC: &str = "$apr1$";
 
 /// Check for a `Basic` HTTP Authorization Schema of an incoming request
-/// and uses `bcrypt` for password hashing verification.
+/// and verifies its password against the given hash. See [`verify_password`].
 pub fn check_request(
     headers: &http::HeaderMap,
     userid: &str,
     password: &str,
 ) -> Result<(), StatusCode> {
     if let Some(ref credentials) = headers.typed_get::<Authorization<Basic>>() {
-        if credentials.0.username() == userid {
-            return match bcrypt_verify(credentials.0.password(), password) {
-                Ok(valid) => {
-                    if valid {
-                        Ok(())
-                    } else {
-                        Err(StatusCode::UNAUTHORIZED)
-                    }
-                }
-                Err(err) => {
-                    tracing::error!("bcrypt password verification error: {:?}", err);
-                    Err(StatusCode::UNAUTHORIZED)
-                }
-            };
+        if credentials.0.username() == userid && verify_password(credentials.0.password(), password)
+        {
+            return Ok(());
         }
     }
 
     Err(StatusCode::UNAUTHORIZED)
 }
 
+/// Verifies a password against a hash using one of the supported formats:
+/// `bcrypt` (`$2y$`, `$2b$`, `$2a$`), SHA-512 crypt (`$6$`), SHA-256 crypt (`$5$`),
+/// Apache MD5 (`$apr1$`) or MD5 crypt (`$1$`).
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$2") {
+        return match bcrypt_verify(password, hash) {
+            Ok(valid) => valid,
+            Err(err) => {
+                tracing::error!("bcrypt password verification error: {:?}", err);
+                false
+            }
+        };
+    }
+    if hash.starts_with("$6$") {
+        return pwhash::sha512_crypt::verify(password, hash);
+    }
+    if hash.starts_with("$5$") {
+        return pwhash::sha256_crypt::verify(password, hash);
+    }
+    if let Some(salt) = hash.strip_prefix(APR1_MAGIC) {
+        let salt = salt.split('$').next().unwrap_or_default();
+        return constant_time_eq(apr1_md5(password, salt).as_bytes(), hash.as_bytes());
+    }
+    if hash.starts_with("$1$") {
+        return pwhash::md5_crypt::verify(password, hash);
+    }
+
+    tracing::error!("unsupported password hash format");
+    false
+}
+
+/// It defines the users allowed to authenticate along with their password hashes.
+/// Users can be loaded from an Apache-style htpasswd file which is reloaded when it changes.
+/// See [`UserStore::watch`].
+///
+/// Password hashes are verified on the blocking threads with a limited concurrency
+/// and successfully verified credentials are cached for a while, so the clients
+/// sending them on every request don't pay the cost of slow hashes like `bcrypt`.
+pub struct UserStore {
+    users: Arc<RwLock<HashMap<String, String>>>,
+    file: Option<Arc<HtpasswdFile>>,
+    /// Digests of the recently verified credentials and their verification time.
+    cache: Arc<Mutex<HashMap<[u8; 32], Instant>>>,
+    cache_ttl: Duration,
+    verifications: Semaphore,
+}
+
+/// It tracks the changes of an htpasswd file.
+struct HtpasswdFile {
+    path: PathBuf,
+    /// Modification time of the file when it was last read.
+    modified: Mutex<Option<SystemTime>>,
+    /// Users not defined in the file, like the `--basic-auth` one, kept on every reload.
+    other_users: HashMap<String, String>,
+}
+
+impl UserStore {
+    /// Creates a new user store from an optional `user-id:password` pair
+    /// and an optional htpasswd file. Users of the file take precedence.
+    pub fn new(pair: Option<&str>, file: Option<&Path>) -> Result<Self> {
+        let mut users = HashMap::new();
+        if let Some(pair) = pair {
+            let (userid, hash) = pair
+                .split_once(':')
+                .with_context(|| "invalid basic authentication `user-id:password` pair")?;
+            users.insert(userid.to_owned(), hash.to_owned());
+        }
+
+        let file = match file {
+            Some(path) => {
+                let modified = std::fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .with_context(|| {
+                        format!(
+                            "htpasswd file not found or inaccessible: {}",
+                            path.display()
+                        )
+                    })?;
+                let other_users = users.clone();
+                users.extend(read_htpasswd(path)?);
+                Some(Arc::new(HtpasswdFile {
+                    path: path.to_owned(),
+                    modified: Mutex::new(Some(modified)),
+                    other_users,
+                }))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            users: Arc::new(RwLock::new(users)),
+            file,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            cache_ttl: DEFAULT_CACHE_TTL,
+            verifications: Semaphore::new(DEFAULT_MAX_VERIFICATIONS),
+        })
+    }
+
+    /// Sets the lifetime of the verified credentials cache entries. A zero value disables the cache.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum number of concurrent password verifications.
+    pub fn with_max_verifications(mut self, max: usize) -> Self {
+        self.verifications = Semaphore::new(max.max(1));
+        self
+    }
+
+    /// Returns the number of users.
+    pub fn len(&self) -> usize {
+        self.users.read().unwrap().len()
+    }
+
+    /// Checks whether the store has no users.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check for a `Basic` HTTP Authorization Schema of an incoming request
+    /// against the known users. It returns the user id if the credentials are valid.
+    pub async fn check_request(&self, headers: &http::HeaderMap) -> Result<String, StatusCode> {
+        if let Some(ref credentials) = headers.typed_get::<Authorization<Basic>>() {
+            let userid = credentials.0.username();
+            if self.verify(userid, credentials.0.password()).await {
+                return Ok(userid.to_owned());
+            }
+        }
+
+        Err(StatusCode::UNAUTHORIZED)
+    }
+
+    /// Verifies the password of the given user.
+    pub async fn verify(&self, userid: &str, password: &str) -> bool {
+        let hash = match self.users.read().unwrap().get(userid) {
+            Some(hash) => hash.to_owned(),
+            None => return false,
+        };
+
+        // The stored hash is part of the key so a password change invalidates the entry
+        let key: [u8; 32] = Sha256::new()
+            .chain(userid)
+            .chain([0])
+            .chain(password)
+            .chain([0])
+            .chain(&hash)
+            .finalize()
+            .into();
+        if self.is_cached(&key) {
+            return true;
+        }
+
+        let _permit = match self.verifications.acquire().await {
+            Ok(permit) => permit,
+            Err(_) => return false,
+        };
+        let password = password.to_owned();
+        let valid = tokio::task::spawn_blocking(move || verify_password(&password, &hash))
+            .await
+            .unwrap_or(false);
+        if valid {
+            self.cache_insert(key);
+        }
+        valid
+    }
+
+    fn is_cached(&self, key: &[u8; 32]) -> bool {
+        let cache = self.cache.lock().unwrap();
+        matches!(cache.get(key), Some(at) if at.elapsed() < self.cache_ttl)
+    }
+
+    fn cache_insert(&self, key: [u8; 32]) {
+        if self.cache_ttl.is_zero() {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.retain(|_, at| at.elapsed() < self.cache_ttl);
+            if cache.len() >= MAX_CACHE_ENTRIES {
+                cache.clear();
+            }
+        }
+        cache.insert(key, Instant::now());
+    }
+
+    /// Returns the password hash of the given user if known.
+    pub fn password_hash(&self, userid: &str) -> Option<String> {
+        self.users.read().unwrap().get(userid).cloned()
+    }
+
+    /// Returns a task reloading the htpasswd file if any when it changes, checking it every `interval`.
+    /// The file is checked and read on the blocking threads so requests are never delayed by it.
+    pub fn watch(&self, interval: Duration) -> Option<impl Future<Output = ()> + Send + 'static> {
+        let file = self.file.clone()?;
+        let users = self.users.clone();
+        let cache = self.cache.clone();
+        Some(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let (file, users, cache) = (file.clone(), users.clone(), cache.clone());
+                if tokio::task::spawn_blocking(move || reload_if_changed(&file, &users, &cache))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        })
+    }
+}
+
+/// Reloads an htpasswd file if it was modified since it was last read.
+/// The current users are kept if the file can not be read, whereas the users not defined
+/// in the file are always kept.
+fn reload_if_changed(
+    file: &HtpasswdFile,
+    users: &RwLock<HashMap<String, String>>,
+    cache: &Mutex<HashMap<[u8; 32], Instant>>,
+) {
+    let modified = std::fs::metadata(&file.path)
+        .and_then(|m| m.modified())
+        .ok();
+    let mut current = file.modified.lock().unwrap();
+    if modified.is_none() || modified == *current {
+        return;
+    }
+
+    match read_htpasswd(&file.path) {
+        Ok(new_users) => {
+            tracing::info!(
+                "basic authentication: htpasswd file reloaded with {} users",
+                new_users.len()
+            );
+            let mut all_users = file.other_users.clone();
+            all_users.extend(new_users);
+            *users.write().unwrap() = all_users;
+            cache.lock().unwrap().clear();
+            *current = modified;
+        }
+        Err(err) => tracing::error!(
+            "basic authentication: unable to reload the htpasswd file: {:?}",
+            err
+        ),
+    }
+}
+
+/// Reads the `user-id:password` lines of an htpasswd file.
+fn read_htpasswd(path: &Path) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read htpasswd file {}", path.display()))?;
+    parse_htpasswd(&content)
+        .with_context(|| format!("failed to parse htpasswd file {}", path.display()))
+}
+
+/// Parses the `user-id:password` lines of an htpasswd file content.
+/// Empty lines and comments (`#`) are ignored.
+pub fn parse_htpasswd(content: &str) -> Result<HashMap<String, String>> {
+    let mut users = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((userid, hash)) if !userid.is_empty() && !hash.is_empty() => {
+                users.insert(userid.to_owned(), hash.to_owned());
+            }
+            _ => bail!("invalid `user-id:password` entry at line {}", i + 1),
+        }
+    }
+    Ok(users)
+}
+
+/// Computes the Apache MD5 (`$apr1$`) hash of a password.
+fn apr1_md5(password: &str, salt: &str) -> String {
+    let password = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(8)];
+
+    let alternate = Md5::new()
+        .chain(password)
+        .chain(salt)
+        .chain(password)
+        .finalize();
+
+    let mut ctx = Md5::new()
+        .chain(password)
+        .chain(APR1_MAGIC.as_bytes())
+        .chain(salt);
+    for chunk in password.chunks(16) {
+        ctx.update(&alternate[..chunk.len()]);
+    }
+    let mut i = password.len();
+    while i > 0 {
+        if i & 1 == 1 {
+            ctx.update([0u8]);
+        } else {
+            ctx.update(&password[..1]);
+        }
+        i >>= 1;
+    }
+    let mut digest = ctx.finalize();
+
+    for i in 0..1000 {
+        let mut ctx = Md5::new();
+        if i & 1 == 1 {
+            ctx.update(password);
+        } else {
+            ctx.update(digest);
+        }
+        if i % 3 != 0 {
+            ctx.update(salt);
+        }
+        if i % 7 != 0 {
+            ctx.update(password);
+        }
+        if i & 1 == 1 {
+            ctx.update(digest);
+        } else {
+            ctx.update(password);
+        }
+        digest = ctx.finalize();
+    }
+
+    const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let mut encoded = String::with_capacity(22);
+    let mut encode = |value: u32, len: usize| {
+        let mut value = value;
+        for _ in 0..len {
+            encoded.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        encode(
+            (digest[a] as u32) << 16 | (digest[b] as u32) << 8 | digest[c] as u32,
+            4,
+        );
+    }
+    encode(digest[11] as u32, 2);
+
+    format!(
+        "{}{}${}",
+        APR1_MAGIC,
+        String::from_utf8_lossy(salt),
+        encoded
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use super::check_request;
+    use super::*;
     use headers::HeaderMap;
 
     #[test]
@@ -106,4 +469,113 @@ mod tests {
         )
         .is_err());
     }
+
+    #[test]
+    fn test_password_hash_formats() {
+        assert!(verify_password(
+            "secret",
+            "$apr1$Vb6wRpeu$zxVfkAW5O8xnk.AFaFTYx0"
+        ));
+        assert!(verify_password("pass", "$apr1$ab$n.gRySpeF4Zg.rR21jU30."));
+        assert!(verify_password(
+            "secret",
+            "$5$saltsalt$0IyaXrmV7.sGNS6tirgqHLqX/G.FBvgkYA.lpPdS5sA"
+        ));
+        assert!(verify_password(
+            "secret",
+            "$6$saltsalt$TVLlQcbpFVof5W3Yz4DTP6gRstiNuHwwTt6GLc1E5n0U0aDehy0S5knV8wiOQSpT0Y77vwPZN.Pq.H91p5hVO1"
+        ));
+        assert!(verify_password(
+            "secret",
+            "$1$saltsalt$9xy1btjgzLYfb7hivXtC//"
+        ));
+
+        assert!(!verify_password(
+            "other",
+            "$apr1$Vb6wRpeu$zxVfkAW5O8xnk.AFaFTYx0"
+        ));
+        assert!(!verify_password(
+            "other",
+            "$6$saltsalt$TVLlQcbpFVof5W3Yz4DTP6gRstiNuHwwTt6GLc1E5n0U0aDehy0S5knV8wiOQSpT0Y77vwPZN.Pq.H91p5hVO1"
+        ));
+        assert!(!verify_password(
+            "secret",
+            "{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ="
+        ));
+        assert!(!verify_password("secret", "secret"));
+    }
+
+    #[test]
+    fn test_htpasswd_parsing() {
+        let users = parse_htpasswd(
+            "# staging users\njq:$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q\n\nabc:$apr1$ab$n.gRySpeF4Zg.rR21jU30.\n",
+        )
+        .unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(users["abc"], "$apr1$ab$n.gRySpeF4Zg.rR21jU30.");
+
+        assert!(parse_htpasswd("jq").is_err());
+        assert!(parse_htpasswd(":hash").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_user_store_reload() {
+        let path =
+            std::env::temp_dir().join(format!("sws-basic-auth-htpasswd-{}", std::process::id()));
+        std::fs::write(&path, "abc:$apr1$ab$n.gRySpeF4Zg.rR21jU30.\n").unwrap();
+
+        let store = UserStore::new(
+            Some("jq:$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q"),
+            Some(&path),
+        )
+        .unwrap();
+        assert_eq!(store.len(), 2);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Basic anE6anE=".parse().unwrap());
+        assert_eq!(store.check_request(&headers).await.unwrap(), "jq");
+        // abc:pass
+        headers.insert("Authorization", "Basic YWJjOnBhc3M=".parse().unwrap());
+        assert_eq!(store.check_request(&headers).await.unwrap(), "abc");
+
+        // Force a reload with a new modification time
+        std::fs::write(&path, "abc:$apr1$Vb6wRpeu$zxVfkAW5O8xnk.AFaFTYx0\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        reload_if_changed(store.file.as_ref().unwrap(), &store.users, &store.cache);
+
+        assert!(store.check_request(&headers).await.is_err());
+        assert_eq!(store.len(), 2);
+        // The `--basic-auth` user is kept
+        headers.insert("Authorization", "Basic anE6anE=".parse().unwrap());
+        assert_eq!(store.check_request(&headers).await.unwrap(), "jq");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verified_credentials_cache() {
+        let store = UserStore::new(
+            Some("jq:$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q"),
+            None,
+        )
+        .unwrap()
+        .with_max_verifications(1);
+
+        assert!(!store.verify("jq", "wrong").await);
+        assert!(store.cache.lock().unwrap().is_empty());
+        assert!(store.verify("jq", "jq").await);
+        assert_eq!(store.cache.lock().unwrap().len(), 1);
+        // Served from the cache
+        assert!(store.verify("jq", "jq").await);
+        assert!(!store.verify("jq", "jq2").await);
+        assert!(!store.verify("abc", "jq").await);
+
+        let store = UserStore::new(Some("jq:$apr1$ab$n.gRySpeF4Zg.rR21jU30."), None)
+            .unwrap()
+            .with_cache_ttl(Duration::ZERO);
+        assert!(store.verify("jq", "pass").await);
+        assert!(store.cache.lock().unwrap().is_empty());
+    }
 }
@@ -15,23 +15,48 @@ static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 use static_web_server::Result;
 
 fn main() -> Result {
-    #[cfg(windows)]
+    #[cfg(any(windows, feature = "compression", feature = "signed-urls"))]
     {
         use static_web_server::settings::{Commands, Settings};
+        #[cfg(windows)]
         use static_web_server::winservice;
 
         let opts = Settings::get()?;
 
         if let Some(commands) = opts.general.commands {
             match commands {
+                #[cfg(windows)]
                 Commands::Install {} => {
                     return winservice::install_service(opts.general.config_file);
                 }
+                #[cfg(windows)]
                 Commands::Uninstall {} => {
                     return winservice::uninstall_service();
                 }
+                #[cfg(feature = "compression")]
+                Commands::Precompress { dir, encodings } => {
+                    let dir = dir.unwrap_or(opts.general.root);
+                    return static_web_server::precompress::run(&dir, &encodings);
+                }
+                #[cfg(feature = "signed-urls")]
+                Commands::SignUrl {
+                    path,
+                    expires_in,
+                    ip,
+                    base_url,
+                } => {
+                    use static_web_server::signed_urls::{self, UrlSigner};
+                    let signer = UrlSigner::from_options(
+                        opts.general.signed_urls_key.trim(),
+                        opts.general.signed_urls_key_file.as_deref(),
+                    )?;
+                    return signed_urls::run(signer, &path, expires_in, ip, &base_url);
+                }
             }
-        } else if opts.general.windows_service {
+        }
+
+        #[cfg(windows)]
+        if opts.general.windows_service {
             return winservice::run_server_as_service();
         }
     }
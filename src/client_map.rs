@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module providing a bounded map of per-client states used by the request limiters.
+//!
+//! The map is split into shards, each one guarded by its own lock and evicting
+//! its least recently used entry once full. So every operation takes constant time
+//! no matter how many distinct clients (e.g. rotating IP addresses) show up.
+//!
+
+use lru::LruCache;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+/// Number of shards of a map.
+const SHARDS: usize = 16;
+
+/// It defines a bounded map of per-client states.
+pub struct ClientMap<K, V> {
+    shards: Vec<Mutex<LruCache<K, V>>>,
+    hasher: RandomState,
+}
+
+impl<K: Hash + Eq, V> ClientMap<K, V> {
+    /// Creates a new map holding up to `capacity` entries (rounded up to the number of shards).
+    pub fn new(capacity: usize) -> Self {
+        let shard_capacity =
+            NonZeroUsize::new(capacity.div_ceil(SHARDS)).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(LruCache::new(shard_capacity)))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Calls `f` with the entry of a key, inserting the `init` value first if the key is unknown.
+    /// The least recently used entry of the shard is evicted if needed.
+    pub fn with<R>(&self, key: K, init: impl FnOnce() -> V, f: impl FnOnce(&mut V) -> R) -> R {
+        let mut shard = self.shard(&key).lock().unwrap();
+        f(shard.get_or_insert_mut(key, init))
+    }
+
+    /// Calls `f` with the entry of a key if present.
+    pub fn get_with<Q, R>(&self, key: &Q, f: impl FnOnce(&mut V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut shard = self.shard(key).lock().unwrap();
+        shard.get_mut(key).map(f)
+    }
+
+    /// Removes the entry of a key.
+    pub fn remove<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).lock().unwrap().pop(key);
+    }
+
+    /// Returns the number of entries of the map.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.lock().unwrap().len()).sum()
+    }
+
+    /// Checks whether the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> &Mutex<LruCache<K, V>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        &self.shards[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_entries() {
+        let map = ClientMap::new(64);
+        for i in 0..10_000u32 {
+            map.with(i, || 0, |v| *v += 1);
+        }
+        assert!(map.len() <= 64);
+        // The most recent entries are kept
+        assert_eq!(map.get_with(&9_999, |v| *v), Some(1));
+
+        map.with(9_999, || 0, |v| *v += 1);
+        assert_eq!(map.get_with(&9_999, |v| *v), Some(2));
+        map.remove(&9_999);
+        assert_eq!(map.get_with(&9_999, |v| *v), None);
+    }
+}
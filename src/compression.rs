@@ -21,13 +21,14 @@ use bytes::Bytes;
 use futures_util::Stream;
 use headers::{AcceptEncoding, ContentCoding, ContentType, HeaderMap, HeaderMapExt};
 use hyper::{
-    header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH},
+    header::{HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH},
     Body, Method, Response,
 };
 use mime_guess::Mime;
 use pin_project::pin_project;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use tokio::io::{AsyncBufRead, AsyncReadExt};
 use tokio_util::io::{ReaderStream, StreamReader};
 
 use crate::{exts::http::MethodExt, Result};
@@ -60,6 +61,38 @@ pub const TEXT_MIME_TYPES: [&str; 24] = [
     "application/wasm",
 ];
 
+/// The `Available-Dictionary` request header of the Compression Dictionary Transport.
+pub const AVAILABLE_DICTIONARY: HeaderName = HeaderName::from_static("available-dictionary");
+
+/// The `Use-As-Dictionary` response header of the Compression Dictionary Transport.
+pub const USE_AS_DICTIONARY: HeaderName = HeaderName::from_static("use-as-dictionary");
+
+/// Magic number prefixing a Dictionary-Compressed Brotli (`dcb`) stream.
+pub const DCB_MAGIC: [u8; 4] = [0xff, 0x44, 0x43, 0x42];
+
+/// Magic number prefixing a Dictionary-Compressed Zstandard (`dcz`) stream.
+pub const DCZ_MAGIC: [u8; 8] = [0x5e, 0x2a, 0x4d, 0x18, 0x20, 0x00, 0x00, 0x00];
+
+/// Content codings of the Compression Dictionary Transport.
+/// See <https://datatracker.ietf.org/doc/rfc9842/>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictionaryCoding {
+    /// Dictionary-Compressed Brotli.
+    Dcb,
+    /// Dictionary-Compressed Zstandard.
+    Dcz,
+}
+
+impl DictionaryCoding {
+    /// Returns the content coding name which is also used as the pre-compressed file extension.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DictionaryCoding::Dcb => "dcb",
+            DictionaryCoding::Dcz => "dcz",
+        }
+    }
+}
+
 /// Create a wrapping handler that compresses the Body of a [`Response`](hyper::Response)
 /// using `gzip`, `deflate`, `brotli` or `zstd` if is specified in the `Accept-Encoding` header, adding
 /// `content-encoding: <coding>` to the Response's [`HeaderMap`](hyper::HeaderMap)
@@ -79,7 +112,7 @@ pub fn auto(
         // Skip compression for non-text-based MIME types
         if let Some(content_type) = resp.headers().typed_get::<ContentType>() {
             let mime = Mime::from(content_type);
-            if !TEXT_MIME_TYPES.iter().any(|h| *h == mime) {
+            if !is_compressible_mime(&mime) {
                 return Ok(resp);
             }
         }
@@ -188,6 +221,100 @@ pub fn zstd(
     Response::from_parts(head, body)
 }
 
+/// Compress the given bytes reader using `gzip`, `deflate`, `brotli` or `zstd`
+/// and return the resulting bytes or `None` if the content coding is not supported.
+pub async fn compress_reader<R: AsyncBufRead + Unpin>(
+    coding: ContentCoding,
+    _reader: R,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+
+    #[cfg(feature = "compression-gzip")]
+    if coding == ContentCoding::GZIP {
+        GzipEncoder::new(_reader).read_to_end(&mut buf).await?;
+        return Ok(Some(buf));
+    }
+
+    #[cfg(feature = "compression-deflate")]
+    if coding == ContentCoding::DEFLATE {
+        DeflateEncoder::new(_reader).read_to_end(&mut buf).await?;
+        return Ok(Some(buf));
+    }
+
+    #[cfg(feature = "compression-brotli")]
+    if coding == ContentCoding::BROTLI {
+        BrotliEncoder::new(_reader).read_to_end(&mut buf).await?;
+        return Ok(Some(buf));
+    }
+
+    #[cfg(feature = "compression-zstd")]
+    if coding == ContentCoding::ZSTD {
+        ZstdEncoder::new(_reader).read_to_end(&mut buf).await?;
+        return Ok(Some(buf));
+    }
+
+    tracing::trace!("content coding {} is not supported", coding.to_static());
+    Ok(None)
+}
+
+/// Compress the given bytes using the given dictionary and return the resulting `dcb` or `dcz` stream
+/// (including its magic number and dictionary hash header) or `None` if the content coding is not supported.
+pub fn compress_with_dictionary(
+    coding: DictionaryCoding,
+    _dictionary: &[u8],
+    _hash: &[u8; 32],
+    _data: &[u8],
+) -> std::io::Result<Option<Vec<u8>>> {
+    #[cfg(feature = "compression-brotli")]
+    if coding == DictionaryCoding::Dcb {
+        let mut buf = Vec::with_capacity(DCB_MAGIC.len() + _hash.len() + _data.len() / 2);
+        buf.extend_from_slice(&DCB_MAGIC);
+        buf.extend_from_slice(_hash);
+
+        // Make the window big enough to reference the whole dictionary (up to 16 MiB)
+        let mut params = brotli::enc::BrotliEncoderParams::default();
+        let window = (_dictionary.len() + _data.len()).next_power_of_two();
+        params.lgwin = (window.trailing_zeros() as i32).clamp(params.lgwin, 24);
+        let mut nop_callback =
+            |_: &mut brotli::interface::PredictionModeContextMap<brotli::InputReferenceMut<'_>>,
+             _: &mut [brotli::interface::StaticCommand],
+             _: brotli::InputPair<'_>,
+             _: &mut brotli::enc::StandardAlloc| ();
+        brotli::BrotliCompressCustomIoCustomDict(
+            &mut brotli::IoReaderWrapper(&mut &_data[..]),
+            &mut brotli::IoWriterWrapper(&mut buf),
+            &mut [0; 4096],
+            &mut [0; 4096],
+            &params,
+            brotli::enc::StandardAlloc::default(),
+            &mut nop_callback,
+            _dictionary,
+            std::io::Error::from(std::io::ErrorKind::UnexpectedEof),
+        )?;
+        return Ok(Some(buf));
+    }
+
+    #[cfg(feature = "compression-zstd")]
+    if coding == DictionaryCoding::Dcz {
+        let mut compressor = zstd::bulk::Compressor::with_dictionary(0, _dictionary)?;
+        let compressed = compressor.compress(_data)?;
+
+        let mut buf = Vec::with_capacity(DCZ_MAGIC.len() + _hash.len() + compressed.len());
+        buf.extend_from_slice(&DCZ_MAGIC);
+        buf.extend_from_slice(_hash);
+        buf.extend_from_slice(&compressed);
+        return Ok(Some(buf));
+    }
+
+    tracing::trace!("content coding {} is not supported", coding.as_str());
+    Ok(None)
+}
+
+/// Checks if the given MIME type is part of the text-based MIME types list to compress.
+pub fn is_compressible_mime(mime: &Mime) -> bool {
+    TEXT_MIME_TYPES.iter().any(|h| *h == *mime)
+}
+
 /// Given an optional existing encoding header, appends to the existing or creates a new one.
 pub fn create_encoding_header(existing: Option<HeaderValue>, coding: ContentCoding) -> HeaderValue {
     if let Some(val) = existing {
@@ -207,6 +334,53 @@ pub fn get_prefered_encoding(headers: &HeaderMap<HeaderValue>) -> Option<Content
     None
 }
 
+/// Try to get the SHA-256 hash of the dictionary available on the client via the `available-dictionary` header.
+/// The header value is a structured field byte sequence. E.g `:pZGm1Av0IEBKARczz7exkNYsZb8LzaMrV7J32a2fFG4=:`
+pub fn get_available_dictionary(headers: &HeaderMap<HeaderValue>) -> Option<[u8; 32]> {
+    use base64::Engine;
+
+    let value = headers.get(AVAILABLE_DICTIONARY)?.to_str().ok()?.trim();
+    let value = value.strip_prefix(':')?.strip_suffix(':')?;
+    let hash = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .ok()?;
+    hash.try_into().ok()
+}
+
+/// Get the supported dictionary content codings accepted via the `accept-encoding` header
+/// ordered by their quality values. On equal quality, `dcz` is preferred since it is cheaper to compute.
+pub fn get_dictionary_encodings(headers: &HeaderMap<HeaderValue>) -> Vec<DictionaryCoding> {
+    let mut codings: Vec<(DictionaryCoding, f32)> = Vec::new();
+    for value in headers.get_all(ACCEPT_ENCODING) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        for item in value.split(',') {
+            let mut parts = item.split(';');
+            let coding = match parts.next().map(|s| s.trim().to_ascii_lowercase()) {
+                #[cfg(feature = "compression-brotli")]
+                Some(s) if s == "dcb" => DictionaryCoding::Dcb,
+                #[cfg(feature = "compression-zstd")]
+                Some(s) if s == "dcz" => DictionaryCoding::Dcz,
+                _ => continue,
+            };
+            let quality = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && !codings.iter().any(|(c, _)| *c == coding) {
+                codings.push((coding, quality));
+            }
+        }
+    }
+    codings.sort_by(|a, b| {
+        b.1.total_cmp(&a.1)
+            .then_with(|| (a.0 == DictionaryCoding::Dcb).cmp(&(b.0 == DictionaryCoding::Dcb)))
+    });
+    codings.into_iter().map(|(c, _)| c).collect()
+}
+
 /// A wrapper around any type that implements [`Stream`](futures_util::Stream) to be
 /// compatible with async_compression's `Stream` based encoders.
 #[pin_project]
@@ -0,0 +1,408 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Compression cache module to reuse dynamically compressed response bodies.
+//!
+
+use bytes::Bytes;
+use headers::{ContentCoding, ContentLength, ContentType, HeaderMap, HeaderMapExt, HeaderValue};
+use hyper::{
+    header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    Body, Method, Response, StatusCode,
+};
+use mime_guess::Mime;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    compression::{self, DictionaryCoding},
+    exts::http::MethodExt,
+    Result,
+};
+
+/// It defines a cache of dynamically compressed response bodies
+/// keyed by file path and content coding and validated by the file modification time and size.
+pub struct CompressionCache {
+    /// Maximum size in bytes of the in-memory entries.
+    max_size: u64,
+    /// Optional directory where compressed entries are persisted.
+    dir: Option<PathBuf>,
+    /// In-memory entries.
+    entries: Mutex<Entries>,
+}
+
+/// Cache key made of the file path, the content coding and the SHA-256 hash
+/// of the dictionary used by dictionary-compressed entries if any.
+type Key = (PathBuf, &'static str, Option<[u8; 32]>);
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    size: u64,
+    tick: u64,
+}
+
+/// The source file version a compressed entry was created from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Version {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Version {
+    /// Header prefixed to the persisted entries, e.g. `sws-cc 1690000000.000000000 1024\n`.
+    fn header(&self) -> Option<String> {
+        let modified = self.modified.duration_since(UNIX_EPOCH).ok()?;
+        Some(format!(
+            "sws-cc {}.{:09} {}\n",
+            modified.as_secs(),
+            modified.subsec_nanos(),
+            self.len
+        ))
+    }
+}
+
+struct Entry {
+    version: Version,
+    data: Bytes,
+    last_used: u64,
+}
+
+impl Entries {
+    fn get(&mut self, key: &Key, version: Version) -> Option<Bytes> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.map.get_mut(key) {
+            // Entries are only valid for the exact source file they were created from,
+            // so replacing a file by an older one also invalidates them
+            Some(entry) if entry.version == version => {
+                entry.last_used = tick;
+                Some(entry.data.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, key: Key, version: Version, data: Bytes, max_size: u64) {
+        let len = data.len() as u64;
+        if len > max_size {
+            return;
+        }
+
+        self.tick += 1;
+        let entry = Entry {
+            version,
+            data,
+            last_used: self.tick,
+        };
+        if let Some(old) = self.map.insert(key, entry) {
+            self.size -= old.data.len() as u64;
+        }
+        self.size += len;
+
+        // Evict the least recently used entries until the size limit is satisfied
+        while self.size > max_size {
+            let lru_key = match self.map.iter().min_by_key(|(_, e)| e.last_used) {
+                Some((k, _)) => k.clone(),
+                None => break,
+            };
+            if let Some(old) = self.map.remove(&lru_key) {
+                tracing::trace!(
+                    "compression cache: evicting entry for {}",
+                    lru_key.0.display()
+                );
+                self.size -= old.data.len() as u64;
+            }
+        }
+    }
+}
+
+impl CompressionCache {
+    /// Creates a new compression cache with a maximum in-memory size in bytes
+    /// and an optional directory to persist the compressed entries.
+    pub fn new(max_size: u64, dir: Option<PathBuf>) -> Self {
+        Self {
+            max_size,
+            dir,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Same as [`compression::auto`] but it reuses a previously compressed body
+    /// of the given file path when available, otherwise it compresses the whole body
+    /// and stores it in the cache.
+    /// Responses that can not be cached (partial content, unknown files or files bigger than the cache)
+    /// are compressed on the fly via [`compression::auto`] instead.
+    pub async fn auto(
+        &self,
+        method: &Method,
+        headers: &HeaderMap<HeaderValue>,
+        file_path: &Path,
+        resp: Response<Body>,
+    ) -> Result<Response<Body>> {
+        // Skip compression for HEAD and OPTIONS request methods
+        if method.is_head() || method.is_options() {
+            return Ok(resp);
+        }
+
+        let coding = match compression::get_prefered_encoding(headers) {
+            Some(coding) => coding,
+            None => return Ok(resp),
+        };
+
+        // Skip compression for non-text-based MIME types
+        if let Some(content_type) = resp.headers().typed_get::<ContentType>() {
+            if !compression::is_compressible_mime(&Mime::from(content_type)) {
+                return Ok(resp);
+            }
+        }
+
+        // Only complete file responses are cacheable
+        let meta = match tokio::fs::metadata(file_path).await {
+            Ok(meta) if meta.is_file() => meta,
+            _ => return compression::auto(method, headers, resp),
+        };
+        let version = match meta.modified() {
+            Ok(modified) => Version {
+                modified,
+                len: meta.len(),
+            },
+            Err(_) => return compression::auto(method, headers, resp),
+        };
+        if resp.status() != StatusCode::OK
+            || resp.headers().contains_key(CONTENT_ENCODING)
+            || meta.len() > self.max_size
+        {
+            return compression::auto(method, headers, resp);
+        }
+
+        let key = (file_path.to_owned(), coding.to_static(), None);
+
+        if let Some(data) = self.get(&key, version).await {
+            tracing::trace!(
+                "compression cache: serving cached {} body of {}",
+                key.1,
+                file_path.display()
+            );
+            return Ok(cached_response(resp, coding, data));
+        }
+
+        let (head, body) = resp.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let data = match compression::compress_reader(coding, &body[..]).await? {
+            Some(data) => Bytes::from(data),
+            None => return Ok(Response::from_parts(head, Body::from(body))),
+        };
+
+        tracing::trace!(
+            "compression cache: storing {} body of {}",
+            key.1,
+            file_path.display()
+        );
+        self.store(key, version, data.clone()).await;
+
+        Ok(cached_response(
+            Response::from_parts(head, Body::empty()),
+            coding,
+            data,
+        ))
+    }
+
+    /// Returns the body of the given file compressed with a dictionary of the given SHA-256 hash,
+    /// reusing a previously compressed body when available.
+    /// It returns `None` if the file can not be cached (unknown files or files bigger than the cache)
+    /// or if compression does not reduce its size.
+    pub async fn dictionary_compress(
+        &self,
+        file_path: &Path,
+        coding: DictionaryCoding,
+        dictionary: Bytes,
+        hash: [u8; 32],
+    ) -> Result<Option<Bytes>> {
+        let meta = tokio::fs::metadata(file_path).await?;
+        if !meta.is_file() || meta.len() > self.max_size {
+            return Ok(None);
+        }
+        let version = Version {
+            modified: meta.modified()?,
+            len: meta.len(),
+        };
+
+        let key = (file_path.to_owned(), coding.as_str(), Some(hash));
+        if let Some(data) = self.get(&key, version).await {
+            tracing::trace!(
+                "compression cache: serving cached {} body of {}",
+                key.1,
+                file_path.display()
+            );
+            return Ok(Some(data));
+        }
+
+        let data = tokio::fs::read(file_path).await?;
+        let compressed = tokio::task::spawn_blocking(move || {
+            compression::compress_with_dictionary(coding, &dictionary, &hash, &data)
+        })
+        .await??;
+        let data = match compressed {
+            Some(data) => Bytes::from(data),
+            None => return Ok(None),
+        };
+
+        tracing::trace!(
+            "compression cache: storing {} body of {}",
+            key.1,
+            file_path.display()
+        );
+        self.store(key, version, data.clone()).await;
+
+        Ok(Some(data))
+    }
+
+    /// Stores a compressed entry in memory and in the cache directory if any.
+    async fn store(&self, key: Key, version: Version, data: Bytes) {
+        self.persist(&key, version, &data).await;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, version, data, self.max_size);
+    }
+
+    /// Looks up a compressed entry in memory first and then in the cache directory if any.
+    async fn get(&self, key: &Key, version: Version) -> Option<Bytes> {
+        if let Some(data) = self.entries.lock().unwrap().get(key, version) {
+            return Some(data);
+        }
+
+        let path = self.entry_path(key)?;
+        let header = version.header()?;
+        let mut data = Bytes::from(tokio::fs::read(&path).await.ok()?);
+        // A persisted entry is only valid if its header matches the current source file exactly
+        if !data.starts_with(header.as_bytes()) {
+            return None;
+        }
+        let data = data.split_off(header.len());
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), version, data.clone(), self.max_size);
+
+        Some(data)
+    }
+
+    /// Writes a compressed entry into the cache directory if any.
+    async fn persist(&self, key: &Key, version: Version, data: &[u8]) {
+        static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let (path, header) = match (self.entry_path(key), version.header()) {
+            (Some(path), Some(header)) => (path, header),
+            _ => return,
+        };
+        // Concurrent writers of the same entry use their own temporary file
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut buf = Vec::with_capacity(header.len() + data.len());
+        buf.extend_from_slice(header.as_bytes());
+        buf.extend_from_slice(data);
+        let result = match tokio::fs::write(&tmp_path, buf).await {
+            Ok(_) => tokio::fs::rename(&tmp_path, &path).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            tracing::warn!(
+                "compression cache: unable to persist entry {}: {:?}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    /// Returns the cache directory file path of a compressed entry.
+    fn entry_path(&self, key: &Key) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Some(dir.join(format!("{:016x}.{}", hasher.finish(), key.1)))
+    }
+}
+
+/// Replaces the response body with the compressed data along with its corresponding headers.
+fn cached_response(resp: Response<Body>, coding: ContentCoding, data: Bytes) -> Response<Body> {
+    let (mut head, _) = resp.into_parts();
+    let header = compression::create_encoding_header(head.headers.remove(CONTENT_ENCODING), coding);
+    head.headers.remove(CONTENT_LENGTH);
+    head.headers.append(CONTENT_ENCODING, header);
+    head.headers.typed_insert(ContentLength(data.len() as u64));
+    Response::from_parts(head, Body::from(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_eviction() {
+        let mut entries = Entries::default();
+        let version = Version {
+            modified: SystemTime::now(),
+            len: 4,
+        };
+        let key_a = (PathBuf::from("a.js"), "br", None);
+        let key_b = (PathBuf::from("b.js"), "br", None);
+        let key_c = (PathBuf::from("c.js"), "br", None);
+
+        entries.insert(key_a.clone(), version, Bytes::from(vec![0; 4]), 10);
+        entries.insert(key_b.clone(), version, Bytes::from(vec![0; 4]), 10);
+        assert!(entries.get(&key_a, version).is_some());
+
+        // `b.js` is the least recently used entry so it gets evicted
+        entries.insert(key_c.clone(), version, Bytes::from(vec![0; 4]), 10);
+        assert!(entries.get(&key_a, version).is_some());
+        assert!(entries.get(&key_b, version).is_none());
+        assert!(entries.get(&key_c, version).is_some());
+        assert_eq!(entries.size, 8);
+
+        // Entries bigger than the maximum size are never stored
+        entries.insert(key_b.clone(), version, Bytes::from(vec![0; 11]), 10);
+        assert!(entries.get(&key_b, version).is_none());
+    }
+
+    #[test]
+    fn entries_version() {
+        let mut entries = Entries::default();
+        let version = Version {
+            modified: SystemTime::now(),
+            len: 4,
+        };
+        let key = (PathBuf::from("a.js"), "gzip", None);
+
+        entries.insert(key.clone(), version, Bytes::from(vec![0; 4]), 10);
+        let newer = Version {
+            modified: version.modified + std::time::Duration::from_secs(1),
+            ..version
+        };
+        assert!(entries.get(&key, newer).is_none());
+        // A file replaced by an older one must not reuse the entry either
+        let older = Version {
+            modified: version.modified - std::time::Duration::from_secs(1),
+            ..version
+        };
+        assert!(entries.get(&key, older).is_none());
+        let resized = Version { len: 5, ..version };
+        assert!(entries.get(&key, resized).is_none());
+        assert!(entries.get(&key, version).is_some());
+    }
+}
@@ -0,0 +1,372 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Compression dictionary transport module to serve dictionary-compressed (`dcb` and `dcz`) responses.
+//! See <https://datatracker.ietf.org/doc/rfc9842/>
+//!
+
+use bytes::Bytes;
+use headers::{ContentLength, ContentType, HeaderMap, HeaderMapExt, HeaderValue};
+use hyper::{
+    header::{CONTENT_ENCODING, CONTENT_LENGTH},
+    Body, Method, Response, StatusCode,
+};
+use mime_guess::Mime;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::{
+    compression::{self, DictionaryCoding},
+    compression_cache::CompressionCache,
+    compression_static,
+    exts::http::MethodExt,
+    settings::CompressionDictionaries,
+    Context, Result,
+};
+
+/// Maximum number of dictionaries kept in memory.
+pub const MAX_DICTIONARIES: usize = 256;
+
+/// Maximum size in bytes of a dictionary file as well as of a file compressed on the fly
+/// when no compression cache is configured.
+pub const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// It defines the dictionaries known by the server along with the rules
+/// describing which resources are advertised as dictionaries.
+pub struct DictionaryStore {
+    /// Dictionary rules.
+    rules: Vec<CompressionDictionaries>,
+    /// Known dictionaries.
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    /// Dictionary contents by their SHA-256 hash.
+    dictionaries: HashMap<[u8; 32], Bytes>,
+    /// Dictionary file paths along with their modification time and hash.
+    files: HashMap<PathBuf, (SystemTime, [u8; 32])>,
+}
+
+impl Entries {
+    fn insert(&mut self, path: PathBuf, modified: SystemTime, data: Bytes) -> Option<[u8; 32]> {
+        if self.files.len() >= MAX_DICTIONARIES && !self.files.contains_key(&path) {
+            return None;
+        }
+
+        let mut hash = [0; 32];
+        hash.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, &data).as_ref());
+
+        if let Some((_, old_hash)) = self.files.insert(path, (modified, hash)) {
+            // Drop the outdated dictionary content unless another file still refers to it
+            if old_hash != hash && !self.files.values().any(|(_, h)| *h == old_hash) {
+                self.dictionaries.remove(&old_hash);
+            }
+        }
+        self.dictionaries.insert(hash, data);
+        Some(hash)
+    }
+}
+
+impl DictionaryStore {
+    /// Creates a new dictionary store for the given rules.
+    pub fn new(rules: Vec<CompressionDictionaries>) -> Self {
+        Self {
+            rules,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Walk the given root directory recursively and register every file
+    /// matching a dictionary rule. It returns the number of dictionaries found.
+    pub fn load_dir(&self, root: &Path) -> Result<usize> {
+        let mut count = 0;
+        let mut dirs = vec![root.to_owned()];
+
+        while let Some(dir) = dirs.pop() {
+            let entries = std::fs::read_dir(&dir)
+                .with_context(|| format!("failed to read directory {}", dir.display()))?;
+
+            for entry in entries {
+                let entry = entry?;
+                // NOTE: symbolic links are not followed
+                let file_type = entry.file_type()?;
+                let path = entry.path();
+                if file_type.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                if !file_type.is_file() {
+                    continue;
+                }
+
+                let uri_path = match path.strip_prefix(root) {
+                    Ok(rel) => rel.components().fold(String::new(), |mut uri, c| {
+                        uri.push('/');
+                        uri.push_str(&c.as_os_str().to_string_lossy());
+                        uri
+                    }),
+                    Err(_) => continue,
+                };
+                if self.rule(&uri_path).is_none() {
+                    continue;
+                }
+                let meta = entry.metadata()?;
+                if meta.len() > MAX_FILE_SIZE {
+                    tracing::warn!(
+                        "compression dictionary: skipping {} bigger than {} bytes",
+                        path.display(),
+                        MAX_FILE_SIZE
+                    );
+                    continue;
+                }
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("failed to read dictionary {}", path.display()))?;
+                let registered = self.entries.lock().unwrap().insert(
+                    path.clone(),
+                    meta.modified()?,
+                    Bytes::from(data),
+                );
+                if registered.is_none() {
+                    tracing::warn!(
+                        "compression dictionary: limit of {} dictionaries reached, skipping {}",
+                        MAX_DICTIONARIES,
+                        path.display()
+                    );
+                    continue;
+                }
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the dictionary content of the given SHA-256 hash if known.
+    pub fn get(&self, hash: &[u8; 32]) -> Option<Bytes> {
+        self.entries.lock().unwrap().dictionaries.get(hash).cloned()
+    }
+
+    /// Registers the given file as a dictionary unless it is already up-to-date.
+    /// It returns `None` if the file is too big or the dictionaries limit was reached.
+    async fn register(&self, file_path: &Path) -> std::io::Result<Option<[u8; 32]>> {
+        let meta = tokio::fs::metadata(file_path).await?;
+        let modified = meta.modified()?;
+        if let Some((m, hash)) = self.entries.lock().unwrap().files.get(file_path) {
+            if *m == modified {
+                return Ok(Some(*hash));
+            }
+        }
+        if meta.len() > MAX_FILE_SIZE {
+            return Ok(None);
+        }
+
+        let data = Bytes::from(tokio::fs::read(file_path).await?);
+        tracing::trace!(
+            "compression dictionary: registering {}",
+            file_path.display()
+        );
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .insert(file_path.to_owned(), modified, data))
+    }
+
+    /// Returns the first dictionary rule matching the given URI path.
+    fn rule(&self, uri_path: &str) -> Option<&CompressionDictionaries> {
+        self.rules.iter().find(|r| r.source.is_match(uri_path))
+    }
+
+    /// Appends the `Use-As-Dictionary` header to dictionary resources and replaces
+    /// the response body with a dictionary-compressed (`dcb` or `dcz`) variant
+    /// when the client announces a known dictionary via the `Available-Dictionary` header.
+    /// Pre-compressed dictionary variants are served if `compression_static` is enabled,
+    /// otherwise the body is compressed on the fly if `compression` is enabled
+    /// and reused via the compression `cache` if any.
+    /// Without a cache, only files up to [`MAX_FILE_SIZE`] are compressed on the fly.
+    ///
+    /// It returns the response and whether its body was dictionary-compressed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn auto(
+        &self,
+        method: &Method,
+        headers: &HeaderMap<HeaderValue>,
+        uri_path: &str,
+        file_path: &Path,
+        mut resp: Response<Body>,
+        compression: bool,
+        compression_static: bool,
+        cache: Option<&CompressionCache>,
+    ) -> Result<(Response<Body>, bool)> {
+        if resp.status() != StatusCode::OK {
+            return Ok((resp, false));
+        }
+
+        // Advertise the current resource as a dictionary for future requests
+        if let Some(rule) = self.rule(uri_path) {
+            match self.register(file_path).await {
+                Ok(Some(_)) => {
+                    resp.headers_mut().insert(
+                        compression::USE_AS_DICTIONARY,
+                        rule.use_as_dictionary.clone(),
+                    );
+                }
+                Ok(None) => tracing::warn!(
+                    "compression dictionary: {} is bigger than {} bytes or the limit of {} dictionaries was reached",
+                    file_path.display(),
+                    MAX_FILE_SIZE,
+                    MAX_DICTIONARIES
+                ),
+                Err(err) => tracing::error!(
+                    "compression dictionary: unable to register {}: {:?}",
+                    file_path.display(),
+                    err
+                ),
+            }
+        }
+
+        if !method.is_get() {
+            return Ok((resp, false));
+        }
+
+        let hash = match compression::get_available_dictionary(headers) {
+            Some(hash) => hash,
+            None => return Ok((resp, false)),
+        };
+        let codings = compression::get_dictionary_encodings(headers);
+        if codings.is_empty() {
+            return Ok((resp, false));
+        }
+        let dictionary = match self.get(&hash) {
+            Some(dictionary) => dictionary,
+            None => {
+                tracing::trace!("compression dictionary: unknown available dictionary, skipping");
+                return Ok((resp, false));
+            }
+        };
+
+        // Check for a pre-compressed dictionary variant first
+        if compression_static {
+            for coding in &codings {
+                if let Some(variant) =
+                    compression_static::dictionary_variant(file_path, &hash, *coding)
+                {
+                    tracing::trace!(
+                        "compression dictionary: serving pre-compressed variant {}",
+                        variant.file_path.display()
+                    );
+                    let data = tokio::fs::read(&variant.file_path).await?;
+                    return Ok((encoded_response(resp, *coding, Bytes::from(data)), true));
+                }
+            }
+        }
+
+        if !compression {
+            return Ok((resp, false));
+        }
+
+        // Skip compression for non-text-based MIME types
+        if let Some(content_type) = resp.headers().typed_get::<ContentType>() {
+            if !compression::is_compressible_mime(&Mime::from(content_type)) {
+                return Ok((resp, false));
+            }
+        }
+
+        let coding = codings[0];
+        let compressed = match cache {
+            Some(cache) => {
+                cache
+                    .dictionary_compress(file_path, coding, dictionary, hash)
+                    .await?
+            }
+            None => {
+                if tokio::fs::metadata(file_path).await?.len() > MAX_FILE_SIZE {
+                    return Ok((resp, false));
+                }
+                let data = tokio::fs::read(file_path).await?;
+                tokio::task::spawn_blocking(move || {
+                    compression::compress_with_dictionary(coding, &dictionary, &hash, &data)
+                })
+                .await??
+                .map(Bytes::from)
+            }
+        };
+
+        match compressed {
+            Some(data) => {
+                tracing::trace!(
+                    "compression dictionary: compressing response body on the fly using {}",
+                    coding.as_str()
+                );
+                Ok((encoded_response(resp, coding, data), true))
+            }
+            None => Ok((resp, false)),
+        }
+    }
+}
+
+/// Replaces the response body with the dictionary-compressed data along with its corresponding headers.
+fn encoded_response(resp: Response<Body>, coding: DictionaryCoding, data: Bytes) -> Response<Body> {
+    let (mut head, _) = resp.into_parts();
+    head.headers.remove(CONTENT_LENGTH);
+    head.headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static(coding.as_str()));
+    head.headers.typed_insert(ContentLength(data.len() as u64));
+    Response::from_parts(head, Body::from(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_replace_outdated_dictionary() {
+        let mut entries = Entries::default();
+        let modified = SystemTime::now();
+
+        let hash_v1 = entries
+            .insert(PathBuf::from("app.js"), modified, Bytes::from("v1"))
+            .unwrap();
+        let hash_copy = entries
+            .insert(PathBuf::from("copy.js"), modified, Bytes::from("v1"))
+            .unwrap();
+        assert_eq!(hash_v1, hash_copy);
+
+        // `copy.js` still refers to the first version
+        let hash_v2 = entries
+            .insert(PathBuf::from("app.js"), modified, Bytes::from("v2"))
+            .unwrap();
+        assert!(entries.dictionaries.contains_key(&hash_v1));
+        assert!(entries.dictionaries.contains_key(&hash_v2));
+
+        entries.insert(PathBuf::from("copy.js"), modified, Bytes::from("v2"));
+        assert!(!entries.dictionaries.contains_key(&hash_v1));
+        assert_eq!(entries.dictionaries.len(), 1);
+    }
+
+    #[test]
+    fn entries_limit() {
+        let mut entries = Entries::default();
+        let modified = SystemTime::now();
+
+        for i in 0..MAX_DICTIONARIES {
+            let data = Bytes::from(i.to_string());
+            assert!(entries
+                .insert(PathBuf::from(format!("{i}.js")), modified, data)
+                .is_some());
+        }
+        assert!(entries
+            .insert(PathBuf::from("new.js"), modified, Bytes::from("new"))
+            .is_none());
+        // Known dictionary files can still be updated
+        assert!(entries
+            .insert(PathBuf::from("0.js"), modified, Bytes::from("v2"))
+            .is_some());
+        assert_eq!(entries.files.len(), MAX_DICTIONARIES);
+    }
+}
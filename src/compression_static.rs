@@ -13,7 +13,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{compression, static_files::file_metadata};
+use crate::{
+    compression::{self, DictionaryCoding},
+    static_files::file_metadata,
+};
 
 /// It defines the pre-compressed file variant metadata of a particular file path.
 pub struct CompressedFileVariant<'a> {
@@ -89,3 +92,31 @@ pub async fn precompressed_variant<'a>(
         extension: if comp_ext == "gz" { "gzip" } else { comp_ext },
     })
 }
+
+/// Search for a pre-compressed dictionary variant (`dcb` or `dcz`) of the given file path
+/// which was compressed using the dictionary of the given SHA-256 hash.
+/// The variant file name has the form `<file name>.<dictionary hash in hex>.<dcb|dcz>`.
+pub fn dictionary_variant(
+    file_path: &Path,
+    hash: &[u8; 32],
+    coding: DictionaryCoding,
+) -> Option<CompressedFileVariant<'static>> {
+    let file_name = file_path.file_name().and_then(OsStr::to_str)?;
+    let hash_hex: String = hash.iter().map(|b| format!("{b:02x}")).collect();
+    let ext = coding.as_str();
+
+    let file_path = file_path.with_file_name([file_name, ".", &hash_hex, ".", ext].concat());
+    tracing::trace!(
+        "trying to get the pre-compressed dictionary variant metadata for {}",
+        file_path.display()
+    );
+
+    match file_metadata(&file_path) {
+        Ok((metadata, false)) => Some(CompressedFileVariant {
+            file_path,
+            metadata,
+            extension: ext,
+        }),
+        _ => None,
+    }
+}
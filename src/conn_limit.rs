@@ -0,0 +1,231 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to limit the number of concurrent connections, globally and per client IP address.
+//!
+//! Connections accepted over the limits are closed right away,
+//! before reading any request or starting a TLS handshake.
+//!
+
+use hyper::server::accept::Accept;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::transport::{PeerIdentity, Transport};
+
+/// It defines the open connections counters.
+#[derive(Debug, Default)]
+struct Counters {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+/// It defines the maximum number of concurrent connections.
+#[derive(Debug, Default)]
+pub struct ConnLimits {
+    max: usize,
+    max_per_ip: usize,
+    counters: Mutex<Counters>,
+}
+
+impl ConnLimits {
+    /// Creates new connection limits. A zero value disables the corresponding limit.
+    pub fn new(max: usize, max_per_ip: usize) -> Self {
+        Self {
+            max,
+            max_per_ip,
+            counters: Mutex::default(),
+        }
+    }
+
+    /// Checks whether no limit is set.
+    pub fn is_empty(&self) -> bool {
+        self.max == 0 && self.max_per_ip == 0
+    }
+
+    /// Counts a new connection if it's within the limits.
+    /// The connection is released when the returned guard is dropped.
+    pub fn acquire(self: &Arc<Self>, ip: Option<IpAddr>) -> Option<ConnGuard> {
+        if self.is_empty() {
+            return Some(ConnGuard { limits: None, ip });
+        }
+
+        let ip = ip.map(|ip| ip.to_canonical());
+        let mut counters = self.counters.lock().unwrap();
+        if self.max > 0 && counters.total >= self.max {
+            return None;
+        }
+        if let (true, Some(ip)) = (self.max_per_ip > 0, ip) {
+            let count = counters.per_ip.entry(ip).or_default();
+            if *count >= self.max_per_ip {
+                return None;
+            }
+            *count += 1;
+        }
+        counters.total += 1;
+
+        Some(ConnGuard {
+            limits: Some(self.clone()),
+            ip,
+        })
+    }
+
+    /// Releases a connection of a client.
+    fn release(&self, ip: Option<IpAddr>) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.total = counters.total.saturating_sub(1);
+        if let (true, Some(ip)) = (self.max_per_ip > 0, ip) {
+            if let Some(count) = counters.per_ip.get_mut(&ip) {
+                *count -= 1;
+                if *count == 0 {
+                    counters.per_ip.remove(&ip);
+                }
+            }
+        }
+    }
+}
+
+/// It defines an open connection counted by its limits.
+#[derive(Debug)]
+pub struct ConnGuard {
+    limits: Option<Arc<ConnLimits>>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnGuard {
+    fn drop(&mut self) {
+        if let Some(limits) = &self.limits {
+            limits.release(self.ip);
+        }
+    }
+}
+
+/// It defines an acceptor closing the connections exceeding the limits.
+pub struct LimitedIncoming<A> {
+    incoming: A,
+    limits: Arc<ConnLimits>,
+}
+
+impl<A> LimitedIncoming<A> {
+    /// Wraps an acceptor to apply the given limits.
+    pub fn new(incoming: A, limits: Arc<ConnLimits>) -> Self {
+        Self { incoming, limits }
+    }
+}
+
+impl<A> Accept for LimitedIncoming<A>
+where
+    A: Accept + Unpin,
+    A::Conn: Transport,
+{
+    type Conn = LimitedConn<A::Conn>;
+    type Error = A::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let pin = self.get_mut();
+        loop {
+            let conn = match Pin::new(&mut pin.incoming).poll_accept(cx) {
+                Poll::Ready(Some(Ok(conn))) => conn,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            let remote_addr = conn.remote_addr();
+            match pin.limits.acquire(remote_addr.map(|addr| addr.ip())) {
+                Some(guard) => {
+                    return Poll::Ready(Some(Ok(LimitedConn {
+                        conn,
+                        _guard: guard,
+                    })))
+                }
+                None => tracing::warn!(
+                    "connection limit reached, closing the connection of {:?}",
+                    remote_addr
+                ),
+            }
+        }
+    }
+}
+
+/// It defines a connection counted by its limits until it's closed.
+pub struct LimitedConn<T> {
+    conn: T,
+    _guard: ConnGuard,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for LimitedConn<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().conn).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for LimitedConn<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().conn).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().conn).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().conn).poll_shutdown(cx)
+    }
+}
+
+impl<T: Transport + Unpin> Transport for LimitedConn<T> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.conn.remote_addr()
+    }
+
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        self.conn.peer_identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_and_per_ip_limits() {
+        let limits = Arc::new(ConnLimits::new(3, 2));
+        let a = Some("10.0.0.1".parse().unwrap());
+        let b = Some("::ffff:10.0.0.2".parse().unwrap());
+
+        let a1 = limits.acquire(a).unwrap();
+        let _a2 = limits.acquire(a).unwrap();
+        assert!(limits.acquire(a).is_none());
+        let _b1 = limits.acquire(b).unwrap();
+        assert!(limits.acquire(b).is_none());
+
+        drop(a1);
+        let _b2 = limits.acquire(b).unwrap();
+        assert!(limits.acquire(a).is_none());
+        assert_eq!(limits.counters.lock().unwrap().total, 3);
+
+        let unlimited = Arc::new(ConnLimits::new(0, 0));
+        for _ in 0..10 {
+            std::mem::forget(unlimited.acquire(a).unwrap());
+        }
+        assert_eq!(unlimited.counters.lock().unwrap().total, 0);
+    }
+}
@@ -4,11 +4,18 @@
 // Copyright (C) 2019-present Jose Quintana <joseluisq.net>
 
 //! It provides an arbitrary `Cache-Control` headers functionality
-//! for incoming requests based on a set of file types.
+//! for incoming requests based on a set of file types or on custom rules.
 //!
 
-use headers::{CacheControl, HeaderMapExt};
-use hyper::{Body, Response};
+use headers::{CacheControl, Expires, HeaderMapExt, HeaderValue};
+use hyper::{
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    Body, Response,
+};
+use regex::Regex;
+use std::time::{Duration, SystemTime};
+
+use crate::settings;
 
 // Cache-Control `max-age` variants
 const MAX_AGE_ONE_HOUR: u64 = 60 * 60;
@@ -23,6 +30,33 @@ const CACHE_EXT_ONE_YEAR: [&str; 32] = [
     "weba", "webm", "webp", "woff", "woff2", "zip",
 ];
 
+/// Default pattern used to detect fingerprinted file names. E.g `main.3f9a1c2b.js` or `main-3f9a1c2b.js`
+pub const DEFAULT_FINGERPRINT_PATTERN: &str = r"[.-][0-9a-fA-F]{8,}\.[^.]+$";
+
+/// It appends the `Cache-Control` headers to a response using the first matching custom rule if any,
+/// then the fingerprinted file names detection if enabled and finally the built-in set of file types
+/// unless custom rules are configured.
+pub fn append_cache_headers(
+    uri: &str,
+    rules: Option<&[settings::CacheControl]>,
+    fingerprint: Option<&Regex>,
+    resp: &mut Response<Body>,
+) {
+    if let Some(rules) = rules {
+        if append_rule_headers(uri, rules, resp) {
+            return;
+        }
+    }
+    if let Some(pattern) = fingerprint {
+        if append_fingerprint_headers(uri, pattern, resp) {
+            return;
+        }
+    }
+    if rules.is_none() {
+        append_headers(uri, resp);
+    }
+}
+
 /// It appends a `Cache-Control` header to a response if that one is part of a set of file types.
 pub fn append_headers(uri: &str, resp: &mut Response<Body>) {
     // Default max-age value in seconds (one day)
@@ -42,9 +76,90 @@ pub fn append_headers(uri: &str, resp: &mut Response<Body>) {
     resp.headers_mut().typed_insert(cache_control);
 }
 
+/// It appends a `Cache-Control` header (and an `Expires` one if enabled)
+/// to a response based on the first rule matching the URI path, its file extension or its MIME type.
+/// It returns `false` when there is no matching rule.
+pub fn append_rule_headers(
+    uri: &str,
+    rules: &[settings::CacheControl],
+    resp: &mut Response<Body>,
+) -> bool {
+    let extension = uri_file_extension(uri).map(|ext| ext.to_ascii_lowercase());
+    let mime = response_mime(resp);
+
+    let rule = rules.iter().find(|rule| {
+        rule.source.as_ref().is_none_or(|glob| glob.is_match(uri))
+            && (rule.extensions.is_empty()
+                || extension
+                    .as_ref()
+                    .is_some_and(|ext| rule.extensions.contains(ext)))
+            && (rule.mime_types.is_empty()
+                || mime
+                    .as_deref()
+                    .is_some_and(|mime| rule.mime_types.iter().any(|m| mime_matches(m, mime))))
+    });
+
+    match rule {
+        Some(rule) => {
+            resp.headers_mut().insert(CACHE_CONTROL, rule.value.clone());
+            if let Some(secs) = rule.expires {
+                let expires = SystemTime::now() + duration_from_secs(secs);
+                resp.headers_mut().typed_insert(Expires::from(expires));
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// It appends a one-year `immutable` `Cache-Control` header to a response if the file name
+/// of the URI matches the fingerprint pattern or a `no-cache` one if it is an HTML document.
+/// It returns `false` when none of them applies.
+pub fn append_fingerprint_headers(uri: &str, pattern: &Regex, resp: &mut Response<Body>) -> bool {
+    let file_name = uri.rsplit('/').next().unwrap_or_default();
+    if pattern.is_match(file_name) {
+        resp.headers_mut().insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=31536000, immutable"),
+        );
+        return true;
+    }
+
+    let is_html = match response_mime(resp) {
+        Some(mime) => mime == "text/html",
+        None => matches!(uri_file_extension(uri), Some("html" | "htm")),
+    };
+    if is_html {
+        resp.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        return true;
+    }
+
+    false
+}
+
+/// Gets the lowercase MIME type of a response without parameters.
+fn response_mime(resp: &Response<Body>) -> Option<String> {
+    resp.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+}
+
+/// Checks if a MIME type matches a pattern like `text/html` or `image/*`.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => mime
+            .split_once('/')
+            .is_some_and(|(type_, _)| type_ == prefix),
+        None => pattern == mime,
+    }
+}
+
 /// It caps a duration value at ~136 years.
-fn duration_from_secs(secs: u64) -> std::time::Duration {
-    std::time::Duration::from_secs(std::cmp::min(secs, u32::MAX as u64))
+fn duration_from_secs(secs: u64) -> Duration {
+    Duration::from_secs(std::cmp::min(secs, u32::MAX as u64))
 }
 
 /// Gets the file extension for a URI.
@@ -59,9 +174,11 @@ mod tests {
     use hyper::{Body, Response, StatusCode};
 
     use super::{
-        append_headers, uri_file_extension, CACHE_EXT_ONE_HOUR, CACHE_EXT_ONE_YEAR,
+        append_cache_headers, append_headers, append_rule_headers, mime_matches,
+        uri_file_extension, CACHE_EXT_ONE_HOUR, CACHE_EXT_ONE_YEAR, DEFAULT_FINGERPRINT_PATTERN,
         MAX_AGE_ONE_DAY, MAX_AGE_ONE_HOUR, MAX_AGE_ONE_YEAR,
     };
+    use crate::settings::CacheControl;
 
     #[tokio::test]
     async fn headers_one_hour() {
@@ -112,6 +229,119 @@ mod tests {
         }
     }
 
+    fn rules() -> Vec<CacheControl> {
+        vec![
+            CacheControl {
+                source: Some(globset::Glob::new("/assets/**").unwrap().compile_matcher()),
+                extensions: vec!["js".to_owned()],
+                mime_types: vec![],
+                value: "public, max-age=31536000, immutable".parse().unwrap(),
+                expires: Some(31536000),
+            },
+            CacheControl {
+                source: None,
+                extensions: vec![],
+                mime_types: vec!["text/html".to_owned()],
+                value: "public, no-cache".parse().unwrap(),
+                expires: Some(0),
+            },
+            CacheControl {
+                source: None,
+                extensions: vec![],
+                mime_types: vec!["image/*".to_owned()],
+                value: "private, max-age=60, stale-while-revalidate=30"
+                    .parse()
+                    .unwrap(),
+                expires: None,
+            },
+        ]
+    }
+
+    fn rule_response(uri: &str, content_type: &'static str) -> Response<Body> {
+        let mut resp = Response::new(Body::empty());
+        resp.headers_mut()
+            .insert(http::header::CONTENT_TYPE, content_type.parse().unwrap());
+        append_rule_headers(uri, &rules(), &mut resp);
+        resp
+    }
+
+    #[test]
+    fn headers_rules() {
+        let resp = rule_response("/assets/main.JS", "application/javascript");
+        assert_eq!(
+            resp.headers()["cache-control"],
+            "public, max-age=31536000, immutable"
+        );
+        assert!(resp.headers().contains_key("expires"));
+
+        let resp = rule_response("/index.html", "text/html; charset=utf-8");
+        assert_eq!(resp.headers()["cache-control"], "public, no-cache");
+        assert!(resp.headers().contains_key("expires"));
+
+        let resp = rule_response("/images/logo.png", "image/png");
+        assert_eq!(
+            resp.headers()["cache-control"],
+            "private, max-age=60, stale-while-revalidate=30"
+        );
+        assert!(!resp.headers().contains_key("expires"));
+
+        // Rules replace the default values so no header is appended if nothing matches
+        let resp = rule_response("/main.js", "application/javascript");
+        assert!(!resp.headers().contains_key("cache-control"));
+    }
+
+    #[test]
+    fn headers_fingerprint() {
+        let pattern = regex::Regex::new(DEFAULT_FINGERPRINT_PATTERN).unwrap();
+        let cache_control = |uri: &str, content_type: &'static str| {
+            let mut resp = Response::new(Body::empty());
+            resp.headers_mut()
+                .insert(http::header::CONTENT_TYPE, content_type.parse().unwrap());
+            append_cache_headers(uri, None, Some(&pattern), &mut resp);
+            resp.headers()["cache-control"].to_str().unwrap().to_owned()
+        };
+
+        for uri in ["/assets/main.3f9a1c2b.js", "/main-3F9A1C2B0D.css"] {
+            assert_eq!(
+                cache_control(uri, "text/css"),
+                "public, max-age=31536000, immutable"
+            );
+        }
+        assert_eq!(cache_control("/", "text/html; charset=utf-8"), "no-cache");
+        assert_eq!(cache_control("/about.html", "text/html"), "no-cache");
+        // Other files keep the built-in values
+        assert_eq!(
+            cache_control("/main.js", "text/javascript"),
+            format!("public, max-age={MAX_AGE_ONE_YEAR}")
+        );
+        assert_eq!(
+            cache_control("/settings.json", "application/json"),
+            format!("public, max-age={MAX_AGE_ONE_HOUR}")
+        );
+
+        // Custom rules take precedence over fingerprinted file names
+        let mut resp = Response::new(Body::empty());
+        append_cache_headers(
+            "/assets/app.3f9a1c2b.js",
+            Some(&rules()),
+            Some(&pattern),
+            &mut resp,
+        );
+        assert_eq!(
+            resp.headers()["cache-control"],
+            "public, max-age=31536000, immutable"
+        );
+        assert!(resp.headers().contains_key("expires"));
+    }
+
+    #[test]
+    fn match_mime_types() {
+        assert!(mime_matches("text/html", "text/html"));
+        assert!(mime_matches("image/*", "image/svg+xml"));
+        assert!(!mime_matches("image/*", "text/html"));
+        assert!(!mime_matches("text/html", "text/plain"));
+    }
+
     #[test]
     fn find_uri_extension() {
         assert_eq!(uri_file_extension("/potato.zip"), Some("zip"));
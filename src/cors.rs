@@ -230,10 +230,11 @@ pub enum Validated {
     NotCors,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 /// Forbidden errors.
 pub enum Forbidden {
     /// Forbidden error origin.
+    #[default]
     Origin,
     /// Forbidden error method.
     Method,
@@ -241,12 +242,6 @@ pub enum Forbidden {
     Header,
 }
 
-impl Default for Forbidden {
-    fn default() -> Self {
-        Self::Origin
-    }
-}
-
 impl Configured {
     /// Check for the incoming CORS request.
     pub fn check_request(
@@ -346,7 +341,7 @@ pub trait IntoOrigin {
     fn into_origin(self) -> Origin;
 }
 
-impl<'a> IntoOrigin for &'a str {
+impl IntoOrigin for &str {
     fn into_origin(self) -> Origin {
         let mut parts = self.splitn(2, "://");
         let scheme = parts.next().expect("cors::into_origin: missing url scheme");
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to inject per-response `Content-Security-Policy` nonces into HTML documents.
+//!
+
+use base64::Engine;
+use bytes::Bytes;
+use headers::{ContentLength, HeaderMapExt, HeaderValue};
+use hyper::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+    Body, Method, Response, StatusCode,
+};
+use regex::bytes::{Captures, Regex};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::{exts::http::MethodExt, Result};
+
+/// Default placeholder replaced by the nonce in HTML documents.
+pub const DEFAULT_PLACEHOLDER: &str = "__CSP_NONCE__";
+
+/// Default `Content-Security-Policy` template. The `{nonce}` token is replaced by the nonce.
+pub const DEFAULT_POLICY: &str = "script-src 'nonce-{nonce}' 'strict-dynamic'; style-src 'self' 'nonce-{nonce}'; object-src 'none'; base-uri 'self'";
+
+/// Token of the policy template replaced by the nonce.
+const POLICY_NONCE_TOKEN: &str = "{nonce}";
+
+/// HTML documents larger than this size (10 MiB) are not rewritten.
+const MAX_DOCUMENT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Number of random bytes of a nonce.
+const NONCE_LEN: usize = 16;
+
+/// It defines the CSP nonce options of the server.
+#[derive(Debug)]
+pub struct CspNonce {
+    placeholder: Vec<u8>,
+    policy: String,
+    tags: Option<Regex>,
+    rng: SystemRandom,
+}
+
+impl CspNonce {
+    /// Creates the CSP nonce options.
+    /// If `tags` is enabled then a `nonce` attribute is also added to `<script>` and `<style>` tags.
+    pub fn new(placeholder: &str, policy: &str, tags: bool) -> Result<Self> {
+        if placeholder.is_empty() && !tags {
+            bail!("csp nonce requires a placeholder or the `<script>`/`<style>` tags rewriting");
+        }
+        if !policy.contains(POLICY_NONCE_TOKEN) {
+            bail!(
+                "csp nonce policy should contain the `{}` token",
+                POLICY_NONCE_TOKEN
+            );
+        }
+        HeaderValue::from_str(&policy.replace(POLICY_NONCE_TOKEN, ""))
+            .map_err(|err| anyhow!("invalid csp nonce policy: {}", err))?;
+
+        let tags = if tags {
+            Some(Regex::new(r"(?i)<(?:script|style)\b([^>]*)>")?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            placeholder: placeholder.as_bytes().to_vec(),
+            policy: policy.to_owned(),
+            tags,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Generates a new random nonce encoded as base64.
+    pub fn generate(&self) -> Result<String> {
+        let mut buf = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut buf)
+            .map_err(|_| anyhow!("unable to generate a random csp nonce"))?;
+        Ok(base64::engine::general_purpose::STANDARD.encode(buf))
+    }
+
+    /// Replaces the placeholder of an HTML document with the given nonce
+    /// and adds the `nonce` attribute to its `<script>` and `<style>` tags if enabled.
+    pub fn rewrite_html(&self, html: &[u8], nonce: &str) -> Vec<u8> {
+        let mut out = replace_bytes(html, &self.placeholder, nonce.as_bytes());
+
+        if let Some(tags) = &self.tags {
+            out = tags
+                .replace_all(&out, |caps: &Captures<'_>| {
+                    let tag = &caps[0];
+                    let attrs = caps[1].to_ascii_lowercase();
+                    if attrs.windows(6).any(|w| w == b"nonce=") {
+                        return tag.to_vec();
+                    }
+                    // Insert the attribute right after the tag name
+                    let name_len = if tag[2].eq_ignore_ascii_case(&b'c') {
+                        "<script".len()
+                    } else {
+                        "<style".len()
+                    };
+                    let mut t = Vec::with_capacity(tag.len() + nonce.len() + 9);
+                    t.extend_from_slice(&tag[..name_len]);
+                    t.extend_from_slice(b" nonce=\"");
+                    t.extend_from_slice(nonce.as_bytes());
+                    t.push(b'"');
+                    t.extend_from_slice(&tag[name_len..]);
+                    t
+                })
+                .into_owned();
+        }
+
+        out
+    }
+
+    /// Returns the `Content-Security-Policy` header value for the given nonce.
+    pub fn policy(&self, nonce: &str) -> Result<HeaderValue> {
+        Ok(HeaderValue::from_str(
+            &self.policy.replace(POLICY_NONCE_TOKEN, nonce),
+        )?)
+    }
+
+    /// Rewrites the body of a successful HTML `GET` response using a new nonce.
+    /// Since every response body is unique, its validators (`ETag` and `Last-Modified`) are removed.
+    ///
+    /// It returns the response along with the `Content-Security-Policy` header value
+    /// matching the nonce if the response was rewritten.
+    pub async fn rewrite(
+        &self,
+        method: &Method,
+        resp: Response<Body>,
+    ) -> Result<(Response<Body>, Option<HeaderValue>)> {
+        if !method.is_get() || resp.status() != StatusCode::OK || !is_html(&resp) {
+            return Ok((resp, None));
+        }
+        // Large documents are served as they are
+        match resp.headers().typed_get::<ContentLength>() {
+            Some(ContentLength(len)) if len <= MAX_DOCUMENT_SIZE => {}
+            _ => return Ok((resp, None)),
+        }
+
+        let (mut head, body) = resp.into_parts();
+        let html = hyper::body::to_bytes(body).await?;
+
+        let nonce = self.generate()?;
+        let data = Bytes::from(self.rewrite_html(&html, &nonce));
+
+        head.headers.remove(CONTENT_LENGTH);
+        head.headers.remove(ETAG);
+        head.headers.remove(LAST_MODIFIED);
+        head.headers.typed_insert(ContentLength(data.len() as u64));
+
+        Ok((
+            Response::from_parts(head, Body::from(data)),
+            Some(self.policy(&nonce)?),
+        ))
+    }
+}
+
+/// Checks whether the response content type is an HTML document.
+pub fn is_html(resp: &Response<Body>) -> bool {
+    resp.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim_start().to_ascii_lowercase().starts_with("text/html"))
+}
+
+/// Replaces all occurrences of `from` in `data` with `to`.
+fn replace_bytes(data: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    if from.is_empty() {
+        return data.to_vec();
+    }
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i..].starts_with(from) {
+            out.extend_from_slice(to);
+            i += from.len();
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_placeholder_and_tags() {
+        let csp = CspNonce::new(DEFAULT_PLACEHOLDER, DEFAULT_POLICY, true).unwrap();
+        let html = br#"<html><head><STYLE>p{}</STYLE><SCRIPT src="/app.js"></SCRIPT>
+<script nonce="__CSP_NONCE__">x()</script><scripts></scripts><meta name="csp-nonce" content="__CSP_NONCE__"></head></html>"#;
+
+        let out = String::from_utf8(csp.rewrite_html(html, "abc")).unwrap();
+        assert_eq!(
+            out,
+            r#"<html><head><STYLE nonce="abc">p{}</STYLE><SCRIPT nonce="abc" src="/app.js"></SCRIPT>
+<script nonce="abc">x()</script><scripts></scripts><meta name="csp-nonce" content="abc"></head></html>"#
+        );
+    }
+
+    #[test]
+    fn rewrite_placeholder_only() {
+        let csp = CspNonce::new("{{nonce}}", "script-src 'nonce-{nonce}'", false).unwrap();
+        let out = csp.rewrite_html(b"<script nonce=\"{{nonce}}\"></script><style></style>", "n");
+        assert_eq!(out, b"<script nonce=\"n\"></script><style></style>");
+        assert_eq!(csp.policy("n").unwrap(), "script-src 'nonce-n'");
+    }
+
+    #[test]
+    fn nonces_are_unique() {
+        let csp = CspNonce::new(DEFAULT_PLACEHOLDER, DEFAULT_POLICY, false).unwrap();
+        let nonce = csp.generate().unwrap();
+        assert_eq!(nonce.len(), 24);
+        assert_ne!(nonce, csp.generate().unwrap());
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(CspNonce::new("", DEFAULT_POLICY, false).is_err());
+        assert!(CspNonce::new(DEFAULT_PLACEHOLDER, "script-src 'self'", true).is_err());
+        assert!(CspNonce::new(DEFAULT_PLACEHOLDER, "script-src\n'nonce-{nonce}'", true).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to collect CSP (`report-uri`) and Reporting API (`report-to`) violation reports.
+//!
+
+use headers::HeaderMap;
+use hyper::{header::CONTENT_TYPE, Body, StatusCode};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+use crate::{client_map::ClientMap, helpers::read_body, Context, Result};
+
+/// Content type of the CSP `report-uri` reports.
+pub const CSP_REPORT_CONTENT_TYPE: &str = "application/csp-report";
+/// Content type of the Reporting API `report-to` reports.
+pub const REPORTS_CONTENT_TYPE: &str = "application/reports+json";
+
+/// Default maximum size in bytes of a report request body.
+pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024;
+
+/// Window of the per-client rate limit.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Maximum number of clients tracked by the rate limiter.
+const RATE_LIMIT_MAX_CLIENTS: usize = 10_000;
+
+/// Destination of the collected reports.
+enum Output {
+    /// The tracing log.
+    Log,
+    /// A JSON lines file.
+    File(tokio::sync::Mutex<tokio::fs::File>),
+}
+
+/// It defines the violation reports collector.
+pub struct ReportCollector {
+    /// URI path of the collector endpoint.
+    endpoint: String,
+    /// Maximum size in bytes of a report request body.
+    max_size: u64,
+    /// Maximum number of reports per minute and client. Zero disables the limit.
+    rate_limit: u32,
+    /// Number of reports per client in the current window.
+    clients: ClientMap<IpAddr, (Instant, u32)>,
+    output: Output,
+}
+
+impl ReportCollector {
+    /// Creates a new reports collector for the given endpoint.
+    /// Reports are appended to the `file` as JSON lines if provided, otherwise they are logged.
+    pub async fn new(
+        endpoint: &str,
+        file: Option<&Path>,
+        max_size: u64,
+        rate_limit: u32,
+    ) -> Result<Self> {
+        if !endpoint.starts_with('/') {
+            bail!("csp reports endpoint should be an absolute URI path");
+        }
+        let output = match file {
+            Some(path) => {
+                let file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .with_context(|| format!("failed to open reports file {}", path.display()))?;
+                Output::File(tokio::sync::Mutex::new(file))
+            }
+            None => Output::Log,
+        };
+
+        Ok(Self {
+            endpoint: endpoint.to_owned(),
+            max_size,
+            rate_limit,
+            clients: ClientMap::new(RATE_LIMIT_MAX_CLIENTS),
+            output,
+        })
+    }
+
+    /// Checks whether the URI path corresponds to the collector endpoint.
+    pub fn is_endpoint(&self, uri_path: &str) -> bool {
+        self.endpoint == uri_path
+    }
+
+    /// Collects the reports of a request body.
+    /// It returns the status code of the response.
+    pub async fn collect(
+        &self,
+        headers: &HeaderMap,
+        body: Body,
+        remote_ip: Option<IpAddr>,
+    ) -> StatusCode {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        if content_type != CSP_REPORT_CONTENT_TYPE && content_type != REPORTS_CONTENT_TYPE {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE;
+        }
+
+        if let Some(ip) = remote_ip {
+            if !self.check_rate_limit(ip, Instant::now()) {
+                tracing::debug!("csp reports: rate limit exceeded for {}", ip);
+                return StatusCode::TOO_MANY_REQUESTS;
+            }
+        }
+
+        let data = match read_body(body, self.max_size).await {
+            Ok(Some(data)) => data,
+            Ok(None) => return StatusCode::PAYLOAD_TOO_LARGE,
+            Err(err) => {
+                tracing::debug!("csp reports: unable to read request body: {:?}", err);
+                return StatusCode::BAD_REQUEST;
+            }
+        };
+
+        let reports = match parse_reports(&content_type, &data) {
+            Some(reports) => reports,
+            None => return StatusCode::BAD_REQUEST,
+        };
+
+        let received_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let user_agent = headers
+            .get(hyper::header::USER_AGENT)
+            .and_then(|v| v.to_str().ok());
+
+        for report in reports {
+            let line = json!({
+                "received_at": received_at,
+                "remote_addr": remote_ip.map(|ip| ip.to_string()),
+                "user_agent": user_agent,
+                "content_type": content_type,
+                "report": report,
+            });
+            if let Err(err) = self.write(&line.to_string()).await {
+                tracing::error!("csp reports: unable to write report: {:?}", err);
+                return StatusCode::INTERNAL_SERVER_ERROR;
+            }
+        }
+
+        StatusCode::NO_CONTENT
+    }
+
+    /// Writes a report line to the output.
+    async fn write(&self, line: &str) -> std::io::Result<()> {
+        match &self.output {
+            Output::Log => {
+                tracing::warn!("csp violation report: {}", line);
+                Ok(())
+            }
+            Output::File(file) => {
+                let mut file = file.lock().await;
+                file.write_all(format!("{line}\n").as_bytes()).await?;
+                file.flush().await
+            }
+        }
+    }
+
+    /// Counts a report of the given client and checks whether it is within the rate limit.
+    fn check_rate_limit(&self, ip: IpAddr, now: Instant) -> bool {
+        if self.rate_limit == 0 {
+            return true;
+        }
+        self.clients.with(
+            ip,
+            || (now, 0),
+            |(start, count)| {
+                if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+                    *start = now;
+                    *count = 0;
+                }
+                *count += 1;
+                *count <= self.rate_limit
+            },
+        )
+    }
+}
+
+/// Parses the reports of a request body.
+/// A CSP report body contains a single `csp-report` object
+/// while a Reporting API body contains an array of reports.
+fn parse_reports(content_type: &str, data: &[u8]) -> Option<Vec<Value>> {
+    match serde_json::from_slice::<Value>(data).ok()? {
+        Value::Object(report) if content_type == CSP_REPORT_CONTENT_TYPE => report
+            .contains_key("csp-report")
+            .then(|| vec![report.into()]),
+        Value::Array(reports) if content_type == REPORTS_CONTENT_TYPE => {
+            reports.iter().all(|r| r.is_object()).then_some(reports)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_parsing() {
+        let reports = parse_reports(
+            CSP_REPORT_CONTENT_TYPE,
+            br#"{"csp-report":{"document-uri":"https://example.com/","violated-directive":"script-src"}}"#,
+        )
+        .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0]["csp-report"]["violated-directive"], "script-src");
+
+        let reports = parse_reports(
+            REPORTS_CONTENT_TYPE,
+            br#"[{"type":"csp-violation","body":{}},{"type":"deprecation","body":{}}]"#,
+        )
+        .unwrap();
+        assert_eq!(reports.len(), 2);
+
+        assert!(parse_reports(CSP_REPORT_CONTENT_TYPE, br#"{"other":{}}"#).is_none());
+        assert!(parse_reports(REPORTS_CONTENT_TYPE, br#"{"type":"csp-violation"}"#).is_none());
+        assert!(parse_reports(REPORTS_CONTENT_TYPE, br#"[1, 2]"#).is_none());
+        assert!(parse_reports(REPORTS_CONTENT_TYPE, b"not json").is_none());
+    }
+
+    #[tokio::test]
+    async fn rate_limit_window() {
+        let collector = ReportCollector::new("/csp-reports", None, DEFAULT_MAX_SIZE, 2)
+            .await
+            .unwrap();
+        let ip: IpAddr = "192.168.1.10".parse().unwrap();
+        let other: IpAddr = "192.168.1.11".parse().unwrap();
+        let now = Instant::now();
+
+        assert!(collector.check_rate_limit(ip, now));
+        assert!(collector.check_rate_limit(ip, now));
+        assert!(!collector.check_rate_limit(ip, now));
+        assert!(collector.check_rate_limit(other, now));
+        assert!(collector.check_rate_limit(ip, now + RATE_LIMIT_WINDOW));
+    }
+
+    #[tokio::test]
+    async fn body_size_limit() {
+        assert_eq!(
+            read_body(Body::from("12345"), 5).await.unwrap(),
+            Some(b"12345".to_vec())
+        );
+        assert_eq!(read_body(Body::from("123456"), 5).await.unwrap(), None);
+    }
+}
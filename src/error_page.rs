@@ -46,7 +46,8 @@ pub fn error_response(
         | &StatusCode::URI_TOO_LONG
         | &StatusCode::UNSUPPORTED_MEDIA_TYPE
         | &StatusCode::RANGE_NOT_SATISFIABLE
-        | &StatusCode::EXPECTATION_FAILED => {
+        | &StatusCode::EXPECTATION_FAILED
+        | &StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE => {
             // Extra check for 404 status code and its HTML content
             if status_code == &StatusCode::NOT_FOUND && !page404.is_empty() {
                 error_page_content = String::from_utf8_lossy(page404).to_string();
@@ -0,0 +1,565 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module providing a form-based login flow backed by signed session cookies.
+//!
+//! Credentials posted to the login page are checked against the users of the
+//! `Basic` HTTP authentication. A successful login sets an HMAC-signed cookie
+//! carrying the user id and its expiration time: `<user>.<expires>.<signature>`.
+//! The signature also covers the password hash of the user, so changing
+//! the password of a user ends its sessions.
+//!
+
+use base64::Engine;
+use headers::{HeaderMap, HeaderValue};
+use hyper::{
+    header::{ALLOW, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE},
+    Body, Method, Response, StatusCode, Uri,
+};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{basic_auth::UserStore, exts::http::MethodExt, helpers::read_body, Context, Result};
+
+/// Name of the session cookie.
+pub const SESSION_COOKIE: &str = "sws_session";
+
+/// Form field containing the path to redirect to after a successful login.
+const NEXT_FIELD: &str = "next";
+
+/// Maximum size in bytes of a login form body.
+const MAX_FORM_SIZE: u64 = 8 * 1024;
+
+/// Minimum length in bytes of the session signing key.
+const MIN_KEY_LEN: usize = 16;
+
+/// Built-in login page.
+const DEFAULT_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Sign in</title>
+<style>
+body{font-family:-apple-system,BlinkMacSystemFont,"Segoe UI",Helvetica,Arial,sans-serif;display:flex;justify-content:center;padding-top:10vh;margin:0}
+form{display:flex;flex-direction:column;gap:.5rem;width:18rem}
+input,button{font-size:1rem;padding:.4rem}
+p{color:#c0392b;margin:0}
+</style>
+</head>
+<body>
+<form method="post" action="{{action}}">
+<h1>Sign in</h1>
+<p>{{error}}</p>
+<label for="username">Username</label>
+<input id="username" name="username" autocomplete="username" required autofocus>
+<label for="password">Password</label>
+<input id="password" name="password" type="password" autocomplete="current-password" required>
+<input type="hidden" name="next" value="{{next}}">
+<button type="submit">Sign in</button>
+</form>
+</body>
+</html>
+"#;
+
+/// It defines the form login options.
+pub struct FormLoginOpts<'a> {
+    /// URI path of the login page.
+    pub login_path: &'a str,
+    /// URI path of the logout endpoint.
+    pub logout_path: &'a str,
+    /// Optional file path of a custom login page.
+    pub page: Option<&'a Path>,
+    /// Secret key signing the session cookies. A random key is used if empty.
+    pub key: &'a str,
+    /// Lifetime of the sessions in seconds.
+    pub ttl: u64,
+    /// Whether the session cookie is only sent over HTTPS.
+    pub secure: bool,
+}
+
+/// It defines the form login flow and its session cookies.
+pub struct FormLogin {
+    login_path: String,
+    logout_path: String,
+    page: String,
+    key: hmac::Key,
+    ttl: u64,
+    secure: bool,
+}
+
+impl FormLogin {
+    /// Creates a new form login flow from the given options.
+    pub fn new(opts: FormLoginOpts<'_>) -> Result<Self> {
+        for path in [opts.login_path, opts.logout_path] {
+            if !path.starts_with('/') || path.contains(['?', '#']) {
+                bail!("form login paths should be absolute URI paths: {}", path);
+            }
+        }
+        if opts.login_path == opts.logout_path {
+            bail!("form login and logout paths should be different");
+        }
+        if opts.ttl == 0 {
+            bail!("form login session lifetime should be greater than zero");
+        }
+
+        let page = match opts.page {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read login page {}", path.display()))?,
+            None => DEFAULT_PAGE.to_owned(),
+        };
+
+        let key = if opts.key.is_empty() {
+            // Sessions don't survive a restart of the server with a random key
+            let mut secret = [0u8; 32];
+            SystemRandom::new()
+                .fill(&mut secret)
+                .map_err(|_| anyhow!("failed to generate a form login session key"))?;
+            hmac::Key::new(hmac::HMAC_SHA256, &secret)
+        } else if opts.key.len() < MIN_KEY_LEN {
+            bail!(
+                "form login session key should be at least {} bytes long",
+                MIN_KEY_LEN
+            );
+        } else {
+            hmac::Key::new(hmac::HMAC_SHA256, opts.key.as_bytes())
+        };
+
+        Ok(Self {
+            login_path: opts.login_path.to_owned(),
+            logout_path: opts.logout_path.to_owned(),
+            page,
+            key,
+            ttl: opts.ttl,
+            secure: opts.secure,
+        })
+    }
+
+    /// Checks whether the URI path corresponds to the login page.
+    pub fn is_login(&self, uri_path: &str) -> bool {
+        self.login_path == uri_path
+    }
+
+    /// Checks whether the URI path corresponds to the login page or the logout endpoint.
+    pub fn is_endpoint(&self, uri_path: &str) -> bool {
+        self.is_login(uri_path) || self.logout_path == uri_path
+    }
+
+    /// Returns the session cookie value of a user and its password hash expiring at the given UNIX time.
+    pub fn session(&self, userid: &str, password_hash: &str, expires: u64) -> String {
+        let user = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(userid);
+        let tag = hmac::sign(
+            &self.key,
+            message(userid, password_hash, expires).as_bytes(),
+        );
+        let sig = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(tag.as_ref());
+        format!("{user}.{expires}.{sig}")
+    }
+
+    /// Verifies a session cookie value against the current users at the given UNIX time.
+    /// It returns the user id of a valid session.
+    pub fn verify_at(&self, session: &str, users: &UserStore, now: u64) -> Option<String> {
+        let mut parts = session.splitn(3, '.');
+        let user = parts.next()?;
+        let expires = parts.next()?.parse::<u64>().ok()?;
+        let sig = parts.next()?;
+
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let userid = String::from_utf8(engine.decode(user).ok()?).ok()?;
+        let sig = engine.decode(sig).ok()?;
+        let password_hash = users.password_hash(&userid)?;
+        let message = message(&userid, &password_hash, expires);
+        hmac::verify(&self.key, message.as_bytes(), &sig).ok()?;
+
+        (now <= expires).then_some(userid)
+    }
+
+    /// Returns the user id of a valid session cookie of a request
+    /// if the user is still known with the same password.
+    pub fn session_user(&self, headers: &HeaderMap, users: &UserStore) -> Option<String> {
+        let now = unix_now();
+        cookies(headers)
+            .filter(|(name, _)| *name == SESSION_COOKIE)
+            .find_map(|(_, value)| self.verify_at(value, users, now))
+    }
+
+    /// Returns the response of an unauthenticated request.
+    /// Page requests are redirected to the login page, otherwise `None` is returned.
+    pub fn redirect_to_login(&self, method: &Method, uri: &Uri) -> Option<Response<Body>> {
+        if !method.is_get() && !method.is_head() {
+            return None;
+        }
+        let next = uri.path_and_query().map_or("/", |v| v.as_str());
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair(NEXT_FIELD, next)
+            .finish();
+        Some(redirect(&format!("{}?{}", self.login_path, query), None))
+    }
+
+    /// Handles a request of the login page or the logout endpoint.
+    pub async fn handle(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: Option<Body>,
+        users: &UserStore,
+    ) -> Result<Response<Body>> {
+        if !self.is_login(uri.path()) {
+            // Logout, only `POST` so other sites can't end a session with a mere link or image
+            if method != Method::POST {
+                let mut resp = status_response(StatusCode::METHOD_NOT_ALLOWED);
+                resp.headers_mut()
+                    .insert(ALLOW, HeaderValue::from_static("POST"));
+                return Ok(resp);
+            }
+            let cookie = self.cookie("", 0);
+            return Ok(redirect(&self.login_path, Some(cookie)));
+        }
+
+        let next = form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+            .find(|(name, _)| name == NEXT_FIELD)
+            .map(|(_, value)| value.into_owned());
+        let body = match body {
+            Some(body) if method == Method::POST => body,
+            _ if method.is_get() || method.is_head() => {
+                return Ok(self.page(StatusCode::OK, next.as_deref(), ""))
+            }
+            _ => return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED)),
+        };
+
+        let is_form = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .is_some_and(|v| {
+                v.trim()
+                    .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+            });
+        if !is_form {
+            return Ok(status_response(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        }
+        let data = match read_body(body, MAX_FORM_SIZE).await {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok(status_response(StatusCode::PAYLOAD_TOO_LARGE)),
+            Err(err) => {
+                tracing::debug!("form login: unable to read request body: {:?}", err);
+                return Ok(status_response(StatusCode::BAD_REQUEST));
+            }
+        };
+
+        let (mut userid, mut password, mut next) = (String::new(), String::new(), next);
+        for (name, value) in form_urlencoded::parse(&data) {
+            match name.as_ref() {
+                "username" => userid = value.into_owned(),
+                "password" => password = value.into_owned(),
+                NEXT_FIELD => next = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        let password_hash = match users.password_hash(&userid) {
+            Some(hash) if !userid.is_empty() && users.verify(&userid, &password).await => hash,
+            _ => {
+                tracing::warn!("form login: invalid credentials for user `{}`", userid);
+                return Ok(self.page(
+                    StatusCode::UNAUTHORIZED,
+                    next.as_deref(),
+                    "Invalid username or password.",
+                ));
+            }
+        };
+
+        tracing::info!("form login: user `{}` signed in", userid);
+        let expires = unix_now().saturating_add(self.ttl);
+        let session = self.session(&userid, &password_hash, expires);
+        let cookie = self.cookie(&session, self.ttl);
+        let location = next.as_deref().filter(|v| is_local_path(v)).unwrap_or("/");
+        Ok(redirect(location, Some(cookie)))
+    }
+
+    /// Returns the login page response with its placeholders replaced.
+    fn page(&self, status: StatusCode, next: Option<&str>, error: &str) -> Response<Body> {
+        let next = next.filter(|v| is_local_path(v)).unwrap_or("/");
+        let html = self
+            .page
+            .replace("{{action}}", &escape_html(&self.login_path))
+            .replace("{{next}}", &escape_html(next))
+            .replace("{{error}}", &escape_html(error));
+
+        let mut resp = Response::new(Body::empty());
+        *resp.status_mut() = status;
+        let headers = resp.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(html.len()));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        *resp.body_mut() = Body::from(html);
+        resp
+    }
+
+    /// Returns the `Set-Cookie` header value of the session cookie.
+    fn cookie(&self, value: &str, max_age: u64) -> HeaderValue {
+        let secure = if self.secure { "; Secure" } else { "" };
+        let cookie = format!(
+            "{SESSION_COOKIE}={value}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax{secure}"
+        );
+        // The session value only contains base64url characters and dots
+        HeaderValue::from_str(&cookie).expect("invalid session cookie value")
+    }
+}
+
+/// Signed message of a session.
+fn message(userid: &str, password_hash: &str, expires: u64) -> String {
+    format!("{expires}\n{userid}\n{password_hash}")
+}
+
+/// Returns the `name=value` pairs of the `Cookie` headers of a request.
+fn cookies(headers: &HeaderMap) -> impl Iterator<Item = (&str, &str)> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|v| v.trim().split_once('='))
+}
+
+/// Checks whether a redirect target is a path of the same origin.
+/// Control characters and whitespace are rejected since browsers strip them
+/// from URLs, e.g. `/\t/evil.com` is followed as `//evil.com`.
+fn is_local_path(path: &str) -> bool {
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && !path.starts_with("/\\")
+        && !path
+            .bytes()
+            .any(|b| b.is_ascii_control() || b.is_ascii_whitespace())
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn redirect(location: &str, cookie: Option<HeaderValue>) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::SEE_OTHER;
+    if let Ok(location) = HeaderValue::from_str(location) {
+        resp.headers_mut().insert(LOCATION, location);
+    }
+    if let Some(cookie) = cookie {
+        resp.headers_mut().insert(SET_COOKIE, cookie);
+    }
+    resp.headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    resp
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn login() -> FormLogin {
+        FormLogin::new(FormLoginOpts {
+            login_path: "/login",
+            logout_path: "/logout",
+            page: None,
+            key: "0123456789abcdef0123456789abcdef",
+            ttl: 3600,
+            secure: false,
+        })
+        .unwrap()
+    }
+
+    const HASH: &str = "$2y$05$32zazJ1yzhlDHnt26L3MFOgY0HVqPmDUvG0KUx6cjf9RDiUGp/M9q";
+
+    fn users() -> UserStore {
+        UserStore::new(Some(&format!("jq:{HASH}")), None).unwrap()
+    }
+
+    #[test]
+    fn sessions() {
+        let login = login();
+        let users = users();
+        let session = login.session("jq", HASH, NOW + 60);
+        assert_eq!(
+            login.verify_at(&session, &users, NOW).as_deref(),
+            Some("jq")
+        );
+        assert_eq!(
+            login.verify_at(&session, &users, NOW + 60).as_deref(),
+            Some("jq")
+        );
+        assert!(login.verify_at(&session, &users, NOW + 61).is_none());
+
+        // Tampered user, expiration or key
+        let admin = UserStore::new(Some(&format!("admin:{HASH}")), None).unwrap();
+        let other = login.session("admin", HASH, NOW + 60);
+        let (user, _) = other.split_once('.').unwrap();
+        let (_, rest) = session.split_once('.').unwrap();
+        assert!(login
+            .verify_at(&format!("{user}.{rest}"), &admin, NOW)
+            .is_none());
+        let extended = session.replace(&(NOW + 60).to_string(), &(NOW + 600).to_string());
+        assert!(login.verify_at(&extended, &users, NOW + 120).is_none());
+        let other_login = FormLogin::new(FormLoginOpts {
+            login_path: "/login",
+            logout_path: "/logout",
+            page: None,
+            key: "",
+            ttl: 3600,
+            secure: false,
+        })
+        .unwrap();
+        assert!(other_login.verify_at(&session, &users, NOW).is_none());
+        assert!(login.verify_at("garbage", &users, NOW).is_none());
+
+        // Changed password
+        let changed = UserStore::new(Some("jq:$apr1$abcdefgh$changed"), None).unwrap();
+        assert!(login.verify_at(&session, &changed, NOW).is_none());
+    }
+
+    #[test]
+    fn session_cookie_of_known_users() {
+        let login = login();
+        let users = users();
+        let mut headers = HeaderMap::new();
+        assert!(login.session_user(&headers, &users).is_none());
+
+        let session = login.session("jq", HASH, u64::MAX);
+        headers.insert(
+            COOKIE,
+            format!("theme=dark; {SESSION_COOKIE}={session}")
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(login.session_user(&headers, &users).as_deref(), Some("jq"));
+
+        let session = login.session("unknown", HASH, u64::MAX);
+        headers.insert(
+            COOKIE,
+            format!("{SESSION_COOKIE}={session}").parse().unwrap(),
+        );
+        assert!(login.session_user(&headers, &users).is_none());
+    }
+
+    #[tokio::test]
+    async fn login_and_logout() {
+        let login = login();
+        let users = users();
+        let uri: Uri = "/login?next=/docs/".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+
+        let resp = login
+            .handle(&Method::GET, &uri, &headers, None, &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = Body::from("username=jq&password=wrong");
+        let resp = login
+            .handle(&Method::POST, &uri, &headers, Some(body), &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().get(SET_COOKIE).is_none());
+
+        let body = Body::from("username=jq&password=jq");
+        let resp = login
+            .handle(&Method::POST, &uri, &headers, Some(body), &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(resp.headers()[LOCATION], "/docs/");
+        let cookie = resp.headers()[SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("sws_session="));
+        assert!(cookie.contains("HttpOnly"));
+
+        // Only local redirect targets are followed
+        let body = Body::from("username=jq&password=jq&next=%2F%2Fexample.com");
+        let resp = login
+            .handle(&Method::POST, &uri, &headers, Some(body), &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()[LOCATION], "/");
+        let body = Body::from("username=jq&password=jq&next=%2F%09%2Fexample.com");
+        let resp = login
+            .handle(&Method::POST, &uri, &headers, Some(body), &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()[LOCATION], "/");
+
+        let logout: Uri = "/logout".parse().unwrap();
+        let resp = login
+            .handle(&Method::GET, &logout, &headers, None, &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[ALLOW], "POST");
+        assert!(resp.headers().get(SET_COOKIE).is_none());
+
+        let resp = login
+            .handle(&Method::POST, &logout, &headers, None, &users)
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()[LOCATION], "/login");
+        assert!(resp.headers()[SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .contains("Max-Age=0"));
+    }
+
+    #[test]
+    fn login_redirects() {
+        let login = login();
+        let uri: Uri = "/docs/a.html?b=c".parse().unwrap();
+        let resp = login.redirect_to_login(&Method::GET, &uri).unwrap();
+        assert_eq!(
+            resp.headers()[LOCATION],
+            "/login?next=%2Fdocs%2Fa.html%3Fb%3Dc"
+        );
+        assert!(login.redirect_to_login(&Method::POST, &uri).is_none());
+    }
+
+    #[test]
+    fn local_paths() {
+        assert!(is_local_path("/"));
+        assert!(is_local_path("/docs/a.html?b=c"));
+        assert!(!is_local_path("https://example.com/"));
+        assert!(!is_local_path("//example.com"));
+        assert!(!is_local_path("/\\example.com"));
+        assert!(!is_local_path("/\t/example.com"));
+        assert!(!is_local_path("/\n/example.com"));
+        assert!(!is_local_path("/ /example.com"));
+    }
+}
@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to delegate the authorization of requests to an external HTTP service.
+//!
+//! For every request, a subrequest is sent to the service along with the original
+//! method, URI and selected headers. A `2xx` response allows the request while
+//! `401`, `403` and redirect responses are passed through to the client.
+//! Selected headers of an allowing response are copied onto the final response.
+//!
+
+use headers::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{
+    client::HttpConnector,
+    header::{HOST, LOCATION, WWW_AUTHENTICATE},
+    Body, Client, Method, Request, StatusCode, Uri,
+};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::{auth::AuthError, Result};
+
+/// Default request headers sent to the authorization service.
+pub const DEFAULT_REQUEST_HEADERS: &str = "authorization, cookie";
+
+/// It defines the client of an authorization service.
+pub struct ForwardAuth {
+    client: Client<HttpConnector>,
+    url: Uri,
+    request_headers: Vec<HeaderName>,
+    response_headers: Vec<HeaderName>,
+    timeout: Duration,
+}
+
+impl ForwardAuth {
+    /// Creates a new authorization service client.
+    ///
+    /// `request_headers` and `response_headers` are comma-separated lists of the
+    /// headers sent to the service and the headers of its allowing responses to log
+    /// and copy onto the final responses.
+    pub fn new(
+        url: &str,
+        request_headers: &str,
+        response_headers: &str,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let url = url
+            .parse::<Uri>()
+            .map_err(|err| anyhow!("invalid forward auth url `{}`: {}", url, err))?;
+        if url.scheme_str() != Some("http") || url.host().is_none() {
+            bail!("forward auth url should be an absolute `http` URL: {}", url);
+        }
+        if timeout.is_zero() {
+            bail!("forward auth timeout should be greater than zero");
+        }
+
+        Ok(Self {
+            client: Client::new(),
+            url,
+            request_headers: parse_header_names(request_headers)?,
+            response_headers: parse_header_names(response_headers)?,
+            timeout,
+        })
+    }
+
+    /// Asks the authorization service whether a request is allowed.
+    /// It returns the response headers of the service to copy if allowed.
+    pub async fn check(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Result<HeaderMap, AuthError> {
+        let req = self.subrequest(method, uri, headers, client_ip);
+        let resp = match tokio::time::timeout(self.timeout, self.client.request(req)).await {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(err)) => {
+                tracing::error!("forward auth: request to {} failed: {}", self.url, err);
+                return Err(AuthError::from_status(StatusCode::BAD_GATEWAY));
+            }
+            Err(_) => {
+                tracing::error!("forward auth: request to {} timed out", self.url);
+                return Err(AuthError::from_status(StatusCode::GATEWAY_TIMEOUT));
+            }
+        };
+
+        let status = resp.status();
+        let resp_headers = resp.headers();
+        if status.is_success() {
+            let mut copied = HeaderMap::new();
+            for name in &self.response_headers {
+                for value in resp_headers.get_all(name) {
+                    copied.append(name, value.clone());
+                }
+            }
+            let logged = copied
+                .iter()
+                .filter_map(|(name, value)| Some(format!(" {name}={:?}", value.to_str().ok()?)))
+                .collect::<String>();
+            tracing::info!("forward auth: request allowed{}", logged);
+            return Ok(copied);
+        }
+
+        let mut err = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AuthError::from_status(status),
+            _ if status.is_redirection() && resp_headers.contains_key(LOCATION) => {
+                AuthError::from_status(status)
+            }
+            _ => {
+                tracing::error!(
+                    "forward auth: unexpected response status {} from {}",
+                    status,
+                    self.url
+                );
+                return Err(AuthError::from_status(StatusCode::BAD_GATEWAY));
+            }
+        };
+        err.www_authenticate = resp_headers.get(WWW_AUTHENTICATE).cloned();
+        err.location = resp_headers.get(LOCATION).cloned();
+        Err(err)
+    }
+
+    /// Builds the subrequest of an incoming request.
+    fn subrequest(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Request<Body> {
+        let mut req = Request::new(Body::empty());
+        *req.method_mut() = method.clone();
+        *req.uri_mut() = self.url.clone();
+
+        let sub_headers = req.headers_mut();
+        for name in &self.request_headers {
+            for value in headers.get_all(name) {
+                sub_headers.append(name, value.clone());
+            }
+        }
+        if let Ok(value) = HeaderValue::from_str(method.as_str()) {
+            sub_headers.insert("x-forwarded-method", value);
+        }
+        if let Some(value) = uri
+            .path_and_query()
+            .and_then(|v| HeaderValue::from_str(v.as_str()).ok())
+        {
+            sub_headers.insert("x-forwarded-uri", value);
+        }
+        let host = headers
+            .get(HOST)
+            .cloned()
+            .or_else(|| HeaderValue::from_str(uri.authority()?.as_str()).ok());
+        if let Some(host) = host {
+            sub_headers.insert("x-forwarded-host", host);
+        }
+        if let Some(ip) = client_ip.and_then(|ip| HeaderValue::from_str(&ip.to_string()).ok()) {
+            sub_headers.insert("x-forwarded-for", ip);
+        }
+        req
+    }
+}
+
+/// Parses a comma-separated list of header names.
+fn parse_header_names(s: &str) -> Result<Vec<HeaderName>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            HeaderName::from_bytes(v.as_bytes()).map_err(|_| anyhow!("invalid header name: {}", v))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use std::convert::Infallible;
+
+    /// Starts a stand-in authorization service on a local port.
+    /// It allows the requests of the `alice` user and redirects the others to a login page.
+    fn stand_in_service() -> String {
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+                let mut resp = Response::new(Body::empty());
+                match header("authorization") {
+                    Some("Bearer alice") => {
+                        assert_eq!(header("x-forwarded-method"), Some("GET"));
+                        assert_eq!(header("x-forwarded-uri"), Some("/private/a.html?b=c"));
+                        assert_eq!(header("x-forwarded-host"), Some("example.com"));
+                        assert_eq!(header("x-forwarded-for"), Some("10.0.0.1"));
+                        assert!(header("accept").is_none());
+                        resp.headers_mut()
+                            .insert("x-user", HeaderValue::from_static("alice"));
+                    }
+                    Some("Bearer bob") => *resp.status_mut() = StatusCode::FORBIDDEN,
+                    Some(_) => *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR,
+                    None => {
+                        *resp.status_mut() = StatusCode::FOUND;
+                        resp.headers_mut()
+                            .insert(LOCATION, HeaderValue::from_static("/login"));
+                    }
+                }
+                Ok::<_, Infallible>(resp)
+            }))
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        format!("http://{addr}/auth")
+    }
+
+    #[tokio::test]
+    async fn delegated_authorization() {
+        let url = stand_in_service();
+        let auth = ForwardAuth::new(
+            &url,
+            DEFAULT_REQUEST_HEADERS,
+            "x-user",
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        let uri: Uri = "/private/a.html?b=c".parse().unwrap();
+        let ip = Some("10.0.0.1".parse().unwrap());
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("example.com"));
+        headers.insert("accept", HeaderValue::from_static("text/html"));
+
+        let err = auth
+            .check(&Method::GET, &uri, &headers, ip)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::FOUND);
+        assert_eq!(err.location.unwrap(), "/login");
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer alice"));
+        let copied = auth.check(&Method::GET, &uri, &headers, ip).await.unwrap();
+        assert_eq!(copied.len(), 1);
+        assert_eq!(copied["x-user"], "alice");
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer bob"));
+        let err = auth
+            .check(&Method::GET, &uri, &headers, ip)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer eve"));
+        let err = auth
+            .check(&Method::GET, &uri, &headers, ip)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn unavailable_service() {
+        assert!(ForwardAuth::new("/auth", "", "", Duration::from_secs(1)).is_err());
+        assert!(ForwardAuth::new("https://auth.local/", "", "", Duration::from_secs(1)).is_err());
+
+        // Nothing listens on the port 9 (discard) of the loopback address
+        let auth = ForwardAuth::new("http://127.0.0.1:9/", "", "", Duration::from_secs(1)).unwrap();
+        let err = auth
+            .check(
+                &Method::GET,
+                &Uri::from_static("/"),
+                &HeaderMap::new(),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_GATEWAY);
+    }
+}
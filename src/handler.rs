@@ -6,25 +6,59 @@
 //! Request handler module intended to manage incoming HTTP requests.
 //!
 
-use headers::HeaderValue;
-use hyper::{Body, Request, Response, StatusCode};
-use std::{future::Future, net::IpAddr, net::SocketAddr, path::PathBuf, sync::Arc};
+use headers::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{
+    header::{AUTHORIZATION, LOCATION, RETRY_AFTER, WWW_AUTHENTICATE},
+    Body, Request, Response, StatusCode,
+};
+use regex::Regex;
+use std::{
+    future::Future, net::IpAddr, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration,
+    time::Instant,
+};
 
 #[cfg(feature = "compression")]
-use crate::compression;
+use crate::{
+    compression, compression_cache::CompressionCache, compression_dictionary::DictionaryStore,
+};
 
 #[cfg(feature = "basic-auth")]
-use {crate::basic_auth, hyper::header::WWW_AUTHENTICATE};
+use crate::basic_auth::UserStore;
+#[cfg(feature = "form-login")]
+use crate::form_login::FormLogin;
+#[cfg(feature = "forward-auth")]
+use crate::forward_auth::ForwardAuth;
+#[cfg(feature = "jwt-auth")]
+use crate::jwt_auth::JwtVerifier;
+#[cfg(feature = "signed-urls")]
+use crate::signed_urls::UrlSigner;
 
 #[cfg(feature = "fallback-page")]
 use crate::fallback_page;
 
+#[cfg(feature = "csp-nonce")]
+use {
+    crate::csp_nonce::{self, CspNonce},
+    hyper::header::CONTENT_SECURITY_POLICY,
+};
+
 use crate::{
-    control_headers, cors, custom_headers, error_page,
+    auth,
+    auth_throttle::AuthThrottle,
+    bandwidth::{self, Bandwidth, ConnectionBandwidth, Throttle},
+    control_headers, cors,
+    csp_reports::ReportCollector,
+    custom_headers, error_page,
     exts::http::MethodExt,
-    redirects, rewrites, security_headers,
-    settings::Advanced,
+    ip_filter::{self, IpFilter, IpNet},
+    load_shed::{InflightBody, LoadShedder},
+    rate_limit::{self, RateLimitState, RateLimiter},
+    redirects,
+    request_limits::RequestLimits,
+    rewrites, security_headers,
+    settings::{file::AuthKind, Advanced, Auth},
     static_files::{self, HandleOpts},
+    transport::ClientIdentity,
     Error, Result,
 };
 
@@ -40,6 +74,14 @@ pub struct RequestHandlerOpts {
     pub compression: bool,
     /// Compression static feature.
     pub compression_static: bool,
+    /// Compression cache feature.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_cache: Option<CompressionCache>,
+    /// Compression dictionary transport feature.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_dictionaries: Option<DictionaryStore>,
     /// Directory listing feature.
     #[cfg(feature = "directory-listing")]
     #[cfg_attr(docsrs, doc(cfg(feature = "directory-listing")))]
@@ -55,9 +97,17 @@ pub struct RequestHandlerOpts {
     /// CORS feature.
     pub cors: Option<cors::Configured>,
     /// Security headers feature.
-    pub security_headers: bool,
+    pub security_headers: Option<security_headers::Configured>,
+    /// CSP nonces feature.
+    #[cfg(feature = "csp-nonce")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "csp-nonce")))]
+    pub csp_nonce: Option<CspNonce>,
+    /// CSP violation reports collector feature.
+    pub csp_reports: Option<ReportCollector>,
     /// Cache control headers feature.
     pub cache_control_headers: bool,
+    /// Cache control for fingerprinted file names feature.
+    pub cache_control_fingerprint: Option<Regex>,
     /// Page for 404 errors.
     pub page404: Vec<u8>,
     /// Page for 50x errors.
@@ -69,7 +119,51 @@ pub struct RequestHandlerOpts {
     /// Basic auth feature.
     #[cfg(feature = "basic-auth")]
     #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
-    pub basic_auth: String,
+    pub basic_auth: Option<UserStore>,
+    /// JWT auth feature.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_auth: Option<JwtVerifier>,
+    /// Signed URLs feature.
+    #[cfg(feature = "signed-urls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+    pub signed_urls: Option<UrlSigner>,
+    /// Require signed URLs for the requests not matching an authentication rule.
+    #[cfg(feature = "signed-urls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+    pub signed_urls_required: bool,
+    /// Form login feature.
+    #[cfg(feature = "form-login")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+    pub form_login: Option<FormLogin>,
+    /// Forward authentication feature.
+    #[cfg(feature = "forward-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "forward-auth")))]
+    pub forward_auth: Option<ForwardAuth>,
+    /// Authentication failures throttle feature.
+    pub auth_throttle: Option<AuthThrottle>,
+    /// Client IP allow and deny lists feature.
+    pub ip_filter: IpFilter,
+    /// Proxies trusted to forward the client IP address.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Request rate limit feature.
+    pub rate_limit: Option<RateLimiter>,
+    /// Request header identifying the rate limited clients.
+    pub rate_limit_key: Option<HeaderName>,
+    /// Bandwidth limit per connection in bytes per second.
+    pub bandwidth_limit: u64,
+    /// Bandwidth limit shared by all the connections.
+    pub bandwidth_limit_global: Option<Arc<Bandwidth>>,
+    /// Bytes of every file response sent without bandwidth limits.
+    pub bandwidth_limit_after: u64,
+    /// Load shedding feature.
+    pub load_shed: Option<Arc<LoadShedder>>,
+    /// Seconds to wait before retrying the requests rejected by the load shedding.
+    pub load_shed_retry_after: u64,
+    /// Maximum time to send a whole response.
+    pub response_write_timeout: Option<Duration>,
+    /// Request size limits feature.
+    pub request_limits: RequestLimits,
     /// Log remote address feature.
     pub log_remote_address: bool,
     /// Redirect trailing slash feature.
@@ -81,6 +175,65 @@ pub struct RequestHandlerOpts {
     pub advanced_opts: Option<Advanced>,
 }
 
+impl Default for RequestHandlerOpts {
+    fn default() -> Self {
+        Self {
+            root_dir: PathBuf::from("./public"),
+            compression: true,
+            compression_static: false,
+            #[cfg(feature = "compression")]
+            compression_cache: None,
+            #[cfg(feature = "compression")]
+            compression_dictionaries: None,
+            #[cfg(feature = "directory-listing")]
+            dir_listing: false,
+            #[cfg(feature = "directory-listing")]
+            dir_listing_order: 6,
+            #[cfg(feature = "directory-listing")]
+            dir_listing_format: DirListFmt::Html,
+            cors: None,
+            security_headers: None,
+            #[cfg(feature = "csp-nonce")]
+            csp_nonce: None,
+            csp_reports: None,
+            cache_control_headers: true,
+            cache_control_fingerprint: None,
+            page404: Vec::new(),
+            page50x: Vec::new(),
+            #[cfg(feature = "fallback-page")]
+            page_fallback: Vec::new(),
+            #[cfg(feature = "basic-auth")]
+            basic_auth: None,
+            #[cfg(feature = "jwt-auth")]
+            jwt_auth: None,
+            #[cfg(feature = "signed-urls")]
+            signed_urls: None,
+            #[cfg(feature = "signed-urls")]
+            signed_urls_required: false,
+            #[cfg(feature = "form-login")]
+            form_login: None,
+            #[cfg(feature = "forward-auth")]
+            forward_auth: None,
+            auth_throttle: None,
+            ip_filter: IpFilter::default(),
+            trusted_proxies: Vec::new(),
+            rate_limit: None,
+            rate_limit_key: None,
+            bandwidth_limit: 0,
+            bandwidth_limit_global: None,
+            bandwidth_limit_after: 0,
+            load_shed: None,
+            load_shed_retry_after: 1,
+            response_write_timeout: None,
+            request_limits: RequestLimits::default(),
+            log_remote_address: false,
+            redirect_trailing_slash: true,
+            ignore_hidden_files: false,
+            advanced_opts: None,
+        }
+    }
+}
+
 /// It defines the main request handler used by the Hyper service request.
 pub struct RequestHandler {
     /// Request handler options.
@@ -94,6 +247,25 @@ impl RequestHandler {
         req: &'a mut Request<Body>,
         remote_addr: Option<SocketAddr>,
     ) -> impl Future<Output = Result<Response<Body>, Error>> + Send + 'a {
+        // Take the body of violation reports before borrowing the request
+        let report_body = match &self.opts.csp_reports {
+            Some(reports)
+                if req.method() == hyper::Method::POST && reports.is_endpoint(req.uri().path()) =>
+            {
+                Some(std::mem::take(req.body_mut()))
+            }
+            _ => None,
+        };
+        #[cfg(feature = "form-login")]
+        let login_body = match &self.opts.form_login {
+            Some(login)
+                if req.method() == hyper::Method::POST && login.is_login(req.uri().path()) =>
+            {
+                Some(std::mem::take(req.body_mut()))
+            }
+            _ => None,
+        };
+
         let method = req.method();
         let headers = req.headers();
         let uri = req.uri();
@@ -113,6 +285,8 @@ impl RequestHandler {
         let ignore_hidden_files = self.opts.ignore_hidden_files;
 
         let mut cors_headers: Option<http::HeaderMap> = None;
+        // Headers of the authorization service copied onto the response
+        let mut forward_headers = http::HeaderMap::new();
 
         // Log request information with its remote address if available
         let mut remote_addr_str = String::new();
@@ -130,6 +304,14 @@ impl RequestHandler {
                 remote_addr_str.push_str(&client_ip_address.to_string())
             }
         }
+        // Bandwidth limit of the connection if any
+        let connection_bandwidth = req.extensions().get::<ConnectionBandwidth>();
+        // Verified TLS client certificate of the connection if any
+        let client_identity = req.extensions().get::<ClientIdentity>();
+        if let Some(identity) = client_identity {
+            remote_addr_str.push_str(" client_cert=");
+            remote_addr_str.push_str(&format!("{:?}", identity.subject));
+        }
         tracing::info!(
             "incoming request: method={} uri={}{}",
             method,
@@ -137,7 +319,97 @@ impl RequestHandler {
             remote_addr_str,
         );
 
-        async move {
+        let client_ip = ip_filter::client_ip(remote_addr, headers, &self.opts.trusted_proxies);
+
+        let handling = async move {
+            // Client IP allow and deny lists
+            // NOTE: the global lists are checked first so a path rule can only restrict them further
+            let path_ip_filter = self
+                .opts
+                .advanced_opts
+                .as_ref()
+                .and_then(|advanced| ip_filter::get_rule(uri_path, &advanced.ip_filter));
+            if !self.opts.ip_filter.is_allowed(client_ip)
+                || path_ip_filter.is_some_and(|rule| !rule.filter.is_allowed(client_ip))
+            {
+                tracing::warn!("client ip {:?} is not allowed", client_ip);
+                return error_page::error_response(
+                    uri,
+                    method,
+                    &StatusCode::FORBIDDEN,
+                    &self.opts.page404,
+                    &self.opts.page50x,
+                );
+            }
+
+            // Path-scoped authentication rules take precedence over the global authentication schemes
+            let auth_rule = self
+                .opts
+                .advanced_opts
+                .as_ref()
+                .and_then(|advanced| auth::get_rule(uri_path, &advanced.auth));
+
+            // Clients banned after repeated authentication failures,
+            // only refused the requests going through an authentication check
+            #[cfg(feature = "form-login")]
+            let login_endpoint = self
+                .opts
+                .form_login
+                .as_ref()
+                .is_some_and(|login| login.is_endpoint(uri_path));
+            #[cfg(not(feature = "form-login"))]
+            let login_endpoint = false;
+            if let Some(retry_after) = self
+                .auth_retry_after(client_ip)
+                .filter(|_| login_endpoint || self.requires_auth(auth_rule))
+            {
+                let mut resp = error_page::error_response(
+                    uri,
+                    method,
+                    &StatusCode::TOO_MANY_REQUESTS,
+                    &self.opts.page404,
+                    &self.opts.page50x,
+                )?;
+                resp.headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+                return Ok(resp);
+            }
+
+            // CSP violation reports collector
+            if let (Some(reports), Some(body)) = (&self.opts.csp_reports, report_body) {
+                let status = reports.collect(headers, body, client_ip).await;
+                if status == StatusCode::NO_CONTENT {
+                    let mut resp = Response::new(Body::empty());
+                    *resp.status_mut() = status;
+                    return Ok(resp);
+                }
+                return error_page::error_response(
+                    uri,
+                    method,
+                    &status,
+                    &self.opts.page404,
+                    &self.opts.page50x,
+                );
+            }
+
+            #[cfg(feature = "form-login")]
+            // Form login page and logout endpoint
+            if let (Some(login), Some(users)) = (&self.opts.form_login, &self.opts.basic_auth) {
+                if login.is_endpoint(uri_path) {
+                    let resp = login
+                        .handle(method, uri, headers, login_body, users)
+                        .await?;
+                    if resp.status() == StatusCode::UNAUTHORIZED {
+                        self.auth_failure(client_ip);
+                    } else if login.is_login(uri_path)
+                        && resp.headers().contains_key(hyper::header::SET_COOKIE)
+                    {
+                        self.auth_success(client_ip);
+                    }
+                    return Ok(resp);
+                }
+            }
+
             // Reject in case of incoming HTTP request method is not allowed
             if !method.is_allowed() {
                 return error_page::error_response(
@@ -169,39 +441,176 @@ impl RequestHandler {
                 };
             }
 
-            #[cfg(feature = "basic-auth")]
-            // `Basic` HTTP Authorization Schema
-            if !self.opts.basic_auth.is_empty() {
-                if let Some((user_id, password)) = self.opts.basic_auth.split_once(':') {
-                    if let Err(err) = basic_auth::check_request(headers, user_id, password) {
-                        tracing::warn!("basic authentication failed {:?}", err);
+            if let Some(rule) = auth_rule {
+                match auth::check_request(
+                    rule,
+                    method,
+                    uri,
+                    headers,
+                    client_ip,
+                    client_identity,
+                    &self.opts,
+                    &mut forward_headers,
+                )
+                .await
+                {
+                    Ok(Some(userid)) => {
+                        tracing::debug!("auth rule: user `{}` authenticated", userid);
+                        if headers.contains_key(AUTHORIZATION) {
+                            self.auth_success(client_ip);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::warn!("auth rule failed with status {}", err.status);
+                        if err.status == StatusCode::UNAUTHORIZED
+                            && headers.contains_key(AUTHORIZATION)
+                        {
+                            self.auth_failure(client_ip);
+                        }
+                        #[cfg(feature = "form-login")]
+                        if let Some(login) = self.opts.form_login.as_ref().filter(|_| {
+                            rule.kind == AuthKind::Basic && err.status == StatusCode::UNAUTHORIZED
+                        }) {
+                            if let Some(resp) = login.redirect_to_login(method, uri) {
+                                return Ok(resp);
+                            }
+                        }
                         let mut resp = error_page::error_response(
                             uri,
                             method,
-                            &StatusCode::UNAUTHORIZED,
+                            &err.status,
                             &self.opts.page404,
                             &self.opts.page50x,
                         )?;
-                        resp.headers_mut().insert(
-                            WWW_AUTHENTICATE,
-                            "Basic realm=\"Static Web Server\", charset=\"UTF-8\""
-                                .parse()
-                                .unwrap(),
-                        );
+                        if let Some(value) = err.www_authenticate {
+                            resp.headers_mut().insert(WWW_AUTHENTICATE, value);
+                        }
+                        if let Some(value) = err.location {
+                            resp.headers_mut().insert(LOCATION, value);
+                        }
                         return Ok(resp);
                     }
-                } else {
-                    tracing::error!("invalid basic authentication `user_id:password` pairs");
+                }
+            }
+
+            #[cfg(feature = "basic-auth")]
+            // `Basic` HTTP Authorization Schema
+            if let Some(users) = self
+                .opts
+                .basic_auth
+                .as_ref()
+                .filter(|_| auth_rule.is_none())
+            {
+                let session = auth::session_user(headers, &self.opts);
+                let result = match session {
+                    Some(userid) => Ok(userid),
+                    None => users
+                        .check_request(headers)
+                        .await
+                        .inspect(|_| self.auth_success(client_ip)),
+                };
+                if let Err(err) = result {
+                    tracing::warn!("basic authentication failed {:?}", err);
+                    if headers.contains_key(AUTHORIZATION) {
+                        self.auth_failure(client_ip);
+                    }
+                    #[cfg(feature = "form-login")]
+                    if let Some(login) = &self.opts.form_login {
+                        if let Some(resp) = login.redirect_to_login(method, uri) {
+                            return Ok(resp);
+                        }
+                    }
+                    let mut resp = error_page::error_response(
+                        uri,
+                        method,
+                        &StatusCode::UNAUTHORIZED,
+                        &self.opts.page404,
+                        &self.opts.page50x,
+                    )?;
+                    resp.headers_mut().insert(
+                        WWW_AUTHENTICATE,
+                        "Basic realm=\"Static Web Server\", charset=\"UTF-8\""
+                            .parse()
+                            .unwrap(),
+                    );
+                    return Ok(resp);
+                }
+            }
+
+            #[cfg(feature = "jwt-auth")]
+            // JSON Web Token `Bearer` authentication
+            if let Some(verifier) = self.opts.jwt_auth.as_ref().filter(|_| auth_rule.is_none()) {
+                if let Err(err) = auth::check_jwt(auth::DEFAULT_REALM, &[], headers, Some(verifier))
+                {
+                    tracing::warn!("jwt authentication failed with status {}", err.status);
+                    if headers.contains_key(AUTHORIZATION) {
+                        self.auth_failure(client_ip);
+                    }
+                    let mut resp = error_page::error_response(
+                        uri,
+                        method,
+                        &err.status,
+                        &self.opts.page404,
+                        &self.opts.page50x,
+                    )?;
+                    if let Some(value) = err.www_authenticate {
+                        resp.headers_mut().insert(WWW_AUTHENTICATE, value);
+                    }
+                    return Ok(resp);
+                }
+            }
+
+            #[cfg(feature = "signed-urls")]
+            // Signed URLs
+            if let Some(signer) = self
+                .opts
+                .signed_urls
+                .as_ref()
+                .filter(|_| self.opts.signed_urls_required && auth_rule.is_none())
+            {
+                if let Err(err) = auth::check_signed_url(uri, client_ip, Some(signer)) {
+                    tracing::warn!("signed url verification failed with status {}", err.status);
                     return error_page::error_response(
                         uri,
                         method,
-                        &StatusCode::INTERNAL_SERVER_ERROR,
+                        &err.status,
                         &self.opts.page404,
                         &self.opts.page50x,
                     );
                 }
             }
 
+            #[cfg(feature = "forward-auth")]
+            // Authorization service
+            if let Some(forward_auth) = self
+                .opts
+                .forward_auth
+                .as_ref()
+                .filter(|_| auth_rule.is_none())
+            {
+                match forward_auth.check(method, uri, headers, client_ip).await {
+                    Ok(copied) => forward_headers.extend(copied),
+                    Err(err) => {
+                        tracing::warn!("forward authentication failed with status {}", err.status);
+                        let mut resp = error_page::error_response(
+                            uri,
+                            method,
+                            &err.status,
+                            &self.opts.page404,
+                            &self.opts.page50x,
+                        )?;
+                        if let Some(value) = err.www_authenticate {
+                            resp.headers_mut().insert(WWW_AUTHENTICATE, value);
+                        }
+                        if let Some(value) = err.location {
+                            resp.headers_mut().insert(LOCATION, value);
+                        }
+                        return Ok(resp);
+                    }
+                }
+            }
+
             // Advanced options
             if let Some(advanced) = &self.opts.advanced_opts {
                 // Redirects
@@ -210,7 +619,7 @@ impl RequestHandler {
                     match HeaderValue::from_str(uri_dest) {
                         Ok(loc) => {
                             let mut resp = Response::new(Body::empty());
-                            resp.headers_mut().insert(hyper::header::LOCATION, loc);
+                            resp.headers_mut().insert(LOCATION, loc);
                             *resp.status_mut() = *status;
                             tracing::trace!(
                                 "uri matches redirect pattern, redirecting with status {}",
@@ -238,7 +647,7 @@ impl RequestHandler {
             }
 
             // Static files
-            match static_files::handle(&HandleOpts {
+            let handle_opts = HandleOpts {
                 method,
                 headers,
                 base_path,
@@ -253,10 +662,30 @@ impl RequestHandler {
                 redirect_trailing_slash,
                 compression_static,
                 ignore_hidden_files,
-            })
-            .await
+            };
+            let result = static_files::handle_response(&handle_opts).await;
+
+            // Pre-compressed HTML documents can not get a CSP nonce so their original file is served instead
+            #[cfg(feature = "csp-nonce")]
+            let result = if self.opts.csp_nonce.is_some()
+                && matches!(&result, Ok(r) if r.is_precompressed && csp_nonce::is_html(&r.resp))
             {
-                Ok((mut resp, _is_precompressed)) => {
+                tracing::trace!(
+                    "csp nonce: skipping the pre-compressed variant of an html document"
+                );
+                let handle_opts = HandleOpts {
+                    compression_static: false,
+                    ..handle_opts
+                };
+                static_files::handle_response(&handle_opts).await
+            } else {
+                result
+            };
+
+            match result {
+                Ok(result) => {
+                    let mut resp = result.resp;
+
                     // Append CORS headers if they are present
                     if let Some(cors_headers) = cors_headers {
                         if !cors_headers.is_empty() {
@@ -267,6 +696,40 @@ impl RequestHandler {
                         }
                     }
 
+                    // Append the headers of the authorization service
+                    resp.headers_mut().extend(forward_headers);
+
+                    // Inject a CSP nonce into HTML documents before compressing them
+                    #[cfg(feature = "csp-nonce")]
+                    let mut csp_nonce_policy = None;
+                    #[cfg(feature = "csp-nonce")]
+                    if let Some(csp_nonce) = &self.opts.csp_nonce {
+                        if !result.is_precompressed {
+                            resp = match csp_nonce.rewrite(method, resp).await {
+                                Ok((res, policy)) => {
+                                    csp_nonce_policy = policy;
+                                    res
+                                }
+                                Err(err) => {
+                                    tracing::error!("error during csp nonce injection: {:?}", err);
+                                    return error_page::error_response(
+                                        uri,
+                                        method,
+                                        &StatusCode::INTERNAL_SERVER_ERROR,
+                                        &self.opts.page404,
+                                        &self.opts.page50x,
+                                    );
+                                }
+                            };
+                        }
+                    }
+
+                    // Rewritten bodies no longer match their files so they are compressed on the fly
+                    #[cfg(all(feature = "compression", feature = "csp-nonce"))]
+                    let is_rewritten = csp_nonce_policy.is_some();
+                    #[cfg(all(feature = "compression", not(feature = "csp-nonce")))]
+                    let is_rewritten = false;
+
                     // Compression content encoding varies so use a `Vary` header
                     #[cfg(feature = "compression")]
                     if self.opts.compression || compression_static {
@@ -276,10 +739,68 @@ impl RequestHandler {
                         );
                     }
 
+                    // Dictionary compression based on the `Available-Dictionary` header
+                    #[cfg(feature = "compression")]
+                    let mut is_dictionary_compressed = false;
+                    #[cfg(feature = "compression")]
+                    if let Some(dictionaries) = self
+                        .opts
+                        .compression_dictionaries
+                        .as_ref()
+                        .filter(|_| !is_rewritten)
+                    {
+                        resp.headers_mut().append(
+                            hyper::header::VARY,
+                            hyper::header::HeaderValue::from_name(
+                                compression::AVAILABLE_DICTIONARY,
+                            ),
+                        );
+                        resp = match dictionaries
+                            .auto(
+                                method,
+                                headers,
+                                uri_path,
+                                &result.file_path,
+                                resp,
+                                self.opts.compression,
+                                compression_static,
+                                self.opts.compression_cache.as_ref(),
+                            )
+                            .await
+                        {
+                            Ok((res, compressed)) => {
+                                is_dictionary_compressed = compressed;
+                                res
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    "error during dictionary body compression: {:?}",
+                                    err
+                                );
+                                return error_page::error_response(
+                                    uri,
+                                    method,
+                                    &StatusCode::INTERNAL_SERVER_ERROR,
+                                    &self.opts.page404,
+                                    &self.opts.page50x,
+                                );
+                            }
+                        };
+                    }
+
                     // Auto compression based on the `Accept-Encoding` header
                     #[cfg(feature = "compression")]
-                    if self.opts.compression && !_is_precompressed {
-                        resp = match compression::auto(method, headers, resp) {
+                    if self.opts.compression
+                        && !result.is_precompressed
+                        && !is_dictionary_compressed
+                    {
+                        let compressed = match &self.opts.compression_cache {
+                            Some(cache) if !is_rewritten => {
+                                cache.auto(method, headers, &result.file_path, resp).await
+                            }
+                            _ => compression::auto(method, headers, resp),
+                        };
+                        resp = match compressed {
                             Ok(res) => res,
                             Err(err) => {
                                 tracing::error!("error during body compression: {:?}", err);
@@ -296,12 +817,26 @@ impl RequestHandler {
 
                     // Append `Cache-Control` headers for web assets
                     if self.opts.cache_control_headers {
-                        control_headers::append_headers(uri_path, &mut resp);
+                        control_headers::append_cache_headers(
+                            uri_path,
+                            self.opts
+                                .advanced_opts
+                                .as_ref()
+                                .and_then(|v| v.cache_control.as_deref()),
+                            self.opts.cache_control_fingerprint.as_ref(),
+                            &mut resp,
+                        );
                     }
 
                     // Append security headers
-                    if self.opts.security_headers {
-                        security_headers::append_headers(&mut resp);
+                    if let Some(security_headers) = &self.opts.security_headers {
+                        security_headers.append_headers(uri_path, &mut resp);
+                    }
+
+                    // Append the `Content-Security-Policy` header matching the CSP nonce
+                    #[cfg(feature = "csp-nonce")]
+                    if let Some(policy) = csp_nonce_policy {
+                        resp.headers_mut().insert(CONTENT_SECURITY_POLICY, policy);
                     }
 
                     // Add/update custom headers
@@ -309,6 +844,11 @@ impl RequestHandler {
                         custom_headers::append_headers(uri_path, &advanced.headers, &mut resp)
                     }
 
+                    // Limit the bandwidth of the final body, once rewritten or compressed
+                    if let Some(throttle) = self.throttle(uri_path, connection_bandwidth) {
+                        resp = resp.map(|body| Body::wrap_stream(throttle.wrap(body)));
+                    }
+
                     Ok(resp)
                 }
                 Err(status) => {
@@ -331,6 +871,32 @@ impl RequestHandler {
                             }
                         }
 
+                        // Append the headers of the authorization service
+                        resp.headers_mut().extend(forward_headers);
+
+                        // Inject a CSP nonce into the fallback page before compressing it
+                        #[cfg(feature = "csp-nonce")]
+                        let mut csp_nonce_policy = None;
+                        #[cfg(feature = "csp-nonce")]
+                        if let Some(csp_nonce) = &self.opts.csp_nonce {
+                            resp = match csp_nonce.rewrite(method, resp).await {
+                                Ok((res, policy)) => {
+                                    csp_nonce_policy = policy;
+                                    res
+                                }
+                                Err(err) => {
+                                    tracing::error!("error during csp nonce injection: {:?}", err);
+                                    return error_page::error_response(
+                                        uri,
+                                        method,
+                                        &StatusCode::INTERNAL_SERVER_ERROR,
+                                        &self.opts.page404,
+                                        &self.opts.page50x,
+                                    );
+                                }
+                            };
+                        }
+
                         // Compression content encoding varies so use a `Vary` header
                         #[cfg(feature = "compression")]
                         if self.opts.compression || compression_static {
@@ -362,12 +928,26 @@ impl RequestHandler {
 
                         // Append `Cache-Control` headers for web assets
                         if self.opts.cache_control_headers {
-                            control_headers::append_headers(uri_path, &mut resp);
+                            control_headers::append_cache_headers(
+                                uri_path,
+                                self.opts
+                                    .advanced_opts
+                                    .as_ref()
+                                    .and_then(|v| v.cache_control.as_deref()),
+                                self.opts.cache_control_fingerprint.as_ref(),
+                                &mut resp,
+                            );
                         }
 
                         // Append security headers
-                        if self.opts.security_headers {
-                            security_headers::append_headers(&mut resp);
+                        if let Some(security_headers) = &self.opts.security_headers {
+                            security_headers.append_headers(uri_path, &mut resp);
+                        }
+
+                        // Append the `Content-Security-Policy` header matching the CSP nonce
+                        #[cfg(feature = "csp-nonce")]
+                        if let Some(policy) = csp_nonce_policy {
+                            resp.headers_mut().insert(CONTENT_SECURITY_POLICY, policy);
                         }
 
                         // Add/update custom headers
@@ -388,6 +968,178 @@ impl RequestHandler {
                     )
                 }
             }
+        };
+
+        async move {
+            // Oversized request URI or headers
+            if let Some(status) = self.opts.request_limits.check(uri, headers) {
+                tracing::warn!("request exceeds the size limits");
+                return error_page::error_response(
+                    uri,
+                    method,
+                    &status,
+                    &self.opts.page404,
+                    &self.opts.page50x,
+                );
+            }
+
+            // Reject requests while the server is overloaded
+            let inflight = match &self.opts.load_shed {
+                Some(shedder) => {
+                    let (guard, overloaded) = shedder.enter();
+                    if overloaded {
+                        tracing::warn!("server overloaded, shedding the request");
+                        let mut resp = error_page::error_response(
+                            uri,
+                            method,
+                            &StatusCode::SERVICE_UNAVAILABLE,
+                            &self.opts.page404,
+                            &self.opts.page50x,
+                        )?;
+                        resp.headers_mut().insert(
+                            RETRY_AFTER,
+                            HeaderValue::from(self.opts.load_shed_retry_after),
+                        );
+                        return Ok(resp);
+                    }
+                    Some(guard)
+                }
+                None => None,
+            };
+
+            // Request rate limit of the client
+            // NOTE: the token is only taken once the request passed the size limits and load shedding
+            let rate_limit = self.rate_limit(uri_path, headers, client_ip);
+            if let Some(state) = rate_limit.filter(|state| !state.allowed) {
+                tracing::warn!("client {:?} exceeded the request rate limit", client_ip);
+                let mut resp = error_page::error_response(
+                    uri,
+                    method,
+                    &StatusCode::TOO_MANY_REQUESTS,
+                    &self.opts.page404,
+                    &self.opts.page50x,
+                )?;
+                state.append_headers(resp.headers_mut());
+                return Ok(resp);
+            }
+
+            let mut resp = handling.await?;
+            if let Some(state) = rate_limit {
+                state.append_headers(resp.headers_mut());
+            }
+            // The request stays in flight until its response body is sent
+            if let Some(guard) = inflight {
+                resp = resp.map(|body| InflightBody::wrap(body, guard));
+            }
+            Ok(resp)
+        }
+    }
+
+    /// Takes a token from the rate limit bucket of the client of a request if any.
+    /// Path-scoped rules take precedence over the global limit.
+    fn rate_limit(
+        &self,
+        uri_path: &str,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Option<RateLimitState> {
+        let limiter = match self
+            .opts
+            .advanced_opts
+            .as_ref()
+            .and_then(|advanced| rate_limit::get_rule(uri_path, &advanced.rate_limit))
+        {
+            Some(rule) => rule.limiter.as_ref(),
+            None => self.opts.rate_limit.as_ref(),
+        }?;
+        let key = rate_limit::client_key(headers, self.opts.rate_limit_key.as_ref(), client_ip)?;
+        Some(limiter.check(&key, Instant::now()))
+    }
+
+    /// Returns the bandwidth limits of a file response if any.
+    /// Path-scoped rules replace the limit of the connection but not the global one.
+    fn throttle(
+        &self,
+        uri_path: &str,
+        connection: Option<&ConnectionBandwidth>,
+    ) -> Option<Throttle> {
+        let rule = self
+            .opts
+            .advanced_opts
+            .as_ref()
+            .and_then(|advanced| bandwidth::get_rule(uri_path, &advanced.bandwidth_limit));
+        let after = rule
+            .and_then(|rule| rule.after)
+            .unwrap_or(self.opts.bandwidth_limit_after);
+
+        let mut throttle = Throttle::new(after);
+        match rule {
+            Some(rule) => {
+                if let Some(rate) = rule.rate {
+                    throttle = throttle.with_limit(Arc::new(Bandwidth::new(rate)));
+                }
+            }
+            None => {
+                if let Some(connection) = connection {
+                    throttle = throttle.with_limit(connection.0.clone());
+                }
+            }
+        }
+        if let Some(global) = &self.opts.bandwidth_limit_global {
+            throttle = throttle.with_limit(global.clone());
+        }
+        Some(throttle).filter(|throttle| !throttle.is_empty())
+    }
+
+    /// Checks whether the requests matching the given authentication rule if any
+    /// go through an authentication check.
+    fn requires_auth(&self, auth_rule: Option<&Auth>) -> bool {
+        if let Some(rule) = auth_rule {
+            return rule.kind != AuthKind::None;
+        }
+        #[cfg(feature = "basic-auth")]
+        if self.opts.basic_auth.is_some() {
+            return true;
+        }
+        #[cfg(feature = "jwt-auth")]
+        if self.opts.jwt_auth.is_some() {
+            return true;
+        }
+        #[cfg(feature = "signed-urls")]
+        if self.opts.signed_urls.is_some() && self.opts.signed_urls_required {
+            return true;
+        }
+        #[cfg(feature = "forward-auth")]
+        if self.opts.forward_auth.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Returns the remaining ban duration in seconds of a client failing to authenticate.
+    fn auth_retry_after(&self, client_ip: Option<IpAddr>) -> Option<u64> {
+        let throttle = self.opts.auth_throttle.as_ref()?;
+        let remaining = throttle.retry_after(client_ip?, Instant::now())?;
+        Some(remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0))
+    }
+
+    /// Records an authentication failure of a client presenting credentials.
+    fn auth_failure(&self, client_ip: Option<IpAddr>) {
+        if let (Some(throttle), Some(ip)) = (&self.opts.auth_throttle, client_ip) {
+            if let Some(ban) = throttle.failure(ip, Instant::now()) {
+                tracing::warn!(
+                    "client ip {} banned for {}s after repeated authentication failures",
+                    ip,
+                    ban.as_secs()
+                );
+            }
+        }
+    }
+
+    /// Forgets the authentication failures of a client.
+    fn auth_success(&self, client_ip: Option<IpAddr>) {
+        if let (Some(throttle), Some(ip)) = (&self.opts.auth_throttle, client_ip) {
+            throttle.success(ip);
         }
     }
 }
@@ -3,6 +3,7 @@
 // See https://static-web-server.net/ for more information
 // Copyright (C) 2019-present Jose Quintana <joseluisq.net>
 
+use hyper::{body::HttpBody, Body};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -25,6 +26,22 @@ pub fn read_bytes(path: &Path) -> Result<Vec<u8>> {
     fs::read(path).with_context(|| format!("failed to read file `{}`", path.display()))
 }
 
+/// Reads a request body up to `max_size` bytes. It returns `None` if the body is larger.
+pub async fn read_body(mut body: Body, max_size: u64) -> Result<Option<Vec<u8>>> {
+    if body.size_hint().lower() > max_size {
+        return Ok(None);
+    }
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if (data.len() + chunk.len()) as u64 > max_size {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(data))
+}
+
 /// Read the entire contents of a file into a bytes vector or default to empty.
 pub fn read_bytes_default(path: &Path) -> Vec<u8> {
     fs::read(path).unwrap_or_default()
@@ -63,6 +80,11 @@ pub fn stringify(dst: &mut String, path: &serde_ignored::Path<'_>) {
     }
 }
 
+/// Compares two byte slices in constant time.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 #[cfg(unix)]
 /// In Unix-like systems it just casts the `PathBuf` into an string.
 pub fn adjust_canonicalization(p: &Path) -> String {
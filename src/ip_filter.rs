@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to allow or deny requests by client IP addresses and CIDR ranges.
+//!
+
+use headers::HeaderMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use crate::{settings::IpFilterRule, Result};
+
+/// It defines an IP address range in CIDR notation (e.g. `10.0.0.0/8`).
+/// A single address is a range of one element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    /// Checks whether the range contains the given address.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| anyhow!("invalid ip address or cidr range: {}", s))?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => match prefix.parse::<u8>() {
+                Ok(prefix) if prefix <= max => prefix,
+                _ => bail!("invalid cidr prefix length: {}", s),
+            },
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Parses a list of IP addresses or CIDR ranges separated by commas.
+pub fn parse_list(s: &str) -> Result<Vec<IpNet>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(IpNet::from_str)
+        .collect()
+}
+
+/// It defines allow and deny lists of IP addresses and CIDR ranges.
+#[derive(Debug, Default)]
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    /// Creates a new filter from the given lists.
+    pub fn new(allow: Vec<IpNet>, deny: Vec<IpNet>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Checks whether the filter has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Checks whether a client address is allowed.
+    ///
+    /// Denied addresses are always rejected. If the allow list is not empty
+    /// then only the addresses it contains are accepted.
+    /// Unknown client addresses are only accepted when there is no allow list.
+    pub fn is_allowed(&self, ip: Option<IpAddr>) -> bool {
+        match ip {
+            Some(ip) => {
+                !self.deny.iter().any(|net| net.contains(ip))
+                    && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip)))
+            }
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+/// It returns the first IP filter rule matching the request uri path if any.
+pub fn get_rule<'a>(
+    uri_path: &str,
+    rules: &'a Option<Vec<IpFilterRule>>,
+) -> Option<&'a IpFilterRule> {
+    rules
+        .as_ref()?
+        .iter()
+        .find(|rule| rule.source.is_match(uri_path))
+}
+
+/// Returns the client IP address of a request.
+///
+/// If the connection comes from a trusted proxy then the `X-Forwarded-For` header
+/// is read from right to left and its first untrusted address is used.
+/// Otherwise, the connection address is used.
+pub fn client_ip(
+    remote_addr: Option<SocketAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let remote_ip = remote_addr?.ip().to_canonical();
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if !is_trusted(remote_ip) {
+        return Some(remote_ip);
+    }
+
+    let mut client_ip = remote_ip;
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .collect::<Vec<_>>();
+    for hop in forwarded.iter().rev() {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client_ip = ip.to_canonical();
+                if !is_trusted(client_ip) {
+                    break;
+                }
+            }
+            // A malformed hop can't be trusted so the last known address is used
+            Err(_) => break,
+        }
+    }
+    Some(client_ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn cidr_ranges() {
+        let net: IpNet = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("10.1.200.3")));
+        assert!(net.contains(ip("::ffff:10.1.0.1")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!(!net.contains(ip("::1")));
+
+        let net: IpNet = "2001:db8::/32".parse().unwrap();
+        assert!(net.contains(ip("2001:db8:1::1")));
+        assert!(!net.contains(ip("2001:db9::1")));
+
+        let net: IpNet = "192.168.1.7".parse().unwrap();
+        assert_eq!(net.to_string(), "192.168.1.7/32");
+        assert!(net.contains(ip("192.168.1.7")));
+        assert!(!net.contains(ip("192.168.1.8")));
+
+        assert!("0.0.0.0/0"
+            .parse::<IpNet>()
+            .unwrap()
+            .contains(ip("8.8.8.8")));
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("example.com".parse::<IpNet>().is_err());
+        assert_eq!(parse_list(" 10.0.0.0/8, ,::1 ").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let filter = IpFilter::new(
+            parse_list("10.0.0.0/8").unwrap(),
+            parse_list("10.0.0.13").unwrap(),
+        );
+        assert!(filter.is_allowed(Some(ip("10.0.0.12"))));
+        assert!(!filter.is_allowed(Some(ip("10.0.0.13"))));
+        assert!(!filter.is_allowed(Some(ip("192.168.0.1"))));
+        assert!(!filter.is_allowed(None));
+
+        let filter = IpFilter::new(vec![], parse_list("192.168.0.0/24").unwrap());
+        assert!(filter.is_allowed(Some(ip("10.0.0.1"))));
+        assert!(!filter.is_allowed(Some(ip("192.168.0.1"))));
+        assert!(filter.is_allowed(None));
+        assert!(IpFilter::default().is_empty());
+    }
+
+    #[test]
+    fn client_ip_behind_proxies() {
+        let trusted = parse_list("10.0.0.0/8").unwrap();
+        let proxy: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        let direct: SocketAddr = "203.0.113.9:4000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "198.51.100.1, 203.0.113.7, 10.0.0.5".parse().unwrap(),
+        );
+
+        assert_eq!(
+            client_ip(Some(proxy), &headers, &trusted),
+            Some(ip("203.0.113.7"))
+        );
+        // The header is ignored for untrusted connections
+        assert_eq!(
+            client_ip(Some(direct), &headers, &trusted),
+            Some(ip("203.0.113.9"))
+        );
+        assert_eq!(client_ip(Some(proxy), &headers, &[]), Some(ip("10.0.0.2")));
+
+        headers.insert("x-forwarded-for", "10.0.0.7, 10.0.0.5".parse().unwrap());
+        assert_eq!(
+            client_ip(Some(proxy), &headers, &trusted),
+            Some(ip("10.0.0.7"))
+        );
+        headers.insert("x-forwarded-for", "garbage, 10.0.0.5".parse().unwrap());
+        assert_eq!(
+            client_ip(Some(proxy), &headers, &trusted),
+            Some(ip("10.0.0.5"))
+        );
+        assert_eq!(client_ip(None, &headers, &trusted), None);
+    }
+}
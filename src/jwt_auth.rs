@@ -0,0 +1,597 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! JSON Web Token (JWT) `Bearer` authentication module.
+//!
+//! Tokens signed with `RS256`, `ES256` or `EdDSA` (Ed25519) are verified
+//! against the public keys of a local JWKS or PEM file.
+//!
+
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use headers::{authorization::Bearer, Authorization, Cookie, HeaderMap, HeaderMapExt};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use x509_parser::{
+    oid_registry::{
+        OID_EC_P256, OID_KEY_TYPE_EC_PUBLIC_KEY, OID_PKCS1_RSAENCRYPTION, OID_SIG_ED25519,
+    },
+    prelude::FromDer,
+    x509::SubjectPublicKeyInfo,
+};
+
+use crate::{Context, Result};
+
+/// Default clock skew in seconds tolerated when checking the `exp` and `nbf` claims.
+pub const DEFAULT_LEEWAY: u64 = 60;
+
+/// Claims of a verified token.
+pub type Claims = Map<String, Value>;
+
+/// Base64url engine accepting both padded and unpadded input.
+const BASE64URL: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Supported signature algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Rs256,
+    Es256,
+    EdDsa,
+}
+
+impl Algorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "RS256" => Some(Self::Rs256),
+            "ES256" => Some(Self::Es256),
+            "EdDSA" => Some(Self::EdDsa),
+            _ => None,
+        }
+    }
+}
+
+/// Public key material.
+#[derive(Debug)]
+enum KeyData {
+    /// RSA modulus and exponent of a JWK.
+    RsaComponents { n: Vec<u8>, e: Vec<u8> },
+    /// PKCS#1 RSA public key, uncompressed P-256 point or raw Ed25519 key.
+    Raw(Vec<u8>),
+}
+
+/// A public key used to verify token signatures.
+#[derive(Debug)]
+struct Key {
+    kid: Option<String>,
+    alg: Algorithm,
+    data: KeyData,
+}
+
+impl Key {
+    fn verify(&self, message: &[u8], sig: &[u8]) -> bool {
+        match (&self.data, self.alg) {
+            (KeyData::RsaComponents { n, e }, _) => RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, sig)
+                .is_ok(),
+            (KeyData::Raw(key), Algorithm::Rs256) => {
+                UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, key)
+                    .verify(message, sig)
+                    .is_ok()
+            }
+            (KeyData::Raw(key), Algorithm::Es256) => {
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, key)
+                    .verify(message, sig)
+                    .is_ok()
+            }
+            (KeyData::Raw(key), Algorithm::EdDsa) => {
+                UnparsedPublicKey::new(&signature::ED25519, key)
+                    .verify(message, sig)
+                    .is_ok()
+            }
+        }
+    }
+}
+
+/// It defines the JWT verifier of the server.
+#[derive(Debug)]
+pub struct JwtVerifier {
+    keys: Vec<Key>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    cookie: Option<String>,
+    leeway: u64,
+}
+
+impl JwtVerifier {
+    /// Creates a new verifier using the public keys of a JWKS or PEM file.
+    /// Empty `issuer`, `audience` or `cookie` values disable their respective checks.
+    pub fn new(
+        keys_file: &Path,
+        issuer: &str,
+        audience: &str,
+        cookie: &str,
+        leeway: u64,
+    ) -> Result<Self> {
+        let data = std::fs::read_to_string(keys_file)
+            .with_context(|| format!("failed to read jwt keys file {}", keys_file.display()))?;
+        let keys = parse_keys(&data)
+            .with_context(|| format!("failed to parse jwt keys file {}", keys_file.display()))?;
+
+        let non_empty = |v: &str| (!v.is_empty()).then(|| v.to_owned());
+        Ok(Self {
+            keys,
+            issuer: non_empty(issuer),
+            audience: non_empty(audience),
+            cookie: non_empty(cookie),
+            leeway,
+        })
+    }
+
+    /// Returns the number of public keys.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Checks whether there are no public keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the token of a request taken from the `Authorization: Bearer` header
+    /// or from the configured cookie otherwise.
+    pub fn token(&self, headers: &HeaderMap) -> Option<String> {
+        if let Some(Authorization(bearer)) = headers.typed_get::<Authorization<Bearer>>() {
+            return Some(bearer.token().to_owned());
+        }
+        let name = self.cookie.as_deref()?;
+        headers
+            .typed_get::<Cookie>()
+            .and_then(|cookie| cookie.get(name).map(|v| v.to_owned()))
+    }
+
+    /// Verifies the signature and the registered claims of a token at the given UNIX time.
+    /// It returns the token claims on success.
+    pub fn verify_at(&self, token: &str, now: u64) -> Result<Claims> {
+        let mut parts = token.split('.');
+        let (header, payload, sig) = match (parts.next(), parts.next(), parts.next(), parts.next())
+        {
+            (Some(h), Some(p), Some(s), None) => (h, p, s),
+            _ => bail!("malformed token"),
+        };
+        let message = &token.as_bytes()[..header.len() + 1 + payload.len()];
+
+        let header: Claims = BASE64URL
+            .decode(header)
+            .ok()
+            .and_then(|v| serde_json::from_slice(&v).ok())
+            .ok_or_else(|| anyhow!("malformed token header"))?;
+        let alg = header
+            .get("alg")
+            .and_then(Value::as_str)
+            .and_then(Algorithm::from_name)
+            .ok_or_else(|| anyhow!("unsupported token algorithm"))?;
+        let kid = header.get("kid").and_then(Value::as_str);
+
+        let sig = BASE64URL
+            .decode(sig)
+            .map_err(|_| anyhow!("malformed token signature"))?;
+        let verified = self
+            .keys
+            .iter()
+            .filter(|k| k.alg == alg)
+            .filter(|k| match (kid, &k.kid) {
+                (Some(kid), Some(key_kid)) => kid == key_kid,
+                _ => true,
+            })
+            .any(|k| k.verify(message, &sig));
+        if !verified {
+            bail!("invalid token signature");
+        }
+
+        let claims: Claims = BASE64URL
+            .decode(payload)
+            .ok()
+            .and_then(|v| serde_json::from_slice(&v).ok())
+            .ok_or_else(|| anyhow!("malformed token payload"))?;
+
+        let now = now as f64;
+        let leeway = self.leeway as f64;
+        if let Some(exp) = claims.get("exp") {
+            match exp.as_f64() {
+                Some(exp) if now <= exp + leeway => {}
+                _ => bail!("token expired"),
+            }
+        }
+        if let Some(nbf) = claims.get("nbf") {
+            match nbf.as_f64() {
+                Some(nbf) if now + leeway >= nbf => {}
+                _ => bail!("token not yet valid"),
+            }
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+                bail!("invalid token issuer");
+            }
+        }
+        if let Some(audience) = &self.audience {
+            if !claims
+                .get("aud")
+                .is_some_and(|aud| claim_matches(aud, audience))
+            {
+                bail!("invalid token audience");
+            }
+        }
+
+        Ok(claims)
+    }
+
+    /// Verifies a token at the current time.
+    pub fn verify(&self, token: &str) -> Result<Claims> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.verify_at(token, now)
+    }
+}
+
+/// Checks whether the claims contain all the required `(name, value)` pairs.
+/// A claim matches if it is equal to the value or if it is an array containing it.
+pub fn has_claims(claims: &Claims, required: &[(String, String)]) -> bool {
+    required.iter().all(|(name, value)| {
+        claims
+            .get(name)
+            .is_some_and(|claim| claim_matches(claim, value))
+    })
+}
+
+fn claim_matches(claim: &Value, value: &str) -> bool {
+    match claim {
+        Value::String(s) => s == value,
+        Value::Array(items) => items.iter().any(|item| claim_matches(item, value)),
+        Value::Number(n) => n.to_string() == value,
+        Value::Bool(b) => b.to_string() == value,
+        _ => false,
+    }
+}
+
+/// Parses the public keys of a JWKS document (or a single JWK) or of PEM blocks.
+fn parse_keys(data: &str) -> Result<Vec<Key>> {
+    let keys = if data.trim_start().starts_with('{') {
+        parse_jwks(data)?
+    } else {
+        parse_pem(data)?
+    };
+    if keys.is_empty() {
+        bail!("no supported public keys found");
+    }
+    Ok(keys)
+}
+
+fn parse_jwks(data: &str) -> Result<Vec<Key>> {
+    let doc: Value = serde_json::from_str(data)?;
+    let jwks = match doc.get("keys") {
+        Some(Value::Array(keys)) => keys.to_owned(),
+        Some(_) => bail!("invalid jwks `keys` member"),
+        None => vec![doc],
+    };
+
+    let mut keys = Vec::new();
+    for jwk in jwks.iter() {
+        let kid = jwk.get("kid").and_then(Value::as_str);
+        if jwk.get("use").and_then(Value::as_str).unwrap_or("sig") != "sig" {
+            continue;
+        }
+        match parse_jwk(jwk)? {
+            Some((alg, data)) => {
+                if let Some(name) = jwk.get("alg").and_then(Value::as_str) {
+                    if Algorithm::from_name(name) != Some(alg) {
+                        tracing::warn!("jwt: ignoring key {:?} with algorithm {}", kid, name);
+                        continue;
+                    }
+                }
+                keys.push(Key {
+                    kid: kid.map(|v| v.to_owned()),
+                    alg,
+                    data,
+                })
+            }
+            None => tracing::warn!("jwt: ignoring unsupported key {:?}", kid),
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_jwk(jwk: &Value) -> Result<Option<(Algorithm, KeyData)>> {
+    let member = |name: &str| -> Result<Vec<u8>> {
+        let value = jwk
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("jwk `{}` member is missing", name))?;
+        BASE64URL
+            .decode(value)
+            .with_context(|| format!("invalid jwk `{name}` member"))
+    };
+    let crv = jwk.get("crv").and_then(Value::as_str);
+
+    let key = match jwk.get("kty").and_then(Value::as_str) {
+        Some("RSA") => (
+            Algorithm::Rs256,
+            KeyData::RsaComponents {
+                n: member("n")?,
+                e: member("e")?,
+            },
+        ),
+        Some("EC") if crv == Some("P-256") => {
+            let (x, y) = (member("x")?, member("y")?);
+            if x.len() != 32 || y.len() != 32 {
+                bail!("invalid P-256 jwk coordinates");
+            }
+            let mut point = vec![0x04];
+            point.extend_from_slice(&x);
+            point.extend_from_slice(&y);
+            (Algorithm::Es256, KeyData::Raw(point))
+        }
+        Some("OKP") if crv == Some("Ed25519") => (Algorithm::EdDsa, KeyData::Raw(member("x")?)),
+        _ => return Ok(None),
+    };
+    Ok(Some(key))
+}
+
+fn parse_pem(data: &str) -> Result<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut lines = data.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        let label = match line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|v| v.strip_suffix("-----"))
+        {
+            Some(label) => label,
+            None => continue,
+        };
+        let end = format!("-----END {label}-----");
+        let b64: String = lines.by_ref().take_while(|l| *l != end).collect();
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(b64)
+            .with_context(|| format!("invalid pem block `{label}`"))?;
+
+        match label {
+            "PUBLIC KEY" => keys.push(parse_spki(&der)?),
+            "RSA PUBLIC KEY" => keys.push(Key {
+                kid: None,
+                alg: Algorithm::Rs256,
+                data: KeyData::Raw(der),
+            }),
+            _ => tracing::warn!("jwt: ignoring pem block `{}`", label),
+        }
+    }
+    Ok(keys)
+}
+
+/// Parses a DER encoded `SubjectPublicKeyInfo` structure.
+fn parse_spki(der: &[u8]) -> Result<Key> {
+    let (_, spki) = SubjectPublicKeyInfo::from_der(der)
+        .map_err(|err| anyhow!("invalid public key structure: {}", err))?;
+    // The key is a bit string without unused bits
+    if spki.subject_public_key.unused_bits != 0 {
+        bail!("invalid public key structure");
+    }
+
+    let oid = &spki.algorithm.algorithm;
+    let alg = if *oid == OID_PKCS1_RSAENCRYPTION {
+        Algorithm::Rs256
+    } else if *oid == OID_KEY_TYPE_EC_PUBLIC_KEY {
+        let curve = spki
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|p| p.as_oid().ok());
+        if curve != Some(OID_EC_P256) {
+            bail!("unsupported elliptic curve public key");
+        }
+        Algorithm::Es256
+    } else if *oid == OID_SIG_ED25519 {
+        Algorithm::EdDsa
+    } else {
+        bail!("unsupported public key algorithm");
+    };
+    Ok(Key {
+        kid: None,
+        alg,
+        data: KeyData::Raw(spki.subject_public_key.data.to_vec()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::{rand::SystemRandom, signature::KeyPair};
+    use serde_json::json;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn pem_der(pem: &str) -> Vec<u8> {
+        let b64: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(b64)
+            .unwrap()
+    }
+
+    fn encode(alg: &str, kid: &str, claims: &Value) -> String {
+        let header = json!({"alg": alg, "typ": "JWT", "kid": kid});
+        format!(
+            "{}.{}",
+            BASE64URL.encode(header.to_string()),
+            BASE64URL.encode(claims.to_string())
+        )
+    }
+
+    fn sign_rs256(claims: &Value) -> String {
+        let key = signature::RsaKeyPair::from_pkcs8(&pem_der(include_str!(
+            "../tests/fixtures/jwt/rsa_key.pem"
+        )))
+        .unwrap();
+        let message = encode("RS256", "rsa-1", claims);
+        let mut sig = vec![0; key.public_modulus_len()];
+        key.sign(
+            &signature::RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            message.as_bytes(),
+            &mut sig,
+        )
+        .unwrap();
+        format!("{}.{}", message, BASE64URL.encode(sig))
+    }
+
+    fn sign_es256(claims: &Value) -> String {
+        let key = signature::EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            &pem_der(include_str!("../tests/fixtures/jwt/ec_key.pem")),
+        )
+        .unwrap();
+        let message = encode("ES256", "ec-1", claims);
+        let sig = key.sign(&SystemRandom::new(), message.as_bytes()).unwrap();
+        format!("{}.{}", message, BASE64URL.encode(sig))
+    }
+
+    fn sign_eddsa(claims: &Value) -> String {
+        let key = signature::Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pem_der(include_str!(
+            "../tests/fixtures/jwt/ed25519_key.pem"
+        )))
+        .unwrap();
+        assert_eq!(key.public_key().as_ref().len(), 32);
+        let message = encode("EdDSA", "ed-1", claims);
+        let sig = key.sign(message.as_bytes());
+        format!("{}.{}", message, BASE64URL.encode(sig))
+    }
+
+    fn verifier(file: &str) -> JwtVerifier {
+        JwtVerifier::new(
+            Path::new(file),
+            "https://issuer.local",
+            "static",
+            "token",
+            DEFAULT_LEEWAY,
+        )
+        .unwrap()
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "jq",
+            "iss": "https://issuer.local",
+            "aud": ["api", "static"],
+            "exp": NOW + 300,
+            "nbf": NOW - 10,
+            "role": "admin",
+            "groups": ["dev", "ops"],
+        })
+    }
+
+    #[test]
+    fn verify_algorithms() {
+        for file in [
+            "tests/fixtures/jwt/jwks.json",
+            "tests/fixtures/jwt/public_keys.pem",
+        ] {
+            let verifier = verifier(file);
+            assert_eq!(verifier.len(), 3);
+            for token in [
+                sign_rs256(&claims()),
+                sign_es256(&claims()),
+                sign_eddsa(&claims()),
+            ] {
+                let claims = verifier.verify_at(&token, NOW).unwrap();
+                assert_eq!(claims["sub"], "jq");
+            }
+        }
+
+        let verifier = verifier("tests/fixtures/jwt/rsa_pub_pkcs1.pem");
+        assert!(verifier.verify_at(&sign_rs256(&claims()), NOW).is_ok());
+        assert!(verifier.verify_at(&sign_es256(&claims()), NOW).is_err());
+    }
+
+    #[test]
+    fn verify_signature() {
+        let verifier = verifier("tests/fixtures/jwt/jwks.json");
+        let token = sign_es256(&claims());
+
+        // Tampered payload
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let mut tampered = claims();
+        tampered["role"] = json!("root");
+        let payload = BASE64URL.encode(tampered.to_string());
+        parts[1] = &payload;
+        let err = verifier.verify_at(&parts.join("."), NOW).unwrap_err();
+        assert_eq!(err.to_string(), "invalid token signature");
+
+        // Unsupported algorithm
+        let none = format!("{}.", encode("none", "ec-1", &claims()));
+        assert!(verifier.verify_at(&none, NOW).is_err());
+        let hs256 = format!("{}.c2ln", encode("HS256", "hmac-1", &claims()));
+        assert!(verifier.verify_at(&hs256, NOW).is_err());
+        assert!(verifier.verify_at("a.b", NOW).is_err());
+    }
+
+    #[test]
+    fn verify_registered_claims() {
+        let verifier = verifier("tests/fixtures/jwt/jwks.json");
+        let token = sign_eddsa(&claims());
+        let err = |now: u64| verifier.verify_at(&token, now).unwrap_err().to_string();
+
+        assert!(verifier
+            .verify_at(&token, NOW + 300 + DEFAULT_LEEWAY)
+            .is_ok());
+        assert_eq!(err(NOW + 301 + DEFAULT_LEEWAY), "token expired");
+        assert_eq!(err(NOW - 11 - DEFAULT_LEEWAY), "token not yet valid");
+
+        let mut other = claims();
+        other["iss"] = json!("https://other.local");
+        let err = verifier.verify_at(&sign_eddsa(&other), NOW).unwrap_err();
+        assert_eq!(err.to_string(), "invalid token issuer");
+
+        let mut other = claims();
+        other["aud"] = json!("api");
+        let err = verifier.verify_at(&sign_eddsa(&other), NOW).unwrap_err();
+        assert_eq!(err.to_string(), "invalid token audience");
+    }
+
+    #[test]
+    fn token_sources_and_claims() {
+        let verifier = verifier("tests/fixtures/jwt/jwks.json");
+        let mut headers = HeaderMap::new();
+        assert!(verifier.token(&headers).is_none());
+
+        headers.insert("cookie", "theme=dark; token=abc".parse().unwrap());
+        assert_eq!(verifier.token(&headers).unwrap(), "abc");
+        headers.insert("authorization", "Bearer xyz".parse().unwrap());
+        assert_eq!(verifier.token(&headers).unwrap(), "xyz");
+
+        let claims = claims();
+        let claims = claims.as_object().unwrap();
+        let req = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert!(has_claims(claims, &[]));
+        assert!(has_claims(
+            claims,
+            &req(&[("role", "admin"), ("groups", "ops")])
+        ));
+        assert!(!has_claims(claims, &req(&[("role", "dev")])));
+        assert!(!has_claims(claims, &req(&[("scope", "read")])));
+    }
+}
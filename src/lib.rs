@@ -63,6 +63,10 @@
 //! - Default and custom error pages.
 //! - Custom URL rewrites and redirects via glob patterns.
 //! - Support for serving pre-compressed (Gzip/Brotli) files.
+//! - Optional in-memory or on-disk cache for dynamically compressed files.
+//! - [Compression Dictionary Transport](https://datatracker.ietf.org/doc/rfc9842/) support via shared Brotli/Zstd dictionaries.
+//! - `precompress` subcommand to generate pre-compressed (Gzip/Brotli/Zstd) file variants.
+//! - HMAC-signed expiring URLs and `sign-url` subcommand to mint them.
 //! - First-class [Docker](https://docs.docker.com/get-started/overview/) support. [Scratch](https://hub.docker.com/_/scratch) and latest [Alpine Linux](https://hub.docker.com/_/alpine) Docker images.
 //! - Ability to accept a socket listener as a file descriptor for use in sandboxing and on-demand applications (E.g [systemd](http://0pointer.de/blog/projects/socket-activation.html)).
 //! - Cross-platform. Pre-compiled binaries for Linux, macOS, Windows and FreeBSD (`x86`,`x86_64`,`ARM`,`ARM64`).
@@ -88,6 +92,14 @@
 //! `directory-listing` | Activates the directory listing feature.
 //! [**Basic Authorization**](./features/basic-authentication.md) |
 //! `basic-auth` | Activates the Basic HTTP Authorization Schema feature.
+//! [**JWT Authentication**](./features/jwt-authentication.md) |
+//! `jwt-auth` | Activates the JSON Web Token `Bearer` authentication feature.
+//! [**Signed URLs**](./features/signed-urls.md) |
+//! `signed-urls` | Activates the HMAC-signed expiring URLs feature.
+//! [**Form Login**](./features/form-login.md) |
+//! `form-login` | Activates the form-based login with signed session cookies. It depends on `basic-auth`.
+//! [**Forward Authentication**](./features/forward-authentication.md) |
+//! `forward-auth` | Activates the delegation of the request authorization to an external HTTP service.
 //! [**Fallback Page**](./features/error-pages.md#fallback-page-for-use-with-client-routers) |
 //! `fallback-page` | Activates the Fallback Page feature.
 //!
@@ -106,17 +118,32 @@ extern crate anyhow;
 extern crate serde;
 
 // Public modules
+pub mod auth;
+pub mod auth_throttle;
+pub mod bandwidth;
 #[cfg(feature = "basic-auth")]
 #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
 pub mod basic_auth;
+pub mod client_map;
 #[cfg(feature = "compression")]
 #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
 pub mod compression;
 #[cfg(feature = "compression")]
 #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub mod compression_cache;
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub mod compression_dictionary;
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
 pub mod compression_static;
+pub mod conn_limit;
 pub mod control_headers;
 pub mod cors;
+#[cfg(feature = "csp-nonce")]
+#[cfg_attr(docsrs, doc(cfg(feature = "csp-nonce")))]
+pub mod csp_nonce;
+pub mod csp_reports;
 pub mod custom_headers;
 #[cfg(feature = "directory-listing")]
 #[cfg_attr(docsrs, doc(cfg(feature = "directory-listing")))]
@@ -126,12 +153,31 @@ pub mod exts;
 #[cfg(feature = "fallback-page")]
 #[cfg_attr(docsrs, doc(cfg(feature = "fallback-page")))]
 pub mod fallback_page;
+#[cfg(feature = "form-login")]
+#[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+pub mod form_login;
+#[cfg(feature = "forward-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "forward-auth")))]
+pub mod forward_auth;
 pub mod handler;
 #[cfg(feature = "http2")]
 #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
 pub mod https_redirect;
+pub mod ip_filter;
+#[cfg(feature = "jwt-auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+pub mod jwt_auth;
+pub mod load_shed;
 pub mod logger;
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub mod ocsp;
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub mod precompress;
+pub mod rate_limit;
 pub mod redirects;
+pub mod request_limits;
 pub mod rewrites;
 pub mod security_headers;
 pub mod server;
@@ -140,10 +186,17 @@ pub mod settings;
 #[cfg(any(unix, windows))]
 #[cfg_attr(docsrs, doc(cfg(any(unix, windows))))]
 pub mod signals;
+#[cfg(feature = "signed-urls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+pub mod signed_urls;
 pub mod static_files;
+pub mod timeouts;
 #[cfg(feature = "http2")]
 #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
 pub mod tls;
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub mod tls_reload;
 pub mod transport;
 #[cfg(windows)]
 #[cfg_attr(docsrs, doc(cfg(windows)))]
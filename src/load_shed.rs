@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to reject requests with `503 Service Unavailable` responses when the server is overloaded.
+//!
+//! The load is measured by the number of requests in flight and by the queueing delay
+//! of the blocking thread pool used for the file system operations.
+//! The pool queue can't be inspected directly so a probe task is regularly
+//! scheduled on it and the time it waits for a thread is used instead.
+//!
+
+use bytes::Bytes;
+use futures_util::Stream;
+use hyper::{body::HttpBody, Body};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Interval between two probes of the blocking thread pool.
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Value of the probe start when no probe is pending.
+const NO_PROBE: u64 = u64::MAX;
+
+/// It defines the load shedding thresholds and the current load of the server.
+#[derive(Debug)]
+pub struct LoadShedder {
+    max_inflight: usize,
+    max_blocking_delay: Duration,
+    inflight: AtomicUsize,
+    base: Instant,
+    probe_start: AtomicU64,
+    probe_delay: AtomicU64,
+}
+
+impl LoadShedder {
+    /// Creates a new load shedder. A zero value disables the corresponding threshold.
+    pub fn new(max_inflight: usize, max_blocking_delay: Duration) -> Self {
+        Self {
+            max_inflight,
+            max_blocking_delay,
+            inflight: AtomicUsize::new(0),
+            base: Instant::now(),
+            probe_start: AtomicU64::new(NO_PROBE),
+            probe_delay: AtomicU64::new(0),
+        }
+    }
+
+    /// Checks whether no threshold is set.
+    pub fn is_empty(&self) -> bool {
+        self.max_inflight == 0 && self.max_blocking_delay.is_zero()
+    }
+
+    /// Counts a new request in flight until the returned guard is dropped.
+    /// It also returns whether the request should be rejected.
+    pub fn enter(self: &Arc<Self>) -> (InflightGuard, bool) {
+        let inflight = self.inflight.fetch_add(1, Ordering::Relaxed) + 1;
+        let guard = InflightGuard {
+            shedder: self.clone(),
+        };
+        let overloaded = (self.max_inflight > 0 && inflight > self.max_inflight)
+            || (!self.max_blocking_delay.is_zero()
+                && self.blocking_delay(Instant::now()) > self.max_blocking_delay);
+        (guard, overloaded)
+    }
+
+    /// Returns the queueing delay of the blocking thread pool.
+    /// A pending probe counts as well so a saturated pool is noticed before the probe runs.
+    pub fn blocking_delay(&self, now: Instant) -> Duration {
+        let last = Duration::from_micros(self.probe_delay.load(Ordering::Relaxed));
+        match self.probe_start.load(Ordering::Relaxed) {
+            NO_PROBE => last,
+            start => last.max(
+                self.elapsed(now)
+                    .saturating_sub(Duration::from_micros(start)),
+            ),
+        }
+    }
+
+    /// Probes the blocking thread pool regularly.
+    /// It should be spawned on the runtime of the server.
+    pub async fn probe_blocking_pool(self: Arc<Self>) {
+        loop {
+            let start = Instant::now();
+            self.probe_start
+                .store(self.elapsed(start).as_micros() as u64, Ordering::Relaxed);
+            if tokio::task::spawn_blocking(|| ()).await.is_err() {
+                return;
+            }
+            let delay = start.elapsed();
+            self.probe_delay
+                .store(delay.as_micros() as u64, Ordering::Relaxed);
+            self.probe_start.store(NO_PROBE, Ordering::Relaxed);
+            if delay > self.max_blocking_delay {
+                tracing::warn!(
+                    "blocking thread pool is saturated: queueing delay={}ms",
+                    delay.as_millis()
+                );
+            }
+            tokio::time::sleep(PROBE_INTERVAL).await;
+        }
+    }
+
+    /// Returns the time elapsed since the creation of the load shedder.
+    fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.base)
+    }
+}
+
+/// It defines a request in flight.
+#[derive(Debug)]
+pub struct InflightGuard {
+    shedder: Arc<LoadShedder>,
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.shedder.inflight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// It defines a response body keeping its request in flight until it's sent or dropped.
+pub struct InflightBody<G = InflightGuard> {
+    body: Body,
+    guard: Option<G>,
+}
+
+impl<G: Send + Unpin + 'static> InflightBody<G> {
+    /// Wraps a response body so its request guard is held until the body completes.
+    ///
+    /// Bodies already complete are returned as they are, releasing the guard.
+    pub fn wrap(body: Body, guard: G) -> Body {
+        if body.is_end_stream() {
+            return body;
+        }
+        Body::wrap_stream(Self {
+            body,
+            guard: Some(guard),
+        })
+    }
+}
+
+impl<G: Unpin> Stream for InflightBody<G> {
+    type Item = hyper::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        let poll = Pin::new(&mut this.body).poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            this.guard.take();
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inflight_requests() {
+        let shedder = Arc::new(LoadShedder::new(2, Duration::ZERO));
+        let (first, overloaded) = shedder.enter();
+        assert!(!overloaded);
+        let (_second, overloaded) = shedder.enter();
+        assert!(!overloaded);
+        let (third, overloaded) = shedder.enter();
+        assert!(overloaded);
+        drop(third);
+        drop(first);
+        assert!(!shedder.enter().1);
+    }
+
+    #[tokio::test]
+    async fn inflight_bodies() {
+        use futures_util::StreamExt;
+
+        let shedder = Arc::new(LoadShedder::new(1, Duration::ZERO));
+        let (guard, _) = shedder.enter();
+        let mut body = InflightBody::wrap(Body::from("data"), guard);
+        // The request is in flight while its body is being sent
+        assert!(shedder.enter().1);
+        assert_eq!(body.next().await.unwrap().unwrap(), "data");
+        assert!(body.next().await.is_none());
+        assert_eq!(shedder.inflight.load(Ordering::Relaxed), 0);
+
+        let (guard, _) = shedder.enter();
+        let body = InflightBody::wrap(Body::empty(), guard);
+        assert!(body.is_end_stream());
+        assert_eq!(shedder.inflight.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pending_probe() {
+        let shedder = LoadShedder::new(0, Duration::from_millis(50));
+        let now = Instant::now();
+        assert_eq!(shedder.blocking_delay(now), Duration::ZERO);
+
+        shedder.probe_delay.store(10_000, Ordering::Relaxed);
+        assert_eq!(shedder.blocking_delay(now), Duration::from_millis(10));
+
+        // A probe waiting for a thread since 200ms
+        shedder
+            .probe_start
+            .store(shedder.elapsed(now).as_micros() as u64, Ordering::Relaxed);
+        let later = now + Duration::from_millis(200);
+        assert_eq!(shedder.blocking_delay(later), Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to check the OCSP responses stapled to the TLS handshakes.
+//!
+//! A response is only stapled if it's successful, reports a `good` status and
+//! matches the serial number and the issuer of the certificate. Its signature
+//! is left to the clients, which verify it anyway.
+//!
+
+use der::{
+    oid::{
+        db::rfc5912::{ID_SHA_1, ID_SHA_256},
+        AssociatedOid,
+    },
+    Decode,
+};
+use ring::digest;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_rustls::rustls::Certificate;
+use x509_ocsp::{
+    BasicOcspResponse, CertStatus, OcspGeneralizedTime, OcspResponse, OcspResponseStatus,
+};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::tls::cert_public_key;
+
+/// It defines the validity period of an OCSP response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OcspValidity {
+    /// Time the status was known to be correct.
+    pub this_update: SystemTime,
+    /// Time newer information will be available, if any.
+    pub next_update: Option<SystemTime>,
+}
+
+impl OcspValidity {
+    /// Checks whether the response is expired.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.next_update.is_some_and(|next| next <= now)
+    }
+
+    /// Returns the remaining time of the response if less than a quarter
+    /// of its validity period is left.
+    pub fn expires_soon(&self, now: SystemTime) -> Option<Duration> {
+        let next = self.next_update?;
+        let period = next.duration_since(self.this_update).ok()?;
+        let remaining = next.duration_since(now).unwrap_or_default();
+        (remaining < period / 4).then_some(remaining)
+    }
+}
+
+/// Checks a DER encoded OCSP response against a certificate chain.
+///
+/// The issuer key hash is only checked when the chain includes the issuer certificate.
+pub fn check_response(der: &[u8], chain: &[Certificate]) -> Result<OcspValidity, String> {
+    let cert = chain.first().ok_or("empty certificate chain")?;
+    let (_, cert) = X509Certificate::from_der(&cert.0).map_err(|_| "invalid certificate")?;
+    let issuer_key = chain.get(1).and_then(|issuer| cert_public_key(&issuer.0));
+
+    for single in basic_response(der)?.tbs_response_data.responses {
+        let cert_id = &single.cert_id;
+        if cert_id.serial_number.as_bytes() != cert.raw_serial() {
+            continue;
+        }
+        let hash = match &cert_id.hash_algorithm.oid {
+            oid if *oid == ID_SHA_1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            oid if *oid == ID_SHA_256 => &digest::SHA256,
+            _ => return Err("unsupported ocsp certificate id hash algorithm".into()),
+        };
+        if digest::digest(hash, cert.issuer().as_raw()).as_ref()
+            != cert_id.issuer_name_hash.as_bytes()
+            || issuer_key.as_deref().is_some_and(|key| {
+                digest::digest(hash, key).as_ref() != cert_id.issuer_key_hash.as_bytes()
+            })
+        {
+            continue;
+        }
+        return match single.cert_status {
+            CertStatus::Good(_) => Ok(OcspValidity {
+                this_update: system_time(single.this_update),
+                next_update: single.next_update.map(system_time),
+            }),
+            CertStatus::Revoked(_) => Err("the certificate is revoked".into()),
+            CertStatus::Unknown(_) => Err("the certificate status is unknown".into()),
+        };
+    }
+    Err("the ocsp response doesn't match the certificate".into())
+}
+
+/// Returns the basic response of a successful OCSP response.
+fn basic_response(der: &[u8]) -> Result<BasicOcspResponse, String> {
+    let malformed = |err: der::Error| format!("malformed ocsp response, {err}");
+    let resp = OcspResponse::from_der(der).map_err(malformed)?;
+    if resp.response_status != OcspResponseStatus::Successful {
+        return Err(format!(
+            "unsuccessful ocsp response status: {:?}",
+            resp.response_status
+        ));
+    }
+    let bytes = resp
+        .response_bytes
+        .ok_or("malformed ocsp response, missing response bytes")?;
+    if bytes.response_type != BasicOcspResponse::OID {
+        return Err("unsupported ocsp response type".into());
+    }
+    BasicOcspResponse::from_der(bytes.response.as_bytes()).map_err(malformed)
+}
+
+/// Converts an OCSP generalized time into a system time.
+fn system_time(time: OcspGeneralizedTime) -> SystemTime {
+    UNIX_EPOCH + time.0.to_unix_duration()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(pem: &[u8]) -> Vec<Certificate> {
+        rustls_pemfile::certs(&mut &pem[..])
+            .unwrap()
+            .into_iter()
+            .map(Certificate)
+            .collect()
+    }
+
+    #[test]
+    fn ocsp_responses() {
+        let ocsp_chain = chain(include_bytes!("../tests/tls/local.ocsp_cert.pem"));
+        let good = include_bytes!("../tests/tls/local.ocsp_good.der");
+        let validity = check_response(good, &ocsp_chain).unwrap();
+        assert!(validity.next_update.unwrap() > validity.this_update);
+
+        let revoked = include_bytes!("../tests/tls/local.ocsp_revoked.der");
+        assert_eq!(
+            check_response(revoked, &ocsp_chain).unwrap_err(),
+            "the certificate is revoked"
+        );
+
+        let other_chain = chain(include_bytes!("../tests/tls/local.dev_cert.pem"));
+        assert!(check_response(good, &other_chain).is_err());
+        assert!(check_response(b"not a response", &ocsp_chain).is_err());
+    }
+
+    #[test]
+    fn ocsp_expiry() {
+        let day = Duration::from_secs(86_400);
+        let this_update = UNIX_EPOCH + Duration::from_secs(1_767_225_600);
+        let validity = OcspValidity {
+            this_update,
+            next_update: Some(this_update + 4 * day),
+        };
+        assert_eq!(validity.expires_soon(this_update + day), None);
+        assert_eq!(
+            validity.expires_soon(this_update + 3 * day + day / 2),
+            Some(day / 2)
+        );
+        assert!(!validity.is_expired(this_update + 3 * day));
+        assert!(validity.is_expired(this_update + 4 * day));
+    }
+}
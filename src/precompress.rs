@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to generate the pre-compressed file variants (`.gz`, `.br` and `.zst`)
+//! of a directory which are served by the compression static feature.
+//!
+
+use headers::ContentCoding;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+use crate::{compression, Context, Result};
+
+/// Default content codings used to generate pre-compressed file variants.
+pub const DEFAULT_ENCODINGS: &str = "gzip,br,zstd";
+
+/// It defines the results of a pre-compression run.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PrecompressStats {
+    /// Number of file variants (re)generated.
+    pub compressed: usize,
+    /// Number of file variants that were already up-to-date.
+    pub up_to_date: usize,
+    /// Number of file variants skipped because compression did not reduce their size.
+    pub skipped: usize,
+}
+
+/// Generate the pre-compressed file variants of all compressible files of the given directory
+/// and print a summary of the results.
+pub fn run(dir: &Path, encodings: &str) -> Result {
+    let encodings = parse_encodings(encodings)?;
+
+    let stats = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(precompress_dir(dir, &encodings))?;
+
+    println!(
+        "Pre-compression of {} completed: {} compressed, {} up-to-date, {} skipped (no size gain)",
+        dir.display(),
+        stats.compressed,
+        stats.up_to_date,
+        stats.skipped
+    );
+
+    Ok(())
+}
+
+/// Parse a list of content codings separated by commas. E.g `gzip, br, zstd`.
+pub fn parse_encodings(encodings: &str) -> Result<Vec<ContentCoding>> {
+    let mut codings = Vec::new();
+    for enc in encodings
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+    {
+        let coding = match enc.to_lowercase().as_str() {
+            #[cfg(feature = "compression-gzip")]
+            "gzip" | "gz" => ContentCoding::GZIP,
+            #[cfg(feature = "compression-brotli")]
+            "br" | "brotli" => ContentCoding::BROTLI,
+            #[cfg(feature = "compression-zstd")]
+            "zstd" | "zst" => ContentCoding::ZSTD,
+            _ => bail!("unsupported pre-compression encoding: {}", enc),
+        };
+        if !codings.contains(&coding) {
+            codings.push(coding);
+        }
+    }
+    if codings.is_empty() {
+        bail!("at least one pre-compression encoding should be provided");
+    }
+    Ok(codings)
+}
+
+/// Walk the given directory recursively and (re)generate the outdated pre-compressed variants
+/// of every file whose MIME type is part of the compressible set.
+pub async fn precompress_dir(dir: &Path, encodings: &[ContentCoding]) -> Result<PrecompressStats> {
+    match tokio::fs::metadata(dir).await {
+        Ok(meta) if meta.is_dir() => {}
+        Ok(_) => bail!("path {} is not a valid directory", dir.display()),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!(
+                    "pre-compression directory {} was not found or inaccessible",
+                    dir.display()
+                )
+            })
+        }
+    }
+
+    let mut stats = PrecompressStats::default();
+    let mut dirs = vec![dir.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("failed to read directory {}", dir.display()))?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            // NOTE: symbolic links are not followed
+            let file_type = entry.file_type().await?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() {
+                precompress_file(&path, &entry.metadata().await?, encodings, &mut stats).await?;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Generate the pre-compressed variants of a single file if it is compressible.
+async fn precompress_file(
+    path: &Path,
+    meta: &Metadata,
+    encodings: &[ContentCoding],
+    stats: &mut PrecompressStats,
+) -> Result {
+    let mime = match mime_guess::from_path(path).first() {
+        Some(mime) if compression::is_compressible_mime(&mime) => mime,
+        _ => return Ok(()),
+    };
+    let modified = meta.modified()?;
+
+    let mut content: Option<Vec<u8>> = None;
+    for coding in encodings {
+        let variant = match variant_path(path, *coding) {
+            Some(variant) => variant,
+            None => continue,
+        };
+
+        // Skip variants which are newer than their source file
+        let variant_meta = tokio::fs::metadata(&variant).await.ok();
+        if let Some(variant_meta) = &variant_meta {
+            if variant_meta.modified()? >= modified {
+                stats.up_to_date += 1;
+                continue;
+            }
+        }
+
+        if content.is_none() {
+            content = Some(
+                tokio::fs::read(path)
+                    .await
+                    .with_context(|| format!("failed to read file {}", path.display()))?,
+            );
+        }
+        let content = content.as_deref().unwrap_or_default();
+
+        let compressed = match compression::compress_reader(*coding, content).await? {
+            Some(compressed) => compressed,
+            None => continue,
+        };
+
+        if compressed.len() >= content.len() {
+            tracing::debug!(
+                "pre-compression: skipping {} ({}) since compression does not help",
+                path.display(),
+                mime
+            );
+            // An outdated variant should not be served anymore
+            if variant_meta.filter(|meta| meta.is_file()).is_some() {
+                tokio::fs::remove_file(&variant).await?;
+            }
+            stats.skipped += 1;
+            continue;
+        }
+
+        let tmp_variant = tmp_path(&variant);
+        tokio::fs::write(&tmp_variant, &compressed)
+            .await
+            .with_context(|| format!("failed to write file {}", tmp_variant.display()))?;
+        if let Err(err) = tokio::fs::rename(&tmp_variant, &variant).await {
+            let _ = tokio::fs::remove_file(&tmp_variant).await;
+            return Err(err).with_context(|| format!("failed to write file {}", variant.display()));
+        }
+
+        println!(
+            "{} ({} -> {} bytes)",
+            variant.display(),
+            content.len(),
+            compressed.len()
+        );
+        stats.compressed += 1;
+    }
+
+    Ok(())
+}
+
+/// Returns the pre-compressed variant file path of a file for the given content coding.
+fn variant_path(path: &Path, coding: ContentCoding) -> Option<PathBuf> {
+    let ext = match coding {
+        ContentCoding::GZIP => "gz",
+        ContentCoding::BROTLI => "br",
+        ContentCoding::ZSTD => "zst",
+        _ => return None,
+    };
+    let mut name = path.file_name()?.to_os_string();
+    name.push(".");
+    name.push(ext);
+    Some(path.with_file_name(name))
+}
+
+/// Returns a temporary file path next to a variant file which can not clash with the site files.
+/// E.g. `main.js.gz` -> `main.js.gz.1234.tmp`.
+fn tmp_path(variant: &Path) -> PathBuf {
+    let mut path = variant.as_os_str().to_owned();
+    path.push(format!(".{}.tmp", std::process::id()));
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_list() {
+        assert_eq!(
+            parse_encodings("gzip, br,zstd,gz").unwrap(),
+            vec![
+                ContentCoding::GZIP,
+                ContentCoding::BROTLI,
+                ContentCoding::ZSTD
+            ]
+        );
+        assert!(parse_encodings("deflate").is_err());
+        assert!(parse_encodings("").is_err());
+    }
+
+    #[test]
+    fn variant_paths() {
+        let path = Path::new("public/main.js");
+        assert_eq!(
+            variant_path(path, ContentCoding::GZIP),
+            Some(PathBuf::from("public/main.js.gz"))
+        );
+        assert_eq!(
+            variant_path(path, ContentCoding::ZSTD),
+            Some(PathBuf::from("public/main.js.zst"))
+        );
+        assert_eq!(variant_path(path, ContentCoding::DEFLATE), None);
+    }
+
+    #[test]
+    fn tmp_paths() {
+        let path = Path::new("public/main.js.gz");
+        assert_eq!(
+            tmp_path(path),
+            PathBuf::from(format!("public/main.js.gz.{}.tmp", std::process::id()))
+        );
+    }
+}
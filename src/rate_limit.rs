@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to limit the request rate of the clients using token buckets.
+//!
+//! Every client gets a bucket of `burst` tokens refilled at `rate` tokens per second.
+//! Each request takes a token and the requests finding an empty bucket are rejected.
+//!
+
+use headers::{HeaderMap, HeaderName, HeaderValue};
+use hyper::header::RETRY_AFTER;
+use std::net::IpAddr;
+use std::time::Instant;
+
+use crate::{client_map::ClientMap, settings::RateLimitRule, Result};
+
+/// Maximum number of tracked clients per limiter.
+const MAX_CLIENTS: usize = 10_000;
+
+/// Maximum length of a client key taken from a request header.
+const MAX_KEY_LEN: usize = 256;
+
+/// Token bucket of a client.
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// It defines the outcome of a rate limit check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitState {
+    /// Whether the request is allowed.
+    pub allowed: bool,
+    /// Capacity of the bucket.
+    pub limit: u32,
+    /// Remaining requests in the bucket.
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request is allowed.
+    pub retry_after: u64,
+}
+
+impl RateLimitState {
+    /// Appends the `RateLimit-*` headers and the `Retry-After` header of rejected requests.
+    pub fn append_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("ratelimit-reset", HeaderValue::from(self.reset));
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(self.retry_after));
+        }
+    }
+}
+
+/// It defines the token buckets of the clients.
+pub struct RateLimiter {
+    rate: f64,
+    burst: u32,
+    buckets: ClientMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    /// Creates a new limiter allowing `rate` requests per second with bursts of `burst` requests.
+    /// If `burst` is zero then the rate rounded up is used.
+    pub fn new(rate: f64, burst: u32) -> Result<Self> {
+        if !rate.is_finite() || rate <= 0.0 {
+            bail!("rate limit should be a number greater than zero: {}", rate);
+        }
+        let burst = if burst == 0 {
+            (rate.ceil() as u32).max(1)
+        } else {
+            burst
+        };
+        Ok(Self {
+            rate,
+            burst,
+            buckets: ClientMap::new(MAX_CLIENTS),
+        })
+    }
+
+    /// Takes a token from the bucket of a client.
+    pub fn check(&self, key: &str, now: Instant) -> RateLimitState {
+        let burst = self.burst as f64;
+        // The least recently seen clients are forgotten first, which is the same as a full bucket
+        // for clients that were idle long enough
+        let (allowed, tokens) = self.buckets.with(
+            key.to_owned(),
+            || Bucket {
+                tokens: burst,
+                last: now,
+            },
+            |bucket| {
+                bucket.tokens = self.refill(bucket, now);
+                bucket.last = now;
+                let allowed = bucket.tokens >= 1.0;
+                if allowed {
+                    bucket.tokens -= 1.0;
+                }
+                (allowed, bucket.tokens)
+            },
+        );
+
+        RateLimitState {
+            allowed,
+            limit: self.burst,
+            remaining: tokens.floor() as u32,
+            reset: self.seconds_until(burst - tokens),
+            retry_after: self.seconds_until(1.0 - tokens).max(1),
+        }
+    }
+
+    /// Returns the tokens of a bucket at a given time.
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst as f64)
+    }
+
+    /// Returns the whole seconds needed to refill the given tokens.
+    fn seconds_until(&self, tokens: f64) -> u64 {
+        if tokens <= 0.0 {
+            return 0;
+        }
+        (tokens / self.rate).ceil() as u64
+    }
+}
+
+/// It returns the first rate limit rule matching the request uri path if any.
+pub fn get_rule<'a>(
+    uri_path: &str,
+    rules: &'a Option<Vec<RateLimitRule>>,
+) -> Option<&'a RateLimitRule> {
+    rules
+        .as_ref()?
+        .iter()
+        .find(|rule| rule.source.is_match(uri_path))
+}
+
+/// Returns the key identifying the client of a request.
+///
+/// The value of the `key_header` is used if present, otherwise the client IP address.
+pub fn client_key(
+    headers: &HeaderMap,
+    key_header: Option<&HeaderName>,
+    client_ip: Option<IpAddr>,
+) -> Option<String> {
+    let value = key_header
+        .and_then(|name| headers.get(name))
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && v.len() <= MAX_KEY_LEN);
+    match value {
+        Some(value) => Some(format!("h:{value}")),
+        None => client_ip.map(|ip| ip.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn token_buckets() {
+        let limiter = RateLimiter::new(2.0, 3).unwrap();
+        let now = Instant::now();
+        for remaining in [2, 1, 0] {
+            let state = limiter.check("a", now);
+            assert!(state.allowed);
+            assert_eq!(state.limit, 3);
+            assert_eq!(state.remaining, remaining);
+        }
+
+        let state = limiter.check("a", now);
+        assert!(!state.allowed);
+        assert_eq!(state.retry_after, 1);
+        assert_eq!(state.reset, 2);
+        assert!(limiter.check("b", now).allowed);
+
+        // Two tokens are refilled after a second
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check("a", later).allowed);
+        assert!(limiter.check("a", later).allowed);
+        assert!(!limiter.check("a", later).allowed);
+
+        assert!(RateLimiter::new(0.0, 1).is_err());
+        assert_eq!(RateLimiter::new(0.5, 0).unwrap().burst, 1);
+    }
+
+    #[test]
+    fn bounded_clients() {
+        let limiter = RateLimiter::new(0.001, 5).unwrap();
+        let now = Instant::now();
+        for i in 0..MAX_CLIENTS * 2 {
+            limiter.check(&i.to_string(), now + Duration::from_millis(i as u64));
+        }
+        assert!(limiter.buckets.len() <= MAX_CLIENTS);
+    }
+
+    #[test]
+    fn client_keys() {
+        let ip = Some("10.0.0.1".parse().unwrap());
+        let name = HeaderName::from_static("x-api-key");
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_key(&headers, Some(&name), ip).unwrap(), "10.0.0.1");
+        headers.insert("x-api-key", HeaderValue::from_static("abc"));
+        assert_eq!(client_key(&headers, Some(&name), ip).unwrap(), "h:abc");
+        assert_eq!(client_key(&headers, None, ip).unwrap(), "10.0.0.1");
+        assert_eq!(client_key(&headers, None, None), None);
+    }
+}
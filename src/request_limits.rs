@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to reject the requests with oversized URIs or headers.
+//!
+
+use headers::HeaderMap;
+use hyper::{StatusCode, Uri};
+
+/// Overhead of a header field in the HTTP/2 header list size.
+const HTTP2_FIELD_OVERHEAD: usize = 32;
+
+/// It defines the size limits of a request. A zero value disables the corresponding limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLimits {
+    /// Maximum length in bytes of the request URI.
+    pub max_uri_length: usize,
+    /// Maximum size in bytes of the request headers, names and values together.
+    pub max_header_bytes: usize,
+    /// Maximum number of request headers.
+    pub max_headers: usize,
+}
+
+impl RequestLimits {
+    /// Returns the status code rejecting a request exceeding the limits if any.
+    pub fn check(&self, uri: &Uri, headers: &HeaderMap) -> Option<StatusCode> {
+        if self.max_uri_length > 0 && uri_length(uri) > self.max_uri_length {
+            return Some(StatusCode::URI_TOO_LONG);
+        }
+        if self.max_headers > 0 && headers.len() > self.max_headers {
+            return Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        }
+        if self.max_header_bytes > 0 {
+            let size: usize = headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum();
+            if size > self.max_header_bytes {
+                return Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+            }
+        }
+        None
+    }
+
+    /// Returns the HTTP/2 `SETTINGS_MAX_HEADER_LIST_SIZE` equivalent to the header limits.
+    ///
+    /// HTTP/2 counts 32 extra bytes per header field so the overhead of the allowed
+    /// headers is added, letting the requests exceeding the limits get an error page.
+    pub fn http2_max_header_list_size(&self) -> Option<u32> {
+        if self.max_header_bytes == 0 {
+            return None;
+        }
+        let size = self.max_header_bytes + HTTP2_FIELD_OVERHEAD * self.max_headers;
+        Some(u32::try_from(size).unwrap_or(u32::MAX))
+    }
+}
+
+/// Returns the length of the path and query of a request URI.
+fn uri_length(uri: &Uri) -> usize {
+    uri.path_and_query().map_or(0, |v| v.as_str().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use headers::HeaderValue;
+
+    #[test]
+    fn request_limits() {
+        let limits = RequestLimits {
+            max_uri_length: 16,
+            max_header_bytes: 32,
+            max_headers: 2,
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("localhost"));
+        assert_eq!(
+            limits.check(&"/index.html".parse().unwrap(), &headers),
+            None
+        );
+        assert_eq!(
+            limits.check(&"/index.html?v=1234567".parse().unwrap(), &headers),
+            Some(StatusCode::URI_TOO_LONG)
+        );
+
+        headers.insert("accept", HeaderValue::from_static("*/*"));
+        assert_eq!(limits.check(&"/".parse().unwrap(), &headers), None);
+        headers.insert("user-agent", HeaderValue::from_static("curl"));
+        assert_eq!(
+            limits.check(&"/".parse().unwrap(), &headers),
+            Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+
+        headers.remove("accept");
+        headers.insert("user-agent", HeaderValue::from_static("a-long-user-agent"));
+        assert_eq!(
+            limits.check(&"/".parse().unwrap(), &headers),
+            Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+
+        assert_eq!(limits.http2_max_header_list_size(), Some(96));
+        assert_eq!(
+            RequestLimits::default().check(&"/".parse().unwrap(), &headers),
+            None
+        );
+        assert_eq!(RequestLimits::default().http2_max_header_list_size(), None);
+    }
+}
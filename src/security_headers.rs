@@ -6,11 +6,225 @@
 //! The module provides several HTTP security headers support.
 //!
 
+use clap::ValueEnum;
+use globset::GlobMatcher;
 use http::header::{
-    CONTENT_SECURITY_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
 };
 use hyper::{Body, Response};
 
+use crate::settings::{self, file::SecurityHeadersValues};
+use crate::{Context, Result};
+
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+const CROSS_ORIGIN_OPENER_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-opener-policy");
+const CROSS_ORIGIN_EMBEDDER_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-embedder-policy");
+const CROSS_ORIGIN_RESOURCE_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-resource-policy");
+
+/// HSTS `max-age` of the presets (2 years).
+const HSTS_MAX_AGE_TWO_YEARS: u64 = 63072000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+/// Named sets of security headers.
+pub enum Preset {
+    /// HSTS (2 years max-age including subdomains and preload), `X-Frame-Options: DENY`,
+    /// `X-Content-Type-Options: nosniff` and `Content-Security-Policy: frame-ancestors 'self'` (default).
+    Default,
+    /// Same-origin only resources, no referrer and most browser features disabled.
+    Strict,
+    /// The default headers plus the COOP/COEP/CORP headers needed for cross-origin isolation.
+    /// E.g `SharedArrayBuffer` usage in WASM applications.
+    CrossOriginIsolated,
+    /// HSTS (1 year max-age), `X-Frame-Options: SAMEORIGIN`, `X-Content-Type-Options: nosniff`
+    /// and `Referrer-Policy: strict-origin-when-cross-origin`.
+    Relaxed,
+}
+
+impl Preset {
+    /// Returns the header values of the preset.
+    pub fn values(&self) -> SecurityHeadersValues {
+        let none = || Some(String::new());
+        let value = |v: &str| Some(v.to_owned());
+
+        let default = SecurityHeadersValues {
+            content_security_policy: value("frame-ancestors 'self'"),
+            permissions_policy: none(),
+            referrer_policy: none(),
+            cross_origin_opener_policy: none(),
+            cross_origin_embedder_policy: none(),
+            cross_origin_resource_policy: none(),
+            x_frame_options: value("DENY"),
+            x_content_type_options: value("nosniff"),
+            hsts_max_age: Some(HSTS_MAX_AGE_TWO_YEARS),
+            hsts_include_subdomains: Some(true),
+            hsts_preload: Some(true),
+        };
+
+        match self {
+            Preset::Default => default,
+            Preset::Strict => SecurityHeadersValues {
+                content_security_policy: value(
+                    "default-src 'self'; base-uri 'self'; form-action 'self'; frame-ancestors 'none'; object-src 'none'",
+                ),
+                permissions_policy: value(
+                    "accelerometer=(), camera=(), geolocation=(), gyroscope=(), magnetometer=(), microphone=(), payment=(), usb=()",
+                ),
+                referrer_policy: value("no-referrer"),
+                cross_origin_opener_policy: value("same-origin"),
+                cross_origin_resource_policy: value("same-origin"),
+                ..default
+            },
+            Preset::CrossOriginIsolated => SecurityHeadersValues {
+                cross_origin_opener_policy: value("same-origin"),
+                cross_origin_embedder_policy: value("require-corp"),
+                cross_origin_resource_policy: value("same-origin"),
+                ..default
+            },
+            Preset::Relaxed => SecurityHeadersValues {
+                content_security_policy: none(),
+                referrer_policy: value("strict-origin-when-cross-origin"),
+                x_frame_options: value("SAMEORIGIN"),
+                hsts_max_age: Some(31536000),
+                hsts_include_subdomains: Some(false),
+                hsts_preload: Some(false),
+                ..default
+            },
+        }
+    }
+}
+
+/// It defines the configured security headers of the server and their per-path overrides.
+#[derive(Debug)]
+pub struct Configured {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    overrides: Vec<(GlobMatcher, Vec<(HeaderName, HeaderValue)>)>,
+}
+
+impl Configured {
+    /// It appends the security headers of the first override matching the URI path if any,
+    /// otherwise the global ones.
+    pub fn append_headers(&self, uri: &str, resp: &mut Response<Body>) {
+        let headers = self
+            .overrides
+            .iter()
+            .find(|(source, _)| source.is_match(uri))
+            .map_or(&self.headers, |(_, headers)| headers);
+
+        for (name, value) in headers {
+            resp.headers_mut().insert(name, value.clone());
+        }
+    }
+}
+
+/// Creates the security headers of a preset along with the values and per-path overrides of the config file.
+/// Note that the `Strict-Transport-Security` header is only used if `tls` is enabled.
+pub fn new(
+    preset: Preset,
+    advanced: Option<&settings::SecurityHeaders>,
+    tls: bool,
+) -> Result<Configured> {
+    let mut values = preset.values();
+    let mut overrides = Vec::new();
+
+    if let Some(advanced) = advanced {
+        merge_values(&mut values, &advanced.values);
+
+        for entry in &advanced.overrides {
+            let mut override_values = match entry.preset {
+                Some(preset) => preset.values(),
+                None => values.clone(),
+            };
+            merge_values(&mut override_values, &entry.values);
+            overrides.push((entry.source.clone(), header_values(&override_values, tls)?));
+        }
+    }
+
+    Ok(Configured {
+        headers: header_values(&values, tls)?,
+        overrides,
+    })
+}
+
+/// Replaces the values of `base` with the ones provided by `values`.
+fn merge_values(base: &mut SecurityHeadersValues, values: &SecurityHeadersValues) {
+    macro_rules! merge {
+        ($($field:ident),*) => {
+            $(if values.$field.is_some() {
+                base.$field = values.$field.clone();
+            })*
+        };
+    }
+    merge!(
+        content_security_policy,
+        permissions_policy,
+        referrer_policy,
+        cross_origin_opener_policy,
+        cross_origin_embedder_policy,
+        cross_origin_resource_policy,
+        x_frame_options,
+        x_content_type_options,
+        hsts_max_age,
+        hsts_include_subdomains,
+        hsts_preload
+    );
+}
+
+/// Converts the security headers values into a list of headers skipping the empty ones.
+fn header_values(
+    values: &SecurityHeadersValues,
+    tls: bool,
+) -> Result<Vec<(HeaderName, HeaderValue)>> {
+    let mut headers = Vec::new();
+
+    // Strict-Transport-Security (HSTS)
+    let hsts_max_age = values.hsts_max_age.unwrap_or_default();
+    if tls && hsts_max_age > 0 {
+        let mut hsts = format!("max-age={hsts_max_age}");
+        if values.hsts_include_subdomains.unwrap_or_default() {
+            hsts.push_str("; includeSubDomains");
+        }
+        if values.hsts_preload.unwrap_or_default() {
+            hsts.push_str("; preload");
+        }
+        headers.push((STRICT_TRANSPORT_SECURITY, HeaderValue::from_str(&hsts)?));
+    }
+
+    for (name, value) in [
+        (X_FRAME_OPTIONS, &values.x_frame_options),
+        (X_CONTENT_TYPE_OPTIONS, &values.x_content_type_options),
+        (CONTENT_SECURITY_POLICY, &values.content_security_policy),
+        (PERMISSIONS_POLICY, &values.permissions_policy),
+        (REFERRER_POLICY, &values.referrer_policy),
+        (
+            CROSS_ORIGIN_OPENER_POLICY,
+            &values.cross_origin_opener_policy,
+        ),
+        (
+            CROSS_ORIGIN_EMBEDDER_POLICY,
+            &values.cross_origin_embedder_policy,
+        ),
+        (
+            CROSS_ORIGIN_RESOURCE_POLICY,
+            &values.cross_origin_resource_policy,
+        ),
+    ] {
+        let value = value.as_deref().unwrap_or_default().trim();
+        if value.is_empty() {
+            continue;
+        }
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("invalid value for the {name} security header: {value}"))?;
+        headers.push((name, value));
+    }
+
+    Ok(headers)
+}
+
 /// It appends security headers like `Strict-Transport-Security: max-age=63072000; includeSubDomains; preload` (2 years max-age),
 ///`X-Frame-Options: DENY` and `Content-Security-Policy: frame-ancestors 'self'`.
 pub fn append_headers(resp: &mut Response<Body>) {
@@ -36,3 +250,89 @@ pub fn append_headers(resp: &mut Response<Body>) {
         "frame-ancestors 'self'".parse().unwrap(),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use globset::Glob;
+
+    fn response_headers(configured: &Configured, uri: &str) -> http::HeaderMap {
+        let mut resp = Response::new(Body::empty());
+        configured.append_headers(uri, &mut resp);
+        resp.headers().to_owned()
+    }
+
+    #[test]
+    fn preset_default_tls_only_hsts() {
+        let headers = response_headers(&new(Preset::Default, None, true).unwrap(), "/");
+        assert_eq!(
+            headers["strict-transport-security"],
+            "max-age=63072000; includeSubDomains; preload"
+        );
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert_eq!(headers["content-security-policy"], "frame-ancestors 'self'");
+        assert_eq!(headers.len(), 4);
+
+        let headers = response_headers(&new(Preset::Default, None, false).unwrap(), "/");
+        assert!(!headers.contains_key("strict-transport-security"));
+        assert_eq!(headers.len(), 3);
+    }
+
+    #[test]
+    fn preset_cross_origin_isolated() {
+        let headers =
+            response_headers(&new(Preset::CrossOriginIsolated, None, false).unwrap(), "/");
+        assert_eq!(headers["cross-origin-opener-policy"], "same-origin");
+        assert_eq!(headers["cross-origin-embedder-policy"], "require-corp");
+        assert_eq!(headers["cross-origin-resource-policy"], "same-origin");
+    }
+
+    #[test]
+    fn values_and_overrides() {
+        let advanced = settings::SecurityHeaders {
+            values: SecurityHeadersValues {
+                content_security_policy: Some("default-src 'self'".to_owned()),
+                x_frame_options: Some("".to_owned()),
+                hsts_max_age: Some(3600),
+                hsts_preload: Some(false),
+                ..Default::default()
+            },
+            overrides: vec![settings::SecurityHeadersOverrides {
+                source: Glob::new("/wasm/**").unwrap().compile_matcher(),
+                preset: Some(Preset::CrossOriginIsolated),
+                values: SecurityHeadersValues {
+                    permissions_policy: Some("camera=()".to_owned()),
+                    ..Default::default()
+                },
+            }],
+        };
+        let configured = new(Preset::Default, Some(&advanced), true).unwrap();
+
+        let headers = response_headers(&configured, "/index.html");
+        assert_eq!(headers["content-security-policy"], "default-src 'self'");
+        assert_eq!(
+            headers["strict-transport-security"],
+            "max-age=3600; includeSubDomains"
+        );
+        assert!(!headers.contains_key("x-frame-options"));
+        assert!(!headers.contains_key("cross-origin-embedder-policy"));
+
+        let headers = response_headers(&configured, "/wasm/app.wasm");
+        assert_eq!(headers["cross-origin-embedder-policy"], "require-corp");
+        assert_eq!(headers["permissions-policy"], "camera=()");
+        assert_eq!(headers["x-frame-options"], "DENY");
+    }
+
+    #[test]
+    fn invalid_header_value() {
+        let advanced = settings::SecurityHeaders {
+            values: SecurityHeadersValues {
+                referrer_policy: Some("no-referrer\n".to_owned() + "x"),
+                ..Default::default()
+            },
+            overrides: vec![],
+        };
+        assert!(new(Preset::Default, Some(&advanced), false).is_err());
+    }
+}
@@ -6,25 +6,57 @@
 //! Server module intended to construct a multi-thread HTTP or HTTP/2 web server.
 //!
 
-use hyper::server::Server as HyperServer;
+use hyper::{header::HeaderName, server::conn::AddrIncoming, server::Server as HyperServer};
 use listenfd::ListenFd;
+use regex::Regex;
 use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::watch::Receiver;
 
+use crate::auth_throttle::AuthThrottle;
+use crate::bandwidth::Bandwidth;
+#[cfg(feature = "basic-auth")]
+use crate::basic_auth::{self, UserStore};
+use crate::conn_limit::{ConnLimits, LimitedIncoming};
+#[cfg(feature = "csp-nonce")]
+use crate::csp_nonce::CspNonce;
+#[cfg(feature = "form-login")]
+use crate::form_login::{FormLogin, FormLoginOpts};
+#[cfg(feature = "forward-auth")]
+use crate::forward_auth::ForwardAuth;
 use crate::handler::{RequestHandler, RequestHandlerOpts};
+#[cfg(feature = "jwt-auth")]
+use crate::jwt_auth::JwtVerifier;
+use crate::load_shed::LoadShedder;
+use crate::rate_limit::RateLimiter;
+use crate::request_limits::RequestLimits;
 #[cfg(any(unix, windows))]
 use crate::signals;
+#[cfg(feature = "signed-urls")]
+use crate::signed_urls::UrlSigner;
+use crate::timeouts::IdleTimeoutIncoming;
+#[cfg(feature = "compression")]
+use crate::{compression_cache::CompressionCache, compression_dictionary::DictionaryStore};
 
 #[cfg(feature = "http2")]
 use {
     crate::tls::{TlsAcceptor, TlsConfigBuilder},
+    crate::tls_reload::TlsReloader,
     crate::{error, error_page, https_redirect},
-    hyper::server::conn::{AddrIncoming, AddrStream},
+    hyper::server::conn::AddrStream,
     hyper::service::{make_service_fn, service_fn},
 };
 
-use crate::{cors, helpers, logger, Settings};
+use crate::{
+    cors,
+    csp_reports::ReportCollector,
+    helpers,
+    ip_filter::{self, IpFilter},
+    logger, security_headers,
+    settings::file::AuthKind,
+    Settings,
+};
 use crate::{service::RouterService, Context, Result};
 
 /// Define a multi-thread HTTP or HTTP/2 web server.
@@ -111,6 +143,14 @@ impl Server {
 
         // Config-file "advanced" options
         let advanced_opts = self.opts.advanced;
+        #[cfg(feature = "http2")]
+        let (advanced_opts, advanced_tls_certificates) = {
+            let mut advanced_opts = advanced_opts;
+            let tls_certificates = advanced_opts
+                .as_mut()
+                .and_then(|v| v.tls_certificates.take());
+            (advanced_opts, tls_certificates)
+        };
 
         // Config file option
         if let Some(config_file) = general.config_file {
@@ -183,8 +223,71 @@ impl Server {
         );
 
         // Security Headers option
-        let security_headers = general.security_headers;
-        tracing::info!("security headers: enabled={}", security_headers);
+        #[cfg(feature = "http2")]
+        let tls = general.http2;
+        #[cfg(not(feature = "http2"))]
+        let tls = false;
+        let security_headers = if general.security_headers {
+            let advanced = advanced_opts
+                .as_ref()
+                .and_then(|v| v.security_headers.as_ref());
+            Some(
+                security_headers::new(general.security_headers_preset, advanced, tls)
+                    .with_context(|| "failed to configure the security headers")?,
+            )
+        } else {
+            None
+        };
+        tracing::info!(
+            "security headers: enabled={}, preset={:?}",
+            general.security_headers,
+            general.security_headers_preset
+        );
+
+        // CSP nonces for HTML documents
+        #[cfg(feature = "csp-nonce")]
+        let csp_nonce = if general.csp_nonce {
+            Some(
+                CspNonce::new(
+                    &general.csp_nonce_placeholder,
+                    &general.csp_nonce_policy,
+                    general.csp_nonce_tags,
+                )
+                .with_context(|| "failed to configure the csp nonces")?,
+            )
+        } else {
+            None
+        };
+        #[cfg(feature = "csp-nonce")]
+        tracing::info!(
+            "csp nonce: enabled={}, placeholder={}, tags={}",
+            general.csp_nonce,
+            general.csp_nonce_placeholder,
+            general.csp_nonce_tags
+        );
+
+        // CSP violation reports collector
+        let csp_reports_endpoint = general.csp_reports_endpoint.trim();
+        let csp_reports = if csp_reports_endpoint.is_empty() {
+            None
+        } else {
+            Some(
+                ReportCollector::new(
+                    csp_reports_endpoint,
+                    general.csp_reports_file.as_deref(),
+                    general.csp_reports_max_size,
+                    general.csp_reports_rate_limit,
+                )
+                .await
+                .with_context(|| "failed to configure the csp reports collector")?,
+            )
+        };
+        tracing::info!(
+            "csp reports: enabled={}, endpoint={}, file={:?}",
+            csp_reports.is_some(),
+            csp_reports_endpoint,
+            general.csp_reports_file
+        );
 
         // Auto compression based on the `Accept-Encoding` header
         #[cfg(not(feature = "compression"))]
@@ -202,6 +305,48 @@ impl Server {
         #[cfg(feature = "compression")]
         tracing::info!("compression static: enabled={}", compression_static);
 
+        // Cache of dynamically compressed files
+        #[cfg(feature = "compression")]
+        let compression_cache = if compression && general.compression_cache {
+            if let Some(dir) = &general.compression_cache_dir {
+                std::fs::create_dir_all(dir).with_context(|| {
+                    format!(
+                        "failed to create the compression cache directory {}",
+                        dir.display()
+                    )
+                })?;
+            }
+            Some(CompressionCache::new(
+                general.compression_cache_max_size,
+                general.compression_cache_dir,
+            ))
+        } else {
+            None
+        };
+        #[cfg(feature = "compression")]
+        tracing::info!(
+            "compression cache: enabled={}, max_size={}",
+            compression_cache.is_some(),
+            general.compression_cache_max_size
+        );
+
+        // Compression dictionary transport
+        #[cfg(feature = "compression")]
+        let compression_dictionaries = match advanced_opts
+            .as_ref()
+            .and_then(|v| v.compression_dictionaries.as_ref())
+        {
+            Some(rules) if compression || compression_static => {
+                let store = DictionaryStore::new(rules.to_vec());
+                let count = store
+                    .load_dir(&root_dir)
+                    .with_context(|| "failed to load the compression dictionaries")?;
+                tracing::info!("compression dictionaries: enabled=true, found={}", count);
+                Some(store)
+            }
+            _ => None,
+        };
+
         // Directory listing options
         #[cfg(feature = "directory-listing")]
         let dir_listing = general.directory_listing;
@@ -222,6 +367,25 @@ impl Server {
         let cache_control_headers = general.cache_control_headers;
         tracing::info!("cache control headers: enabled={}", cache_control_headers);
 
+        // Cache control for fingerprinted file names option
+        let cache_control_fingerprint = if general.cache_control_fingerprint {
+            let pattern =
+                Regex::new(&general.cache_control_fingerprint_pattern).with_context(|| {
+                    format!(
+                        "invalid cache control fingerprint pattern: {}",
+                        general.cache_control_fingerprint_pattern
+                    )
+                })?;
+            Some(pattern)
+        } else {
+            None
+        };
+        tracing::info!(
+            "cache control fingerprint: enabled={}, pattern={}",
+            general.cache_control_fingerprint,
+            general.cache_control_fingerprint_pattern
+        );
+
         // CORS option
         let cors = cors::new(
             general.cors_allow_origins.trim(),
@@ -231,11 +395,327 @@ impl Server {
 
         #[cfg(feature = "basic-auth")]
         // `Basic` HTTP Authentication Schema option
-        let basic_auth = general.basic_auth.trim().to_owned();
+        let basic_auth = {
+            let pair = general.basic_auth.trim();
+            let pair = if pair.is_empty() { None } else { Some(pair) };
+            if pair.is_some() || general.basic_auth_file.is_some() {
+                Some(
+                    UserStore::new(pair, general.basic_auth_file.as_deref())
+                        .with_context(|| "failed to configure the basic authentication")?
+                        .with_cache_ttl(Duration::from_secs(general.basic_auth_cache_ttl))
+                        .with_max_verifications(general.basic_auth_max_verifications),
+                )
+            } else {
+                None
+            }
+        };
         #[cfg(feature = "basic-auth")]
         tracing::info!(
-            "basic authentication: enabled={}",
-            !general.basic_auth.is_empty()
+            "basic authentication: enabled={}, users={}, file={:?}, cache_ttl={}s, max_verifications={}",
+            basic_auth.is_some(),
+            basic_auth.as_ref().map_or(0, |v| v.len()),
+            general.basic_auth_file,
+            general.basic_auth_cache_ttl,
+            general.basic_auth_max_verifications
+        );
+        #[cfg(feature = "basic-auth")]
+        if let Some(watch) = basic_auth
+            .as_ref()
+            .and_then(|users| users.watch(basic_auth::RELOAD_CHECK_INTERVAL))
+        {
+            tokio::spawn(watch);
+        }
+
+        #[cfg(feature = "jwt-auth")]
+        // JSON Web Token `Bearer` authentication option
+        let jwt_auth = match &general.jwt_keys_file {
+            Some(keys_file) => Some(
+                JwtVerifier::new(
+                    keys_file,
+                    general.jwt_issuer.trim(),
+                    general.jwt_audience.trim(),
+                    general.jwt_cookie.trim(),
+                    general.jwt_leeway,
+                )
+                .with_context(|| "failed to configure the jwt authentication")?,
+            ),
+            None => None,
+        };
+        #[cfg(feature = "jwt-auth")]
+        tracing::info!(
+            "jwt authentication: enabled={}, keys={}, file={:?}, issuer={:?}, audience={:?}, cookie={:?}, leeway={}s",
+            jwt_auth.is_some(),
+            jwt_auth.as_ref().map_or(0, |v| v.len()),
+            general.jwt_keys_file,
+            general.jwt_issuer,
+            general.jwt_audience,
+            general.jwt_cookie,
+            general.jwt_leeway
+        );
+
+        #[cfg(feature = "signed-urls")]
+        // Signed URLs option
+        let signed_urls = UrlSigner::from_options(
+            general.signed_urls_key.trim(),
+            general.signed_urls_key_file.as_deref(),
+        )
+        .with_context(|| "failed to configure the signed urls")?;
+        #[cfg(feature = "signed-urls")]
+        if general.signed_urls_required && signed_urls.is_none() {
+            bail!("the `--signed-urls-required` option requires a key provided via the `--signed-urls-key` or `--signed-urls-key-file` options");
+        }
+        #[cfg(feature = "signed-urls")]
+        tracing::info!(
+            "signed urls: enabled={}, required={}, key_file={:?}",
+            signed_urls.is_some(),
+            general.signed_urls_required,
+            general.signed_urls_key_file
+        );
+
+        #[cfg(feature = "form-login")]
+        // Form login option
+        let form_login = {
+            let login_path = general.login_path.trim();
+            if login_path.is_empty() {
+                None
+            } else {
+                if basic_auth.is_none() {
+                    bail!("form login requires users provided via the `--basic-auth` or `--basic-auth-file` options");
+                }
+                #[cfg(feature = "http2")]
+                let secure = general.http2;
+                #[cfg(not(feature = "http2"))]
+                let secure = false;
+                Some(
+                    FormLogin::new(FormLoginOpts {
+                        login_path,
+                        logout_path: general.logout_path.trim(),
+                        page: general.login_page.as_deref(),
+                        key: general.login_session_key.trim(),
+                        ttl: general.login_session_ttl,
+                        secure,
+                    })
+                    .with_context(|| "failed to configure the form login")?,
+                )
+            }
+        };
+        #[cfg(feature = "form-login")]
+        tracing::info!(
+            "form login: enabled={}, login_path={}, logout_path={}, page={:?}, session_ttl={}s",
+            form_login.is_some(),
+            general.login_path.trim(),
+            general.logout_path.trim(),
+            general.login_page,
+            general.login_session_ttl
+        );
+
+        #[cfg(feature = "forward-auth")]
+        // Forward authentication option
+        let forward_auth = {
+            let url = general.forward_auth_url.trim();
+            if url.is_empty() {
+                None
+            } else {
+                Some(
+                    ForwardAuth::new(
+                        url,
+                        &general.forward_auth_request_headers,
+                        &general.forward_auth_response_headers,
+                        Duration::from_secs(general.forward_auth_timeout),
+                    )
+                    .with_context(|| "failed to configure the forward authentication")?,
+                )
+            }
+        };
+        #[cfg(feature = "forward-auth")]
+        tracing::info!(
+            "forward authentication: enabled={}, url={}, request_headers={}, response_headers={}, timeout={}s",
+            forward_auth.is_some(),
+            general.forward_auth_url.trim(),
+            general.forward_auth_request_headers,
+            general.forward_auth_response_headers,
+            general.forward_auth_timeout
+        );
+
+        // Authentication failures throttle option
+        let auth_throttle = if general.auth_max_failures > 0 {
+            Some(AuthThrottle::new(
+                general.auth_max_failures,
+                Duration::from_secs(general.auth_ban_duration),
+                Duration::from_secs(general.auth_max_ban_duration),
+            ))
+        } else {
+            None
+        };
+        tracing::info!(
+            "auth failures throttle: enabled={}, max_failures={}, ban={}s, max_ban={}s",
+            auth_throttle.is_some(),
+            general.auth_max_failures,
+            general.auth_ban_duration,
+            general.auth_max_ban_duration
+        );
+
+        // Authentication rules option
+        let auth_rules = advanced_opts.as_ref().and_then(|v| v.auth.as_ref());
+        if let Some(rules) = auth_rules {
+            #[cfg(feature = "basic-auth")]
+            let has_users = basic_auth.is_some();
+            #[cfg(not(feature = "basic-auth"))]
+            let has_users = false;
+            if !has_users && rules.iter().any(|r| r.kind == AuthKind::Basic) {
+                bail!("basic auth rules require users provided via the `--basic-auth` or `--basic-auth-file` options");
+            }
+            #[cfg(feature = "jwt-auth")]
+            let has_jwt_keys = jwt_auth.is_some();
+            #[cfg(not(feature = "jwt-auth"))]
+            let has_jwt_keys = false;
+            if !has_jwt_keys && rules.iter().any(|r| r.kind == AuthKind::Jwt) {
+                bail!(
+                    "jwt auth rules require public keys provided via the `--jwt-keys-file` option"
+                );
+            }
+            #[cfg(feature = "signed-urls")]
+            let has_signed_urls_key = signed_urls.is_some();
+            #[cfg(not(feature = "signed-urls"))]
+            let has_signed_urls_key = false;
+            if !has_signed_urls_key && rules.iter().any(|r| r.kind == AuthKind::SignedUrl) {
+                bail!("signed-url auth rules require a key provided via the `--signed-urls-key` or `--signed-urls-key-file` options");
+            }
+            #[cfg(feature = "http2")]
+            let has_client_ca = general.http2 && general.http2_tls_client_ca.is_some();
+            #[cfg(not(feature = "http2"))]
+            let has_client_ca = false;
+            #[cfg(feature = "forward-auth")]
+            let has_forward_auth = forward_auth.is_some();
+            #[cfg(not(feature = "forward-auth"))]
+            let has_forward_auth = false;
+            if !has_forward_auth && rules.iter().any(|r| r.kind == AuthKind::Forward) {
+                bail!("forward auth rules require an authorization service provided via the `--forward-auth-url` option");
+            }
+            if !has_client_ca && rules.iter().any(|r| r.kind == AuthKind::ClientCert) {
+                bail!("client-cert auth rules require HTTP/2 + TLS and client CA certificates provided via the `--http2-tls-client-ca` option");
+            }
+        }
+        tracing::info!(
+            "auth rules: enabled={}, rules={}",
+            auth_rules.is_some(),
+            auth_rules.map_or(0, |v| v.len())
+        );
+
+        // Client IP filter and trusted proxies options
+        let ip_filter = IpFilter::new(
+            ip_filter::parse_list(&general.ip_allow)
+                .with_context(|| "failed to parse the `--ip-allow` list")?,
+            ip_filter::parse_list(&general.ip_deny)
+                .with_context(|| "failed to parse the `--ip-deny` list")?,
+        );
+        let trusted_proxies = ip_filter::parse_list(&general.trusted_proxies)
+            .with_context(|| "failed to parse the `--trusted-proxies` list")?;
+        let ip_filter_rules = advanced_opts.as_ref().and_then(|v| v.ip_filter.as_ref());
+        tracing::info!(
+            "ip filter: allow={:?}, deny={:?}, rules={}, trusted_proxies={:?}",
+            general.ip_allow.trim(),
+            general.ip_deny.trim(),
+            ip_filter_rules.map_or(0, |v| v.len()),
+            general.trusted_proxies.trim()
+        );
+
+        // Request rate limit option
+        let rate_limit = if general.rate_limit != 0.0 {
+            Some(
+                RateLimiter::new(general.rate_limit, general.rate_limit_burst)
+                    .with_context(|| "failed to configure the rate limit")?,
+            )
+        } else {
+            None
+        };
+        let rate_limit_key = match general.rate_limit_key.trim() {
+            "" => None,
+            name => Some(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid rate limit key header: {name}"))?,
+            ),
+        };
+        let rate_limit_rules = advanced_opts.as_ref().and_then(|v| v.rate_limit.as_ref());
+        tracing::info!(
+            "rate limit: enabled={}, rate={}/s, burst={}, key={:?}, rules={}",
+            rate_limit.is_some(),
+            general.rate_limit,
+            general.rate_limit_burst,
+            general.rate_limit_key.trim(),
+            rate_limit_rules.map_or(0, |v| v.len())
+        );
+
+        // Bandwidth limit options
+        let bandwidth_limit_global = (general.bandwidth_limit_global > 0)
+            .then(|| Arc::new(Bandwidth::new(general.bandwidth_limit_global)));
+        let bandwidth_rules = advanced_opts
+            .as_ref()
+            .and_then(|v| v.bandwidth_limit.as_ref());
+        tracing::info!(
+            "bandwidth limit: connection={}B/s, global={}B/s, after={}B, rules={}",
+            general.bandwidth_limit,
+            general.bandwidth_limit_global,
+            general.bandwidth_limit_after,
+            bandwidth_rules.map_or(0, |v| v.len())
+        );
+
+        // Connection limits and load shedding options
+        let conn_limits = Arc::new(ConnLimits::new(
+            general.max_connections,
+            general.max_connections_per_ip,
+        ));
+        let load_shed = Some(Arc::new(LoadShedder::new(
+            general.max_inflight_requests,
+            Duration::from_millis(general.max_blocking_delay),
+        )))
+        .filter(|shedder| !shedder.is_empty());
+        if let Some(shedder) = load_shed
+            .as_ref()
+            .filter(|_| general.max_blocking_delay > 0)
+        {
+            tokio::spawn(shedder.clone().probe_blocking_pool());
+        }
+        tracing::info!(
+            "connection limits: max={}, max_per_ip={}",
+            general.max_connections,
+            general.max_connections_per_ip
+        );
+        tracing::info!(
+            "load shedding: enabled={}, max_inflight_requests={}, max_blocking_delay={}ms, retry_after={}s",
+            load_shed.is_some(),
+            general.max_inflight_requests,
+            general.max_blocking_delay,
+            general.load_shed_retry_after
+        );
+
+        // Timeout options
+        let header_read_timeout = Duration::from_secs(general.header_read_timeout);
+        let keep_alive_timeout = Duration::from_secs(general.keep_alive_timeout);
+        // HTTP/2 connections kept alive are pinged to close the ones of unresponsive clients
+        let http2_keep_alive_interval =
+            Some(keep_alive_timeout).filter(|v| general.keep_alive && !v.is_zero());
+        let response_write_timeout =
+            Some(Duration::from_secs(general.response_write_timeout)).filter(|v| !v.is_zero());
+        tracing::info!(
+            "timeouts: header_read={}s, keep_alive={}, keep_alive_timeout={}s, response_write={}s",
+            general.header_read_timeout,
+            general.keep_alive,
+            general.keep_alive_timeout,
+            general.response_write_timeout
+        );
+
+        // Request size limits options
+        let request_limits = RequestLimits {
+            max_uri_length: general.max_uri_length,
+            max_header_bytes: general.max_header_bytes,
+            max_headers: general.max_headers,
+        };
+        tracing::info!(
+            "request limits: max_uri_length={}, max_header_bytes={}, max_headers={}",
+            general.max_uri_length,
+            general.max_header_bytes,
+            general.max_headers
         );
 
         // Log remote address option
@@ -263,6 +743,10 @@ impl Server {
                 root_dir,
                 compression,
                 compression_static,
+                #[cfg(feature = "compression")]
+                compression_cache,
+                #[cfg(feature = "compression")]
+                compression_dictionaries,
                 #[cfg(feature = "directory-listing")]
                 dir_listing,
                 #[cfg(feature = "directory-listing")]
@@ -271,13 +755,39 @@ impl Server {
                 dir_listing_format,
                 cors,
                 security_headers,
+                #[cfg(feature = "csp-nonce")]
+                csp_nonce,
+                csp_reports,
                 cache_control_headers,
+                cache_control_fingerprint,
                 page404: page404.clone(),
                 page50x: page50x.clone(),
                 #[cfg(feature = "fallback-page")]
                 page_fallback,
                 #[cfg(feature = "basic-auth")]
                 basic_auth,
+                #[cfg(feature = "jwt-auth")]
+                jwt_auth,
+                #[cfg(feature = "signed-urls")]
+                signed_urls,
+                #[cfg(feature = "signed-urls")]
+                signed_urls_required: general.signed_urls_required,
+                #[cfg(feature = "form-login")]
+                form_login,
+                #[cfg(feature = "forward-auth")]
+                forward_auth,
+                auth_throttle,
+                ip_filter,
+                trusted_proxies,
+                rate_limit,
+                rate_limit_key,
+                bandwidth_limit: general.bandwidth_limit,
+                bandwidth_limit_global,
+                bandwidth_limit_after: general.bandwidth_limit_after,
+                load_shed,
+                load_shed_retry_after: general.load_shed_retry_after,
+                response_write_timeout,
+                request_limits,
                 log_remote_address,
                 redirect_trailing_slash,
                 ignore_hidden_files,
@@ -339,13 +849,79 @@ impl Server {
                 _ => bail!("failed to initialize TLS because key file missing"),
             };
 
-            let tls = TlsConfigBuilder::new()
-                .cert_path(&http2_tls_cert)
-                .key_path(&http2_tls_key)
+            let client_ca = general.http2_tls_client_ca.clone();
+            let client_auth_optional = general.http2_tls_client_auth_optional;
+            if let Some(client_ca) = &client_ca {
+                tracing::info!(
+                    "http2 tls client auth: enabled=true, optional={}, ca={}",
+                    client_auth_optional,
+                    client_ca.display()
+                );
+            }
+
+            // Certificates selected by the server name of the clients
+            let sni_certs = advanced_tls_certificates.unwrap_or_default();
+            tracing::info!("http2 tls sni certificates: {}", sni_certs.len());
+
+            let ocsp_response = general.http2_tls_ocsp_response.clone();
+            let has_ocsp =
+                ocsp_response.is_some() || sni_certs.iter().any(|c| c.ocsp_response.is_some());
+            if let Some(ocsp_response) = &ocsp_response {
+                tracing::info!("http2 tls ocsp response: {}", ocsp_response.display());
+            }
+
+            // The TLS configuration is rebuilt from the same files on every reload
+            let mut tls_paths = vec![http2_tls_cert.clone(), http2_tls_key.clone()];
+            tls_paths.extend(client_ca.clone());
+            tls_paths.extend(ocsp_response.clone());
+            for c in &sni_certs {
+                tls_paths.extend([c.cert.clone(), c.key.clone()]);
+                tls_paths.extend(c.ocsp_response.clone());
+            }
+            let tls = TlsReloader::new(tls_paths, move || {
+                let mut tls = TlsConfigBuilder::new()
+                    .cert_path(&http2_tls_cert)
+                    .key_path(&http2_tls_key);
+                if let Some(ocsp_response) = &ocsp_response {
+                    tls = tls.ocsp_resp_path(ocsp_response);
+                }
+                for c in &sni_certs {
+                    tls = tls.sni_cert_path(
+                        c.hosts.clone(),
+                        &c.cert,
+                        &c.key,
+                        c.ocsp_response.as_deref(),
+                    );
+                }
+                match &client_ca {
+                    Some(ca) if client_auth_optional => tls.client_auth_optional_path(ca),
+                    Some(ca) => tls.client_auth_required_path(ca),
+                    None => tls,
+                }
                 .build()
-                .with_context(|| {
-                    "failed to initialize TLS probably because invalid cert or key file"
-                })?;
+            })
+            .with_context(|| {
+                "failed to initialize TLS probably because invalid cert or key file"
+            })?;
+            let tls = Arc::new(tls);
+            let tls_config = tls.config();
+
+            let tls_reload = Arc::new(tokio::sync::Notify::new());
+            let tls_reload_interval = Duration::from_secs(general.http2_tls_reload_interval);
+            tracing::info!(
+                "http2 tls reload: interval={}s, sighup={}",
+                general.http2_tls_reload_interval,
+                cfg!(unix)
+            );
+            if has_ocsp && general.http2_tls_ocsp_refresh_interval > 0 {
+                tracing::info!(
+                    "http2 tls ocsp refresh: interval={}s",
+                    general.http2_tls_ocsp_refresh_interval
+                );
+                let interval = Duration::from_secs(general.http2_tls_ocsp_refresh_interval);
+                tokio::spawn(tls.clone().refresh(interval));
+            }
+            tokio::spawn(tls.watch(tls_reload_interval, tls_reload.clone()));
 
             #[cfg(unix)]
             let signals = signals::create_signals()
@@ -353,12 +929,36 @@ impl Server {
             #[cfg(unix)]
             let handle = signals.handle();
 
-            let http2_server =
-                HyperServer::builder(TlsAcceptor::new(tls, incoming)).serve(router_service);
+            let handshake_timeout = Duration::from_secs(general.http2_tls_handshake_timeout);
+            tracing::info!(
+                "http2 tls handshake timeout: {}s",
+                general.http2_tls_handshake_timeout
+            );
+
+            let mut http2_server = HyperServer::builder(IdleTimeoutIncoming::new(
+                LimitedIncoming::new(
+                    TlsAcceptor::new(tls_config, incoming)
+                        .with_handshake_timeout(handshake_timeout),
+                    conn_limits,
+                ),
+                keep_alive_timeout,
+            ))
+            .http1_keepalive(general.keep_alive)
+            .http2_keep_alive_interval(http2_keep_alive_interval);
+            if !header_read_timeout.is_zero() {
+                http2_server = http2_server.http1_header_read_timeout(header_read_timeout);
+            }
+            if let Some(size) = request_limits.http2_max_header_list_size() {
+                http2_server = http2_server.http2_max_header_list_size(size);
+            }
+            let http2_server = http2_server.serve(router_service);
 
             #[cfg(unix)]
-            let http2_server = http2_server
-                .with_graceful_shutdown(signals::wait_for_signals(signals, grace_period));
+            let http2_server = http2_server.with_graceful_shutdown(signals::wait_for_signals(
+                signals,
+                grace_period,
+                Some(tls_reload),
+            ));
 
             #[cfg(windows)]
             let http2_cancel_recv = Arc::new(tokio::sync::Mutex::new(_cancel_recv));
@@ -455,7 +1055,7 @@ impl Server {
 
                 #[cfg(unix)]
                 let server_redirect = server_redirect.with_graceful_shutdown(
-                    signals::wait_for_signals(redirect_signals, grace_period),
+                    signals::wait_for_signals(redirect_signals, grace_period, None),
                 );
                 #[cfg(windows)]
                 let server_redirect = server_redirect.with_graceful_shutdown(async move {
@@ -518,14 +1118,33 @@ impl Server {
             .set_nonblocking(true)
             .with_context(|| "failed to set TCP non-blocking mode")?;
 
-        let http1_server = HyperServer::from_tcp(tcp_listener)
-            .unwrap()
-            .tcp_nodelay(true)
-            .serve(router_service);
+        let listener = tokio::net::TcpListener::from_std(tcp_listener)
+            .with_context(|| "failed to create tokio::net::TcpListener")?;
+        let mut incoming = AddrIncoming::from_listener(listener).with_context(|| {
+            "failed to create an AddrIncoming from the current tokio::net::TcpListener"
+        })?;
+        incoming.set_nodelay(true);
+
+        let mut http1_server = HyperServer::builder(IdleTimeoutIncoming::new(
+            LimitedIncoming::new(incoming, conn_limits),
+            keep_alive_timeout,
+        ))
+        .http1_keepalive(general.keep_alive)
+        .http2_keep_alive_interval(http2_keep_alive_interval);
+        if !header_read_timeout.is_zero() {
+            http1_server = http1_server.http1_header_read_timeout(header_read_timeout);
+        }
+        if let Some(size) = request_limits.http2_max_header_list_size() {
+            http1_server = http1_server.http2_max_header_list_size(size);
+        }
+        let http1_server = http1_server.serve(router_service);
 
         #[cfg(unix)]
-        let http1_server =
-            http1_server.with_graceful_shutdown(signals::wait_for_signals(signals, grace_period));
+        let http1_server = http1_server.with_graceful_shutdown(signals::wait_for_signals(
+            signals,
+            grace_period,
+            None,
+        ));
 
         #[cfg(windows)]
         let http1_cancel_recv = Arc::new(tokio::sync::Mutex::new(_cancel_recv));
@@ -13,8 +13,16 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use tokio::time::Instant;
 
-use crate::{handler::RequestHandler, transport::Transport, Error};
+use crate::{
+    bandwidth::{Bandwidth, ConnectionBandwidth},
+    handler::RequestHandler,
+    load_shed::InflightBody,
+    timeouts::{ActiveRequest, ActiveRequests, DeadlineBody},
+    transport::{PeerIdentity, Transport},
+    Error,
+};
 
 /// It defines the router service which is the main entry point for Hyper Server.
 pub struct RouterService {
@@ -40,7 +48,11 @@ impl<T: Transport + Send + 'static> Service<&T> for RouterService {
     }
 
     fn call(&mut self, conn: &T) -> Self::Future {
-        ready(Ok(self.builder.build(conn.remote_addr())))
+        ready(Ok(self
+            .builder
+            .build(conn.remote_addr())
+            .with_peer_identity(conn.peer_identity())
+            .with_active_requests(conn.active_requests())))
     }
 }
 
@@ -48,6 +60,23 @@ impl<T: Transport + Send + 'static> Service<&T> for RouterService {
 pub struct RequestService {
     handler: Arc<RequestHandler>,
     remote_addr: Option<SocketAddr>,
+    peer_identity: Option<PeerIdentity>,
+    bandwidth: Option<ConnectionBandwidth>,
+    active_requests: Option<ActiveRequests>,
+}
+
+impl RequestService {
+    /// Sets the client identity slot of the connection.
+    pub fn with_peer_identity(mut self, peer_identity: Option<PeerIdentity>) -> Self {
+        self.peer_identity = peer_identity;
+        self
+    }
+
+    /// Sets the counter of the requests being served on the connection.
+    pub fn with_active_requests(mut self, active_requests: Option<ActiveRequests>) -> Self {
+        self.active_requests = active_requests;
+        self
+    }
 }
 
 impl Service<Request<Body>> for RequestService {
@@ -62,7 +91,29 @@ impl Service<Request<Body>> for RequestService {
     fn call(&mut self, mut req: Request<Body>) -> Self::Future {
         let handler = self.handler.clone();
         let remote_addr = self.remote_addr;
-        Box::pin(async move { handler.handle(&mut req, remote_addr).await })
+        // The handshake is already completed when the requests are received
+        if let Some(identity) = self.peer_identity.as_ref().and_then(|v| v.get()) {
+            req.extensions_mut().insert(identity.clone());
+        }
+        if let Some(bandwidth) = &self.bandwidth {
+            req.extensions_mut().insert(bandwidth.clone());
+        }
+        // The connection isn't idle until the response body is sent
+        let mut active = self.active_requests.as_ref().map(ActiveRequest::start);
+        Box::pin(async move {
+            let mut resp = handler.handle(&mut req, remote_addr).await?;
+            if let Some(timeout) = handler.opts.response_write_timeout {
+                // The connection also enforces the deadline in case the client stops reading
+                if let Some(active) = &mut active {
+                    active.set_write_deadline(Instant::now() + timeout);
+                }
+                resp = resp.map(|body| DeadlineBody::wrap(body, timeout));
+            }
+            if let Some(active) = active {
+                resp = resp.map(|body| InflightBody::wrap(body, active));
+            }
+            Ok(resp)
+        })
     }
 }
 
@@ -81,9 +132,14 @@ impl RequestServiceBuilder {
 
     /// Build a new request service.
     pub fn build(&self, remote_addr: Option<SocketAddr>) -> RequestService {
+        // The responses of a connection share its bandwidth limit
+        let rate = self.handler.opts.bandwidth_limit;
         RequestService {
             handler: self.handler.clone(),
             remote_addr,
+            peer_identity: None,
+            active_requests: None,
+            bandwidth: (rate > 0).then(|| ConnectionBandwidth(Arc::new(Bandwidth::new(rate)))),
         }
     }
 }
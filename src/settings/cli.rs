@@ -11,6 +11,8 @@ use std::path::PathBuf;
 #[cfg(feature = "directory-listing")]
 use crate::directory_listing::DirListFmt;
 
+use crate::security_headers::Preset;
+
 /// General server configuration available in CLI and config file options.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -178,6 +180,54 @@ pub struct General {
     /// Specify the file path to read the private key.
     pub http2_tls_key: Option<PathBuf>,
 
+    #[arg(long, env = "SERVER_HTTP2_TLS_CLIENT_CA")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Specify the file path to read the CA certificates used to verify client certificates (mutual TLS). Clients without a valid certificate are rejected during the handshake unless `--http2-tls-client-auth-optional` is enabled.
+    pub http2_tls_client_ca: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_HTTP2_TLS_CLIENT_AUTH_OPTIONAL"
+    )]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Accept TLS clients without a certificate. Certificates that are sent are still verified against `--http2-tls-client-ca`.
+    pub http2_tls_client_auth_optional: bool,
+
+    #[arg(long, default_value = "0", env = "SERVER_HTTP2_TLS_HANDSHAKE_TIMEOUT")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Maximum time in seconds to complete the TLS handshake of a connection. A zero value disables it.
+    pub http2_tls_handshake_timeout: u64,
+
+    #[arg(long, default_value = "10", env = "SERVER_HTTP2_TLS_RELOAD_INTERVAL")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Interval in seconds to check the TLS certificate and key files for changes. Changed files are reloaded without restarting the server, like on `SIGHUP`. A zero value disables the checks.
+    pub http2_tls_reload_interval: u64,
+
+    #[arg(long, env = "SERVER_HTTP2_TLS_OCSP_RESPONSE")]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Specify the file path to read the DER-encoded OCSP response stapled to the TLS handshakes. The response must match the certificate and is not stapled once expired.
+    pub http2_tls_ocsp_response: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value = "3600",
+        env = "SERVER_HTTP2_TLS_OCSP_REFRESH_INTERVAL"
+    )]
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    /// Interval in seconds to re-read the OCSP response files, so responses renewed by an external tool get stapled. A zero value disables it.
+    pub http2_tls_ocsp_refresh_interval: u64,
+
     #[arg(
         long,
         default_value = "false",
@@ -256,6 +306,37 @@ pub struct General {
     /// The compression type is determined by the `Accept-Encoding` header.
     pub compression_static: bool,
 
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_COMPRESSION_CACHE",
+    )]
+    /// Cache the dynamically compressed files (see `--compression`) keyed by file path, modification time and encoding
+    /// so repeated requests are served from the compressed copy.
+    pub compression_cache: bool,
+
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[arg(
+        long,
+        default_value = "67108864",
+        env = "SERVER_COMPRESSION_CACHE_MAX_SIZE"
+    )]
+    /// Maximum size in bytes of the in-memory compression cache. Files bigger than this value are compressed on the fly instead.
+    pub compression_cache_max_size: u64,
+
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[arg(long, env = "SERVER_COMPRESSION_CACHE_DIR")]
+    /// Optional directory path to persist the compression cache entries across server restarts.
+    pub compression_cache_dir: Option<PathBuf>,
+
     #[cfg(feature = "directory-listing")]
     #[cfg_attr(docsrs, doc(cfg(feature = "directory-listing")))]
     #[arg(
@@ -306,10 +387,80 @@ pub struct General {
         env = "SERVER_SECURITY_HEADERS",
     )]
     /// Enable security headers by default when HTTP/2 feature is activated.
-    /// Headers included: "Strict-Transport-Security: max-age=63072000; includeSubDomains; preload" (2 years max-age),
-    /// "X-Frame-Options: DENY" and "Content-Security-Policy: frame-ancestors 'self'".
+    /// Headers included depend on the `--security-headers-preset` option. By default: "Strict-Transport-Security: max-age=63072000; includeSubDomains; preload" (2 years max-age, TLS only),
+    /// "X-Frame-Options: DENY", "X-Content-Type-Options: nosniff" and "Content-Security-Policy: frame-ancestors 'self'".
     pub security_headers: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value = "default",
+        env = "SERVER_SECURITY_HEADERS_PRESET",
+        ignore_case(true)
+    )]
+    /// Named set of security headers to use. Values: "default", "strict", "cross-origin-isolated" or "relaxed". Header values and per-path overrides can be adjusted via the config file.
+    pub security_headers_preset: Preset,
+
+    #[cfg(feature = "csp-nonce")]
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_CSP_NONCE",
+    )]
+    /// Generate a random nonce per HTML response, inject it into the document and send a matching "Content-Security-Policy" header.
+    pub csp_nonce: bool,
+
+    #[cfg(feature = "csp-nonce")]
+    #[arg(
+        long,
+        default_value = crate::csp_nonce::DEFAULT_PLACEHOLDER,
+        env = "SERVER_CSP_NONCE_PLACEHOLDER"
+    )]
+    /// Placeholder of HTML documents replaced by the CSP nonce. An empty value disables it.
+    pub csp_nonce_placeholder: String,
+
+    #[cfg(feature = "csp-nonce")]
+    #[arg(
+        long,
+        default_value = "true",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_CSP_NONCE_TAGS",
+    )]
+    /// Add the CSP nonce as a "nonce" attribute to all "<script>" and "<style>" tags of HTML documents lacking one.
+    pub csp_nonce_tags: bool,
+
+    #[cfg(feature = "csp-nonce")]
+    #[arg(
+        long,
+        default_value = crate::csp_nonce::DEFAULT_POLICY,
+        env = "SERVER_CSP_NONCE_POLICY"
+    )]
+    /// "Content-Security-Policy" header value sent along with rewritten HTML documents. The "{nonce}" token is replaced by the CSP nonce.
+    pub csp_nonce_policy: String,
+
+    #[arg(long, default_value = "", env = "SERVER_CSP_REPORTS_ENDPOINT")]
+    /// URI path of an endpoint collecting the CSP ("report-uri") and Reporting API ("report-to") violation reports sent via POST. E.g "/csp-reports". An empty value disables it.
+    pub csp_reports_endpoint: String,
+
+    #[arg(long, env = "SERVER_CSP_REPORTS_FILE")]
+    /// Optional file path where the collected violation reports are appended as JSON lines. Reports are logged otherwise.
+    pub csp_reports_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "65536", env = "SERVER_CSP_REPORTS_MAX_SIZE")]
+    /// Maximum size in bytes of a violation report request body.
+    pub csp_reports_max_size: u64,
+
+    #[arg(long, default_value = "60", env = "SERVER_CSP_REPORTS_RATE_LIMIT")]
+    /// Maximum number of violation report requests per minute and client IP. Zero disables the limit.
+    pub csp_reports_rate_limit: u32,
+
     #[arg(
         long,
         short = 'e',
@@ -329,11 +480,247 @@ pub struct General {
     /// Enable cache control headers for incoming requests based on a set of file types. The file type list can be found on `src/control_headers.rs` file.
     pub cache_control_headers: bool,
 
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_CACHE_CONTROL_FINGERPRINT",
+    )]
+    /// Cache fingerprinted asset files (whose file name contains a content hash) for one year as immutable while HTML documents always get revalidated via `no-cache`. It requires the `--cache-control-headers` option to be enabled.
+    pub cache_control_fingerprint: bool,
+
+    #[arg(
+        long,
+        default_value = crate::control_headers::DEFAULT_FINGERPRINT_PATTERN,
+        env = "SERVER_CACHE_CONTROL_FINGERPRINT_PATTERN"
+    )]
+    /// Regular expression used to detect fingerprinted file names. It is matched against the file name of the request path.
+    pub cache_control_fingerprint_pattern: String,
+
+    #[arg(long, default_value = "", env = "SERVER_IP_ALLOW")]
+    /// List of client IP addresses or CIDR ranges separated by commas which are allowed to access the server. Any client is allowed if empty.
+    pub ip_allow: String,
+
+    #[arg(long, default_value = "", env = "SERVER_IP_DENY")]
+    /// List of client IP addresses or CIDR ranges separated by commas which are denied to access the server. Denied addresses take precedence over the allowed ones.
+    pub ip_deny: String,
+
+    #[arg(long, default_value = "", env = "SERVER_TRUSTED_PROXIES")]
+    /// List of proxy IP addresses or CIDR ranges separated by commas whose "X-Forwarded-For" header is trusted to determine the client IP address.
+    pub trusted_proxies: String,
+
     #[cfg(feature = "basic-auth")]
     /// It provides The "Basic" HTTP Authentication scheme using credentials as "user-id:password" pairs. Password must be encoded using the "BCrypt" password-hashing function.
     #[arg(long, default_value = "", env = "SERVER_BASIC_AUTH")]
     pub basic_auth: String,
 
+    #[cfg(feature = "basic-auth")]
+    /// Path to an Apache-style htpasswd file containing "user-id:password" lines which is reloaded when it changes. Supported password hashes are "BCrypt", "SHA-512/SHA-256 crypt" and "APR1-MD5".
+    #[arg(long, env = "SERVER_BASIC_AUTH_FILE")]
+    pub basic_auth_file: Option<PathBuf>,
+
+    #[cfg(feature = "basic-auth")]
+    /// Lifetime in seconds of the cache of recently verified Basic Authentication credentials, which avoids verifying slow password hashes like "BCrypt" on every request. A zero value disables it.
+    #[arg(long, default_value = "300", env = "SERVER_BASIC_AUTH_CACHE_TTL")]
+    pub basic_auth_cache_ttl: u64,
+
+    #[cfg(feature = "basic-auth")]
+    /// Maximum number of password hashes verified concurrently. Further verifications wait for their turn.
+    #[arg(long, default_value = "4", env = "SERVER_BASIC_AUTH_MAX_VERIFICATIONS")]
+    pub basic_auth_max_verifications: usize,
+
+    #[cfg(feature = "jwt-auth")]
+    /// Path to a JWKS (JSON) or PEM file containing the public keys used to verify "Bearer" JSON Web Tokens. Supported algorithms are "RS256", "ES256" and "EdDSA". If provided, a valid token is required for every request not matching an authentication rule.
+    #[arg(long, env = "SERVER_JWT_KEYS_FILE")]
+    pub jwt_keys_file: Option<PathBuf>,
+
+    #[cfg(feature = "jwt-auth")]
+    /// Expected JSON Web Token issuer ("iss" claim). The claim is not checked if empty.
+    #[arg(long, default_value = "", env = "SERVER_JWT_ISSUER")]
+    pub jwt_issuer: String,
+
+    #[cfg(feature = "jwt-auth")]
+    /// Expected JSON Web Token audience ("aud" claim). The claim is not checked if empty.
+    #[arg(long, default_value = "", env = "SERVER_JWT_AUDIENCE")]
+    pub jwt_audience: String,
+
+    #[cfg(feature = "jwt-auth")]
+    /// Name of a cookie the JSON Web Token is taken from when no "Authorization: Bearer" header is present. Tokens are only read from the header if empty.
+    #[arg(long, default_value = "", env = "SERVER_JWT_COOKIE")]
+    pub jwt_cookie: String,
+
+    #[cfg(feature = "jwt-auth")]
+    /// Clock skew in seconds tolerated when checking the "exp" and "nbf" claims of a JSON Web Token.
+    #[arg(long, default_value = "60", env = "SERVER_JWT_LEEWAY")]
+    pub jwt_leeway: u64,
+
+    #[cfg(feature = "signed-urls")]
+    /// Secret key used to sign and verify expiring URLs of the "signed-url" authentication rules. It should be at least 16 bytes long.
+    #[arg(long, default_value = "", env = "SERVER_SIGNED_URLS_KEY")]
+    pub signed_urls_key: String,
+
+    #[cfg(feature = "signed-urls")]
+    /// Path to a file containing the secret key used to sign and verify expiring URLs. It can not be used along with "--signed-urls-key".
+    #[arg(long, env = "SERVER_SIGNED_URLS_KEY_FILE")]
+    pub signed_urls_key_file: Option<PathBuf>,
+
+    #[cfg(feature = "signed-urls")]
+    #[arg(
+        long,
+        default_value = "false",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_SIGNED_URLS_REQUIRED",
+    )]
+    /// Require a valid signature for every request not matching an authentication rule. It requires the "--signed-urls-key" or "--signed-urls-key-file" options.
+    pub signed_urls_required: bool,
+
+    #[cfg(feature = "form-login")]
+    /// URI path of a form login page checking the credentials of the "--basic-auth" or "--basic-auth-file" users and setting a signed session cookie. E.g "/login". An empty value disables it.
+    #[arg(long, default_value = "", env = "SERVER_LOGIN_PATH")]
+    pub login_path: String,
+
+    #[cfg(feature = "form-login")]
+    /// URI path of the endpoint ending the form login session.
+    #[arg(long, default_value = "/logout", env = "SERVER_LOGOUT_PATH")]
+    pub logout_path: String,
+
+    #[cfg(feature = "form-login")]
+    /// HTML file path of a custom login page. The "{{action}}", "{{next}}" and "{{error}}" placeholders are replaced by the form action, the page to return to and the login error message.
+    #[arg(long, env = "SERVER_LOGIN_PAGE")]
+    pub login_page: Option<PathBuf>,
+
+    #[cfg(feature = "form-login")]
+    /// Secret key used to sign the form login session cookies. It should be at least 16 bytes long. A random key is generated on startup if empty.
+    #[arg(long, default_value = "", env = "SERVER_LOGIN_SESSION_KEY")]
+    pub login_session_key: String,
+
+    #[cfg(feature = "form-login")]
+    /// Lifetime in seconds of the form login sessions.
+    #[arg(long, default_value = "28800", env = "SERVER_LOGIN_SESSION_TTL")]
+    pub login_session_ttl: u64,
+
+    #[cfg(feature = "forward-auth")]
+    /// URL of an HTTP authorization service asked whether each request not matching an authentication rule is allowed. E.g "http://127.0.0.1:9091/verify". The request is allowed on a 2xx response. An empty value disables it.
+    #[arg(long, default_value = "", env = "SERVER_FORWARD_AUTH_URL")]
+    pub forward_auth_url: String,
+
+    #[cfg(feature = "forward-auth")]
+    /// List of request headers sent to the authorization service separated by commas.
+    #[arg(
+        long,
+        default_value = "authorization, cookie",
+        env = "SERVER_FORWARD_AUTH_REQUEST_HEADERS"
+    )]
+    pub forward_auth_request_headers: String,
+
+    #[cfg(feature = "forward-auth")]
+    /// List of headers of the allowing authorization service responses added to the logs and copied onto the responses, separated by commas. E.g "x-user, x-groups".
+    #[arg(long, default_value = "", env = "SERVER_FORWARD_AUTH_RESPONSE_HEADERS")]
+    pub forward_auth_response_headers: String,
+
+    #[cfg(feature = "forward-auth")]
+    /// Timeout in seconds of the requests to the authorization service.
+    #[arg(long, default_value = "5", env = "SERVER_FORWARD_AUTH_TIMEOUT")]
+    pub forward_auth_timeout: u64,
+
+    /// Number of consecutive authentication failures of a client IP address before it gets temporarily banned with "429 Too Many Requests" responses to the requests requiring authentication. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_AUTH_MAX_FAILURES")]
+    pub auth_max_failures: u32,
+
+    /// Duration in seconds of the first ban of a client failing to authenticate. It's doubled for every further failure.
+    #[arg(long, default_value = "60", env = "SERVER_AUTH_BAN_DURATION")]
+    pub auth_ban_duration: u64,
+
+    /// Maximum duration in seconds of the ban of a client failing to authenticate. The failures of a client are forgotten once it doesn't fail during this time.
+    #[arg(long, default_value = "3600", env = "SERVER_AUTH_MAX_BAN_DURATION")]
+    pub auth_max_ban_duration: u64,
+
+    /// Maximum number of requests per second of a client, which can be fractional (e.g. "0.5"). Requests exceeding it get "429 Too Many Requests" responses. A zero value disables it. Path-scoped limits can be defined in the configuration file.
+    #[arg(long, default_value = "0", env = "SERVER_RATE_LIMIT")]
+    pub rate_limit: f64,
+
+    /// Maximum number of requests a client can send at once before being limited to the rate. A zero value uses the rate rounded up.
+    #[arg(long, default_value = "0", env = "SERVER_RATE_LIMIT_BURST")]
+    pub rate_limit_burst: u32,
+
+    /// Request header identifying the clients for the rate limits (e.g. "x-api-key"). The client IP address is used if empty or if a request lacks the header.
+    #[arg(long, default_value = "", env = "SERVER_RATE_LIMIT_KEY")]
+    pub rate_limit_key: String,
+
+    /// Maximum number of bytes per second sent to a connection by the file responses. A zero value disables it. Path-scoped limits can be defined in the configuration file.
+    #[arg(long, default_value = "0", env = "SERVER_BANDWIDTH_LIMIT")]
+    pub bandwidth_limit: u64,
+
+    /// Maximum number of bytes per second sent by the file responses of all the connections together. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_BANDWIDTH_LIMIT_GLOBAL")]
+    pub bandwidth_limit_global: u64,
+
+    /// Number of bytes of every file response sent at full speed before the bandwidth limits apply.
+    #[arg(long, default_value = "0", env = "SERVER_BANDWIDTH_LIMIT_AFTER")]
+    pub bandwidth_limit_after: u64,
+
+    /// Maximum number of concurrent connections. Further connections are closed right away until others end. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_MAX_CONNECTIONS")]
+    pub max_connections: usize,
+
+    /// Maximum number of concurrent connections of a client IP address. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_MAX_CONNECTIONS_PER_IP")]
+    pub max_connections_per_ip: usize,
+
+    /// Maximum number of requests handled concurrently. Further requests get "503 Service Unavailable" responses. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_MAX_INFLIGHT_REQUESTS")]
+    pub max_inflight_requests: usize,
+
+    /// Maximum time in milliseconds a task waits for a thread of the blocking thread pool (see "--max-blocking-threads"). Requests get "503 Service Unavailable" responses while the pool is slower than that. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_MAX_BLOCKING_DELAY")]
+    pub max_blocking_delay: u64,
+
+    /// Value in seconds of the "Retry-After" header of the "503 Service Unavailable" responses sent when shedding load.
+    #[arg(long, default_value = "1", env = "SERVER_LOAD_SHED_RETRY_AFTER")]
+    pub load_shed_retry_after: u64,
+
+    /// Maximum time in seconds to receive the headers of a HTTP/1 request. Slower clients get their connection closed. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_HEADER_READ_TIMEOUT")]
+    pub header_read_timeout: u64,
+
+    #[arg(
+        long,
+        default_value = "true",
+        default_missing_value("true"),
+        num_args(0..=1),
+        require_equals(true),
+        action = clap::ArgAction::Set,
+        env = "SERVER_KEEP_ALIVE",
+    )]
+    /// Keep connections open to serve further requests. If disabled, HTTP/1 connections are closed after every response and HTTP/2 connections are not pinged to be kept alive.
+    pub keep_alive: bool,
+
+    /// Maximum time in seconds a connection stays open between requests, like an idle keep-alive connection. HTTP/2 connections kept alive are also pinged at this interval. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_KEEP_ALIVE_TIMEOUT")]
+    pub keep_alive_timeout: u64,
+
+    /// Maximum time in seconds to send a whole response, including its body. Responses still being sent get their connection closed. A zero value disables it.
+    #[arg(long, default_value = "0", env = "SERVER_RESPONSE_WRITE_TIMEOUT")]
+    pub response_write_timeout: u64,
+
+    /// Maximum length in bytes of the request URI, path and query string together. Longer requests get a `414 URI Too Long` response. A zero value disables it.
+    #[arg(long, default_value = "8192", env = "SERVER_MAX_URI_LENGTH")]
+    pub max_uri_length: usize,
+
+    /// Maximum size in bytes of the request headers, names and values together. Larger requests get a `431 Request Header Fields Too Large` response. A zero value disables it.
+    #[arg(long, default_value = "32768", env = "SERVER_MAX_HEADER_BYTES")]
+    pub max_header_bytes: usize,
+
+    /// Maximum number of request headers. Requests with more headers get a `431 Request Header Fields Too Large` response. A zero value disables it.
+    #[arg(long, default_value = "100", env = "SERVER_MAX_HEADERS")]
+    pub max_headers: usize,
+
     #[arg(long, short = 'q', default_value = "0", env = "SERVER_GRACE_PERIOD")]
     /// Defines a grace period in seconds after a `SIGTERM` signal is caught which will delay the server before to shut it down gracefully. The maximum value is 255 seconds.
     pub grace_period: u8,
@@ -395,24 +782,63 @@ pub struct General {
     /// Tell the web server to run in a Windows Service context. Note that the `install` subcommand will enable this option automatically.
     pub windows_service: bool,
 
-    // Windows commands
-    #[cfg(windows)]
+    // Subcommands
+    #[cfg(any(windows, feature = "compression", feature = "signed-urls"))]
     #[command(subcommand)]
-    /// Subcommands to install or uninstall the SWS Windows Service.
+    /// Subcommands to install or uninstall the SWS Windows Service, to pre-compress files or to sign URLs.
     pub commands: Option<Commands>,
 }
 
-#[cfg(windows)]
+#[cfg(any(windows, feature = "compression", feature = "signed-urls"))]
 #[derive(Debug, clap::Subcommand)]
-/// Subcommands to install or uninstall the SWS Windows Service.
+/// Subcommands to install or uninstall the SWS Windows Service, to pre-compress files or to sign URLs.
 pub enum Commands {
     /// Install a Windows Service for the web server.
+    #[cfg(windows)]
     #[command(name = "install")]
     Install {},
 
     /// Uninstall the current Windows Service.
+    #[cfg(windows)]
     #[command(name = "uninstall")]
     Uninstall {},
+
+    /// Generate the pre-compressed file variants (`.gz`, `.br` or `.zst`) of every compressible file of a directory.
+    /// Outdated variants are only rewritten when their source file is newer and
+    /// files where compression does not reduce the size are skipped.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[command(name = "precompress")]
+    Precompress {
+        /// Directory path of the files to pre-compress. It defaults to the server root directory.
+        dir: Option<PathBuf>,
+
+        #[arg(long, short = 'e', default_value = crate::precompress::DEFAULT_ENCODINGS)]
+        /// List of encodings separated by commas to generate the file variants for. Values: gzip, br or zstd.
+        encodings: String,
+    },
+
+    /// Print a signed URL of a path which expires after the given number of seconds.
+    /// It uses the key of the "--signed-urls-key" or "--signed-urls-key-file" options.
+    #[cfg(feature = "signed-urls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+    #[command(name = "sign-url")]
+    SignUrl {
+        /// Absolute URI path to sign. E.g. "/downloads/report.pdf".
+        path: String,
+
+        #[arg(long, short = 'e', default_value = "3600")]
+        /// Number of seconds the URL is valid for.
+        expires_in: u64,
+
+        #[arg(long)]
+        /// Client IP address the URL is bound to.
+        ip: Option<std::net::IpAddr>,
+
+        #[arg(long, short = 'b', default_value = "")]
+        /// Base URL prepended to the signed path. E.g. "https://example.com".
+        base_url: String,
+    },
 }
 
 #[cfg(feature = "fallback-page")]
@@ -9,12 +9,15 @@ use headers::HeaderMap;
 use serde::Deserialize;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::path::Path;
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
 
 #[cfg(feature = "directory-listing")]
 use crate::directory_listing::DirListFmt;
 
-use crate::{helpers, Context, Result};
+use crate::{helpers, security_headers::Preset, Context, Result};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -88,6 +91,198 @@ pub struct Rewrites {
     pub destination: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+/// Represents security headers values. An empty value removes the corresponding header.
+pub struct SecurityHeadersValues {
+    /// `Content-Security-Policy` header value.
+    pub content_security_policy: Option<String>,
+    /// `Permissions-Policy` header value.
+    pub permissions_policy: Option<String>,
+    /// `Referrer-Policy` header value.
+    pub referrer_policy: Option<String>,
+    /// `Cross-Origin-Opener-Policy` header value.
+    pub cross_origin_opener_policy: Option<String>,
+    /// `Cross-Origin-Embedder-Policy` header value.
+    pub cross_origin_embedder_policy: Option<String>,
+    /// `Cross-Origin-Resource-Policy` header value.
+    pub cross_origin_resource_policy: Option<String>,
+    /// `X-Frame-Options` header value.
+    pub x_frame_options: Option<String>,
+    /// `X-Content-Type-Options` header value.
+    pub x_content_type_options: Option<String>,
+    /// `Strict-Transport-Security` max-age in seconds. Zero disables the header.
+    pub hsts_max_age: Option<u64>,
+    /// `Strict-Transport-Security` includeSubDomains directive.
+    pub hsts_include_subdomains: Option<bool>,
+    /// `Strict-Transport-Security` preload directive.
+    pub hsts_preload: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents per-path security headers overrides.
+pub struct SecurityHeadersOverrides {
+    /// Source of the override.
+    pub source: String,
+    /// Optional preset used as base instead of the global security headers.
+    pub preset: Option<Preset>,
+    /// Security headers values.
+    #[serde(flatten)]
+    pub values: SecurityHeadersValues,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents security headers options.
+pub struct SecurityHeaders {
+    /// Security headers values replacing the ones of the preset.
+    #[serde(flatten)]
+    pub values: SecurityHeadersValues,
+    /// Per-path overrides.
+    pub overrides: Option<Vec<SecurityHeadersOverrides>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents `Cache-Control` rules.
+pub struct CacheControl {
+    /// Source glob pattern of the request path.
+    pub source: Option<String>,
+    /// List of file extensions.
+    pub extensions: Option<Vec<String>>,
+    /// List of MIME types. E.g `text/html` or `image/*`.
+    pub mime_types: Option<Vec<String>>,
+    /// The `max-age` directive in seconds.
+    pub max_age: Option<u64>,
+    /// The `s-maxage` directive in seconds.
+    pub s_maxage: Option<u64>,
+    /// The `stale-while-revalidate` directive in seconds.
+    pub stale_while_revalidate: Option<u64>,
+    /// The `stale-if-error` directive in seconds.
+    pub stale_if_error: Option<u64>,
+    /// The `no-cache` directive.
+    pub no_cache: Option<bool>,
+    /// The `no-store` directive.
+    pub no_store: Option<bool>,
+    /// The `must-revalidate` directive.
+    pub must_revalidate: Option<bool>,
+    /// The `private` directive, otherwise `public` is used.
+    pub private: Option<bool>,
+    /// The `immutable` directive.
+    pub immutable: Option<bool>,
+    /// Append a matching `Expires` header.
+    pub expires: Option<bool>,
+}
+
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents compression dictionaries.
+pub struct CompressionDictionaries {
+    /// Source of the resources usable as dictionaries.
+    pub source: String,
+    /// URL pattern of the requests the dictionary applies to.
+    #[serde(rename = "match")]
+    pub match_pattern: String,
+    /// Optional dictionary identifier.
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+/// Represents authentication requirements.
+pub enum AuthKind {
+    /// No authentication.
+    None,
+    /// `Basic` HTTP Authentication using the server users.
+    Basic,
+    /// `Bearer` token authentication.
+    Bearer,
+    /// `Bearer` JSON Web Token authentication using the server public keys.
+    Jwt,
+    /// Expiring URLs signed with the server key.
+    SignedUrl,
+    /// TLS client certificates verified against the server client CA.
+    ClientCert,
+    /// Delegation to the server authorization service.
+    Forward,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents path-scoped authentication rules.
+pub struct Auth {
+    /// Source of the rule.
+    pub source: String,
+    /// Authentication requirement.
+    pub kind: AuthKind,
+    /// Users allowed by the `basic` requirement. Any known user if empty.
+    pub users: Option<Vec<String>>,
+    /// Tokens accepted by the `bearer` requirement.
+    pub tokens: Option<Vec<String>>,
+    /// Claims required by the `jwt` requirement.
+    pub claims: Option<BTreeMap<String, String>>,
+    /// Client certificate identities required by the `client-cert` requirement, all of them must match.
+    /// Any verified certificate if empty.
+    pub identities: Option<Vec<String>>,
+    /// Authentication realm.
+    pub realm: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents path-scoped client IP filter rules.
+pub struct IpFilterRule {
+    /// Source of the rule.
+    pub source: String,
+    /// Allowed client IP addresses or CIDR ranges.
+    pub allow: Option<Vec<String>>,
+    /// Denied client IP addresses or CIDR ranges.
+    pub deny: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents path-scoped request rate limit rules.
+pub struct RateLimitRule {
+    /// Source of the rule.
+    pub source: String,
+    /// Maximum number of requests per second of a client. Zero disables the limit.
+    pub rate: f64,
+    /// Maximum number of requests of a client at once.
+    pub burst: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Represents path-scoped bandwidth limit rules.
+pub struct BandwidthRule {
+    /// Source of the rule.
+    pub source: String,
+    /// Maximum number of bytes per second of every response. Zero disables the limit.
+    pub rate: u64,
+    /// Bytes of every response sent without limits.
+    pub after: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+/// Represents TLS certificates selected by the server name (SNI) of the clients.
+pub struct TlsCertificate {
+    /// Server names of the certificate. The DNS names of the certificate if omitted.
+    pub hosts: Option<Vec<String>>,
+    /// Certificate chain file path.
+    pub cert: PathBuf,
+    /// Private key file path.
+    pub key: PathBuf,
+    /// OCSP response file path.
+    pub ocsp_response: Option<PathBuf>,
+}
+
 /// Advanced server options only available in configuration file mode.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -98,6 +293,26 @@ pub struct Advanced {
     pub rewrites: Option<Vec<Rewrites>>,
     /// Redirects
     pub redirects: Option<Vec<Redirects>>,
+    /// Cache-Control rules
+    pub cache_control: Option<Vec<CacheControl>>,
+    /// Security headers
+    pub security_headers: Option<SecurityHeaders>,
+    /// Authentication rules
+    pub auth: Option<Vec<Auth>>,
+    /// Client IP filter rules
+    pub ip_filter: Option<Vec<IpFilterRule>>,
+    /// Request rate limit rules
+    pub rate_limit: Option<Vec<RateLimitRule>>,
+    /// Bandwidth limit rules
+    pub bandwidth_limit: Option<Vec<BandwidthRule>>,
+    /// Compression dictionaries
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_dictionaries: Option<Vec<CompressionDictionaries>>,
+    /// TLS certificates selected by server name
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_certificates: Option<Vec<TlsCertificate>>,
 }
 
 /// General server options available in configuration file mode.
@@ -118,6 +333,12 @@ pub struct General {
     /// Cache Control headers.
     pub cache_control_headers: Option<bool>,
 
+    /// Cache Control for fingerprinted file names.
+    pub cache_control_fingerprint: Option<bool>,
+
+    /// Fingerprinted file names pattern.
+    pub cache_control_fingerprint_pattern: Option<String>,
+
     /// Compression.
     #[cfg(feature = "compression")]
     #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
@@ -128,6 +349,21 @@ pub struct General {
     #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
     pub compression_static: Option<bool>,
 
+    /// Compression cache feature.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_cache: Option<bool>,
+
+    /// Compression cache maximum in-memory size.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_cache_max_size: Option<u64>,
+
+    /// Compression cache directory.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_cache_dir: Option<PathBuf>,
+
     /// Error 404 pages.
     pub page404: Option<PathBuf>,
     /// Error 50x pages.
@@ -145,6 +381,34 @@ pub struct General {
     #[cfg(feature = "http2")]
     #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
     pub http2_tls_key: Option<PathBuf>,
+    /// Http2 tls client CA certificates feature (mutual TLS).
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2_tls_client_ca: Option<PathBuf>,
+    /// Accept TLS clients without a certificate.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2_tls_client_auth_optional: Option<bool>,
+
+    /// TLS handshake timeout.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2_tls_handshake_timeout: Option<u64>,
+
+    /// TLS certificate files check interval.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2_tls_reload_interval: Option<u64>,
+
+    /// TLS OCSP response file stapled to the handshakes.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2_tls_ocsp_response: Option<PathBuf>,
+
+    /// TLS OCSP response refresh interval.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub http2_tls_ocsp_refresh_interval: Option<u64>,
 
     /// Redirect all HTTP requests to HTTPS.
     #[cfg(feature = "http2")]
@@ -166,6 +430,41 @@ pub struct General {
     /// Security headers.
     pub security_headers: Option<bool>,
 
+    /// Security headers preset.
+    pub security_headers_preset: Option<Preset>,
+
+    /// CSP nonce feature.
+    #[cfg(feature = "csp-nonce")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "csp-nonce")))]
+    pub csp_nonce: Option<bool>,
+
+    /// CSP nonce placeholder.
+    #[cfg(feature = "csp-nonce")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "csp-nonce")))]
+    pub csp_nonce_placeholder: Option<String>,
+
+    /// CSP nonce attribute for script and style tags.
+    #[cfg(feature = "csp-nonce")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "csp-nonce")))]
+    pub csp_nonce_tags: Option<bool>,
+
+    /// CSP nonce policy.
+    #[cfg(feature = "csp-nonce")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "csp-nonce")))]
+    pub csp_nonce_policy: Option<String>,
+
+    /// CSP violation reports endpoint.
+    pub csp_reports_endpoint: Option<String>,
+
+    /// CSP violation reports file.
+    pub csp_reports_file: Option<PathBuf>,
+
+    /// CSP violation reports maximum body size.
+    pub csp_reports_max_size: Option<u64>,
+
+    /// CSP violation reports rate limit.
+    pub csp_reports_rate_limit: Option<u32>,
+
     /// Cors allow origins feature.
     pub cors_allow_origins: Option<String>,
     /// Cors allow headers feature.
@@ -186,11 +485,183 @@ pub struct General {
     #[cfg_attr(docsrs, doc(cfg(feature = "directory-listing")))]
     pub directory_listing_format: Option<DirListFmt>,
 
+    /// Allowed client IP addresses or CIDR ranges.
+    pub ip_allow: Option<String>,
+
+    /// Denied client IP addresses or CIDR ranges.
+    pub ip_deny: Option<String>,
+
+    /// Trusted proxy IP addresses or CIDR ranges.
+    pub trusted_proxies: Option<String>,
+
     /// Basic Authentication feature.
     #[cfg(feature = "basic-auth")]
     #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
     pub basic_auth: Option<String>,
 
+    /// Basic Authentication htpasswd file.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_file: Option<PathBuf>,
+
+    /// Basic Authentication verified credentials cache lifetime.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_cache_ttl: Option<u64>,
+
+    /// Basic Authentication maximum concurrent password verifications.
+    #[cfg(feature = "basic-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "basic-auth")))]
+    pub basic_auth_max_verifications: Option<usize>,
+
+    /// JWT authentication public keys file.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_keys_file: Option<PathBuf>,
+
+    /// JWT authentication expected issuer.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_issuer: Option<String>,
+
+    /// JWT authentication expected audience.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_audience: Option<String>,
+
+    /// JWT authentication cookie name.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_cookie: Option<String>,
+
+    /// JWT authentication clock skew leeway.
+    #[cfg(feature = "jwt-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "jwt-auth")))]
+    pub jwt_leeway: Option<u64>,
+
+    /// Signed URLs key.
+    #[cfg(feature = "signed-urls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+    pub signed_urls_key: Option<String>,
+
+    /// Signed URLs key file.
+    #[cfg(feature = "signed-urls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+    pub signed_urls_key_file: Option<PathBuf>,
+
+    /// Require signed URLs globally.
+    #[cfg(feature = "signed-urls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed-urls")))]
+    pub signed_urls_required: Option<bool>,
+
+    /// Form login page path.
+    #[cfg(feature = "form-login")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+    pub login_path: Option<String>,
+
+    /// Form logout endpoint path.
+    #[cfg(feature = "form-login")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+    pub logout_path: Option<String>,
+
+    /// Custom form login page file.
+    #[cfg(feature = "form-login")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+    pub login_page: Option<PathBuf>,
+
+    /// Form login session cookies key.
+    #[cfg(feature = "form-login")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+    pub login_session_key: Option<String>,
+
+    /// Form login sessions lifetime.
+    #[cfg(feature = "form-login")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "form-login")))]
+    pub login_session_ttl: Option<u64>,
+
+    /// Authorization service URL.
+    #[cfg(feature = "forward-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "forward-auth")))]
+    pub forward_auth_url: Option<String>,
+
+    /// Request headers sent to the authorization service.
+    #[cfg(feature = "forward-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "forward-auth")))]
+    pub forward_auth_request_headers: Option<String>,
+
+    /// Authorization service response headers added to the logs.
+    #[cfg(feature = "forward-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "forward-auth")))]
+    pub forward_auth_response_headers: Option<String>,
+
+    /// Authorization service requests timeout.
+    #[cfg(feature = "forward-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "forward-auth")))]
+    pub forward_auth_timeout: Option<u64>,
+
+    /// Authentication failures of a client before a ban.
+    pub auth_max_failures: Option<u32>,
+
+    /// Duration of the first authentication failures ban.
+    pub auth_ban_duration: Option<u64>,
+
+    /// Maximum duration of the authentication failures bans.
+    pub auth_max_ban_duration: Option<u64>,
+
+    /// Request rate limit per client.
+    pub rate_limit: Option<f64>,
+
+    /// Request rate limit burst size.
+    pub rate_limit_burst: Option<u32>,
+
+    /// Request header identifying the rate limited clients.
+    pub rate_limit_key: Option<String>,
+
+    /// Bandwidth limit per connection.
+    pub bandwidth_limit: Option<u64>,
+
+    /// Bandwidth limit shared by all the connections.
+    pub bandwidth_limit_global: Option<u64>,
+
+    /// Bytes of every file response sent without bandwidth limits.
+    pub bandwidth_limit_after: Option<u64>,
+
+    /// Maximum number of concurrent connections.
+    pub max_connections: Option<usize>,
+
+    /// Maximum number of concurrent connections per client IP address.
+    pub max_connections_per_ip: Option<usize>,
+
+    /// Maximum number of requests in flight before shedding load.
+    pub max_inflight_requests: Option<usize>,
+
+    /// Maximum queueing delay of the blocking thread pool before shedding load.
+    pub max_blocking_delay: Option<u64>,
+
+    /// Retry-After value of the load shedding responses.
+    pub load_shed_retry_after: Option<u64>,
+
+    /// HTTP/1 request header read timeout.
+    pub header_read_timeout: Option<u64>,
+
+    /// HTTP/1 and HTTP/2 keep-alive connections.
+    pub keep_alive: Option<bool>,
+
+    /// Timeout of the connections idle between requests.
+    pub keep_alive_timeout: Option<u64>,
+
+    /// Total response write timeout.
+    pub response_write_timeout: Option<u64>,
+
+    /// Maximum request URI length.
+    pub max_uri_length: Option<usize>,
+
+    /// Maximum request headers size.
+    pub max_header_bytes: Option<usize>,
+
+    /// Maximum number of request headers.
+    pub max_headers: Option<usize>,
+
     /// File descriptor binding feature.
     pub fd: Option<usize>,
 
@@ -11,12 +11,17 @@ use globset::{Glob, GlobMatcher};
 use headers::HeaderMap;
 use hyper::StatusCode;
 
-use crate::{Context, Result};
+use crate::{
+    ip_filter::{IpFilter, IpNet},
+    rate_limit::RateLimiter,
+    security_headers::Preset,
+    Context, Result,
+};
 
 pub mod cli;
 pub mod file;
 
-#[cfg(windows)]
+#[cfg(any(windows, feature = "compression", feature = "signed-urls"))]
 pub use cli::Commands;
 
 use cli::General;
@@ -47,6 +52,107 @@ pub struct Redirects {
     pub kind: StatusCode,
 }
 
+/// The `Cache-Control` rules file options.
+pub struct CacheControl {
+    /// Optional source pattern glob matcher
+    pub source: Option<GlobMatcher>,
+    /// List of lowercase file extensions
+    pub extensions: Vec<String>,
+    /// List of lowercase MIME types. E.g `text/html` or `image/*`
+    pub mime_types: Vec<String>,
+    /// The `Cache-Control` header value
+    pub value: headers::HeaderValue,
+    /// Lifetime in seconds used for the `Expires` header if enabled
+    pub expires: Option<u64>,
+}
+
+/// The `security headers` per-path overrides file options.
+pub struct SecurityHeadersOverrides {
+    /// Source pattern glob matcher
+    pub source: GlobMatcher,
+    /// Optional preset used as base instead of the global security headers
+    pub preset: Option<Preset>,
+    /// Security headers values
+    pub values: file::SecurityHeadersValues,
+}
+
+/// The `security headers` file options.
+pub struct SecurityHeaders {
+    /// Security headers values replacing the ones of the preset
+    pub values: file::SecurityHeadersValues,
+    /// Per-path overrides list
+    pub overrides: Vec<SecurityHeadersOverrides>,
+}
+
+/// The `auth` rules file options.
+pub struct Auth {
+    /// Source pattern glob matcher
+    pub source: GlobMatcher,
+    /// Authentication requirement
+    pub kind: file::AuthKind,
+    /// Users allowed by the `basic` requirement. Any known user if empty
+    pub users: Vec<String>,
+    /// Tokens accepted by the `bearer` requirement
+    pub tokens: Vec<String>,
+    /// Claims required by the `jwt` requirement as `(name, value)` pairs
+    pub claims: Vec<(String, String)>,
+    /// Client certificate identities required by the `client-cert` requirement, all of them must match. Any verified certificate if empty
+    pub identities: Vec<String>,
+    /// Authentication realm
+    pub realm: String,
+}
+
+/// The `ip-filter` rules file options.
+pub struct IpFilterRule {
+    /// Source pattern glob matcher
+    pub source: GlobMatcher,
+    /// Allow and deny lists of the client IP addresses
+    pub filter: IpFilter,
+}
+
+/// The `rate-limit` rules file options.
+pub struct RateLimitRule {
+    /// Source pattern glob matcher
+    pub source: GlobMatcher,
+    /// Token buckets of the clients or `None` if the limit is disabled
+    pub limiter: Option<RateLimiter>,
+}
+
+/// The `bandwidth-limit` rules file options.
+pub struct BandwidthRule {
+    /// Source pattern glob matcher
+    pub source: GlobMatcher,
+    /// Bytes per second of every response or `None` if the limit is disabled
+    pub rate: Option<u64>,
+    /// Bytes of every response sent without limits
+    pub after: Option<u64>,
+}
+
+/// The `tls-certificates` file options.
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub struct TlsCertificate {
+    /// Server names of the certificate or empty to use its DNS names
+    pub hosts: Vec<String>,
+    /// Certificate chain file path
+    pub cert: std::path::PathBuf,
+    /// Private key file path
+    pub key: std::path::PathBuf,
+    /// OCSP response file path if any
+    pub ocsp_response: Option<std::path::PathBuf>,
+}
+
+/// The `compression dictionaries` file options.
+#[derive(Clone)]
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub struct CompressionDictionaries {
+    /// Source pattern glob matcher of the resources usable as dictionaries
+    pub source: GlobMatcher,
+    /// The `Use-As-Dictionary` header value sent along with the dictionary resources
+    pub use_as_dictionary: headers::HeaderValue,
+}
+
 /// The `advanced` file options.
 pub struct Advanced {
     /// Headers list.
@@ -55,6 +161,26 @@ pub struct Advanced {
     pub rewrites: Option<Vec<Rewrites>>,
     /// Redirects list.
     pub redirects: Option<Vec<Redirects>>,
+    /// Cache-Control rules list.
+    pub cache_control: Option<Vec<CacheControl>>,
+    /// Security headers options.
+    pub security_headers: Option<SecurityHeaders>,
+    /// Authentication rules list.
+    pub auth: Option<Vec<Auth>>,
+    /// Client IP filter rules list.
+    pub ip_filter: Option<Vec<IpFilterRule>>,
+    /// Request rate limit rules list.
+    pub rate_limit: Option<Vec<RateLimitRule>>,
+    /// Bandwidth limit rules list.
+    pub bandwidth_limit: Option<Vec<BandwidthRule>>,
+    /// Compression dictionaries list.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub compression_dictionaries: Option<Vec<CompressionDictionaries>>,
+    /// TLS certificates selected by server name list.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub tls_certificates: Option<Vec<TlsCertificate>>,
 }
 
 /// The full server CLI and File options.
@@ -77,11 +203,19 @@ impl Settings {
         let mut log_level = opts.log_level;
         let mut config_file = opts.config_file.clone();
         let mut cache_control_headers = opts.cache_control_headers;
+        let mut cache_control_fingerprint = opts.cache_control_fingerprint;
+        let mut cache_control_fingerprint_pattern = opts.cache_control_fingerprint_pattern;
 
         #[cfg(feature = "compression")]
         let mut compression = opts.compression;
         #[cfg(feature = "compression")]
         let mut compression_static = opts.compression_static;
+        #[cfg(feature = "compression")]
+        let mut compression_cache = opts.compression_cache;
+        #[cfg(feature = "compression")]
+        let mut compression_cache_max_size = opts.compression_cache_max_size;
+        #[cfg(feature = "compression")]
+        let mut compression_cache_dir = opts.compression_cache_dir;
 
         let mut page404 = opts.page404;
         let mut page50x = opts.page50x;
@@ -93,6 +227,18 @@ impl Settings {
         #[cfg(feature = "http2")]
         let mut http2_tls_key = opts.http2_tls_key;
         #[cfg(feature = "http2")]
+        let mut http2_tls_client_ca = opts.http2_tls_client_ca;
+        #[cfg(feature = "http2")]
+        let mut http2_tls_client_auth_optional = opts.http2_tls_client_auth_optional;
+        #[cfg(feature = "http2")]
+        let mut http2_tls_handshake_timeout = opts.http2_tls_handshake_timeout;
+        #[cfg(feature = "http2")]
+        let mut http2_tls_reload_interval = opts.http2_tls_reload_interval;
+        #[cfg(feature = "http2")]
+        let mut http2_tls_ocsp_response = opts.http2_tls_ocsp_response;
+        #[cfg(feature = "http2")]
+        let mut http2_tls_ocsp_refresh_interval = opts.http2_tls_ocsp_refresh_interval;
+        #[cfg(feature = "http2")]
         let mut https_redirect = opts.https_redirect;
         #[cfg(feature = "http2")]
         let mut https_redirect_host = opts.https_redirect_host;
@@ -102,9 +248,25 @@ impl Settings {
         let mut https_redirect_from_hosts = opts.https_redirect_from_hosts;
 
         let mut security_headers = opts.security_headers;
+        let mut security_headers_preset = opts.security_headers_preset;
+        #[cfg(feature = "csp-nonce")]
+        let mut csp_nonce = opts.csp_nonce;
+        #[cfg(feature = "csp-nonce")]
+        let mut csp_nonce_placeholder = opts.csp_nonce_placeholder;
+        #[cfg(feature = "csp-nonce")]
+        let mut csp_nonce_tags = opts.csp_nonce_tags;
+        #[cfg(feature = "csp-nonce")]
+        let mut csp_nonce_policy = opts.csp_nonce_policy;
+        let mut csp_reports_endpoint = opts.csp_reports_endpoint;
+        let mut csp_reports_file = opts.csp_reports_file;
+        let mut csp_reports_max_size = opts.csp_reports_max_size;
+        let mut csp_reports_rate_limit = opts.csp_reports_rate_limit;
         let mut cors_allow_origins = opts.cors_allow_origins;
         let mut cors_allow_headers = opts.cors_allow_headers;
         let mut cors_expose_headers = opts.cors_expose_headers;
+        let mut ip_allow = opts.ip_allow;
+        let mut ip_deny = opts.ip_deny;
+        let mut trusted_proxies = opts.trusted_proxies;
 
         #[cfg(feature = "directory-listing")]
         let mut directory_listing = opts.directory_listing;
@@ -115,6 +277,71 @@ impl Settings {
 
         #[cfg(feature = "basic-auth")]
         let mut basic_auth = opts.basic_auth;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_file = opts.basic_auth_file;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_cache_ttl = opts.basic_auth_cache_ttl;
+        #[cfg(feature = "basic-auth")]
+        let mut basic_auth_max_verifications = opts.basic_auth_max_verifications;
+
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_keys_file = opts.jwt_keys_file;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_issuer = opts.jwt_issuer;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_audience = opts.jwt_audience;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_cookie = opts.jwt_cookie;
+        #[cfg(feature = "jwt-auth")]
+        let mut jwt_leeway = opts.jwt_leeway;
+
+        #[cfg(feature = "signed-urls")]
+        let mut signed_urls_key = opts.signed_urls_key;
+        #[cfg(feature = "signed-urls")]
+        let mut signed_urls_key_file = opts.signed_urls_key_file;
+        #[cfg(feature = "signed-urls")]
+        let mut signed_urls_required = opts.signed_urls_required;
+
+        #[cfg(feature = "form-login")]
+        let mut login_path = opts.login_path;
+        #[cfg(feature = "form-login")]
+        let mut logout_path = opts.logout_path;
+        #[cfg(feature = "form-login")]
+        let mut login_page = opts.login_page;
+        #[cfg(feature = "form-login")]
+        let mut login_session_key = opts.login_session_key;
+        #[cfg(feature = "form-login")]
+        let mut login_session_ttl = opts.login_session_ttl;
+
+        #[cfg(feature = "forward-auth")]
+        let mut forward_auth_url = opts.forward_auth_url;
+        #[cfg(feature = "forward-auth")]
+        let mut forward_auth_request_headers = opts.forward_auth_request_headers;
+        #[cfg(feature = "forward-auth")]
+        let mut forward_auth_response_headers = opts.forward_auth_response_headers;
+        #[cfg(feature = "forward-auth")]
+        let mut forward_auth_timeout = opts.forward_auth_timeout;
+        let mut auth_max_failures = opts.auth_max_failures;
+        let mut auth_ban_duration = opts.auth_ban_duration;
+        let mut auth_max_ban_duration = opts.auth_max_ban_duration;
+        let mut rate_limit = opts.rate_limit;
+        let mut rate_limit_burst = opts.rate_limit_burst;
+        let mut rate_limit_key = opts.rate_limit_key;
+        let mut bandwidth_limit = opts.bandwidth_limit;
+        let mut bandwidth_limit_global = opts.bandwidth_limit_global;
+        let mut bandwidth_limit_after = opts.bandwidth_limit_after;
+        let mut max_connections = opts.max_connections;
+        let mut max_connections_per_ip = opts.max_connections_per_ip;
+        let mut max_inflight_requests = opts.max_inflight_requests;
+        let mut max_blocking_delay = opts.max_blocking_delay;
+        let mut load_shed_retry_after = opts.load_shed_retry_after;
+        let mut header_read_timeout = opts.header_read_timeout;
+        let mut keep_alive = opts.keep_alive;
+        let mut keep_alive_timeout = opts.keep_alive_timeout;
+        let mut response_write_timeout = opts.response_write_timeout;
+        let mut max_uri_length = opts.max_uri_length;
+        let mut max_header_bytes = opts.max_header_bytes;
+        let mut max_headers = opts.max_headers;
 
         let mut fd = opts.fd;
         let mut threads_multiplier = opts.threads_multiplier;
@@ -167,6 +394,12 @@ impl Settings {
                     if let Some(v) = general.cache_control_headers {
                         cache_control_headers = v
                     }
+                    if let Some(v) = general.cache_control_fingerprint {
+                        cache_control_fingerprint = v
+                    }
+                    if let Some(v) = general.cache_control_fingerprint_pattern {
+                        cache_control_fingerprint_pattern = v
+                    }
                     #[cfg(feature = "compression")]
                     if let Some(v) = general.compression {
                         compression = v
@@ -175,6 +408,18 @@ impl Settings {
                     if let Some(v) = general.compression_static {
                         compression_static = v
                     }
+                    #[cfg(feature = "compression")]
+                    if let Some(v) = general.compression_cache {
+                        compression_cache = v
+                    }
+                    #[cfg(feature = "compression")]
+                    if let Some(v) = general.compression_cache_max_size {
+                        compression_cache_max_size = v
+                    }
+                    #[cfg(feature = "compression")]
+                    if let Some(v) = general.compression_cache_dir {
+                        compression_cache_dir = Some(v)
+                    }
                     if let Some(v) = general.page404 {
                         page404 = v
                     }
@@ -194,6 +439,30 @@ impl Settings {
                         http2_tls_key = Some(v)
                     }
                     #[cfg(feature = "http2")]
+                    if let Some(v) = general.http2_tls_client_ca {
+                        http2_tls_client_ca = Some(v)
+                    }
+                    #[cfg(feature = "http2")]
+                    if let Some(v) = general.http2_tls_client_auth_optional {
+                        http2_tls_client_auth_optional = v
+                    }
+                    #[cfg(feature = "http2")]
+                    if let Some(v) = general.http2_tls_handshake_timeout {
+                        http2_tls_handshake_timeout = v
+                    }
+                    #[cfg(feature = "http2")]
+                    if let Some(v) = general.http2_tls_reload_interval {
+                        http2_tls_reload_interval = v
+                    }
+                    #[cfg(feature = "http2")]
+                    if let Some(v) = general.http2_tls_ocsp_response {
+                        http2_tls_ocsp_response = Some(v)
+                    }
+                    #[cfg(feature = "http2")]
+                    if let Some(v) = general.http2_tls_ocsp_refresh_interval {
+                        http2_tls_ocsp_refresh_interval = v
+                    }
+                    #[cfg(feature = "http2")]
                     if let Some(v) = general.https_redirect {
                         https_redirect = v
                     }
@@ -222,6 +491,37 @@ impl Settings {
                     if let Some(v) = general.security_headers {
                         security_headers = v
                     }
+                    if let Some(v) = general.security_headers_preset {
+                        security_headers_preset = v
+                    }
+                    #[cfg(feature = "csp-nonce")]
+                    if let Some(v) = general.csp_nonce {
+                        csp_nonce = v
+                    }
+                    #[cfg(feature = "csp-nonce")]
+                    if let Some(v) = general.csp_nonce_placeholder {
+                        csp_nonce_placeholder = v
+                    }
+                    #[cfg(feature = "csp-nonce")]
+                    if let Some(v) = general.csp_nonce_tags {
+                        csp_nonce_tags = v
+                    }
+                    #[cfg(feature = "csp-nonce")]
+                    if let Some(v) = general.csp_nonce_policy {
+                        csp_nonce_policy = v
+                    }
+                    if let Some(v) = general.csp_reports_endpoint {
+                        csp_reports_endpoint = v
+                    }
+                    if let Some(v) = general.csp_reports_file {
+                        csp_reports_file = Some(v)
+                    }
+                    if let Some(v) = general.csp_reports_max_size {
+                        csp_reports_max_size = v
+                    }
+                    if let Some(v) = general.csp_reports_rate_limit {
+                        csp_reports_rate_limit = v
+                    }
                     if let Some(ref v) = general.cors_allow_origins {
                         cors_allow_origins = v.to_owned()
                     }
@@ -231,6 +531,15 @@ impl Settings {
                     if let Some(ref v) = general.cors_expose_headers {
                         cors_expose_headers = v.to_owned()
                     }
+                    if let Some(ref v) = general.ip_allow {
+                        ip_allow = v.to_owned()
+                    }
+                    if let Some(ref v) = general.ip_deny {
+                        ip_deny = v.to_owned()
+                    }
+                    if let Some(ref v) = general.trusted_proxies {
+                        trusted_proxies = v.to_owned()
+                    }
                     #[cfg(feature = "directory-listing")]
                     if let Some(v) = general.directory_listing {
                         directory_listing = v
@@ -247,6 +556,149 @@ impl Settings {
                     if let Some(ref v) = general.basic_auth {
                         basic_auth = v.to_owned()
                     }
+                    #[cfg(feature = "basic-auth")]
+                    if let Some(v) = general.basic_auth_file {
+                        basic_auth_file = Some(v)
+                    }
+                    #[cfg(feature = "basic-auth")]
+                    if let Some(v) = general.basic_auth_cache_ttl {
+                        basic_auth_cache_ttl = v
+                    }
+                    #[cfg(feature = "basic-auth")]
+                    if let Some(v) = general.basic_auth_max_verifications {
+                        basic_auth_max_verifications = v
+                    }
+                    #[cfg(feature = "jwt-auth")]
+                    if let Some(v) = general.jwt_keys_file {
+                        jwt_keys_file = Some(v)
+                    }
+                    #[cfg(feature = "jwt-auth")]
+                    if let Some(v) = general.jwt_issuer {
+                        jwt_issuer = v
+                    }
+                    #[cfg(feature = "jwt-auth")]
+                    if let Some(v) = general.jwt_audience {
+                        jwt_audience = v
+                    }
+                    #[cfg(feature = "jwt-auth")]
+                    if let Some(v) = general.jwt_cookie {
+                        jwt_cookie = v
+                    }
+                    #[cfg(feature = "jwt-auth")]
+                    if let Some(v) = general.jwt_leeway {
+                        jwt_leeway = v
+                    }
+                    #[cfg(feature = "signed-urls")]
+                    if let Some(v) = general.signed_urls_key {
+                        signed_urls_key = v
+                    }
+                    #[cfg(feature = "signed-urls")]
+                    if let Some(v) = general.signed_urls_key_file {
+                        signed_urls_key_file = Some(v)
+                    }
+                    #[cfg(feature = "signed-urls")]
+                    if let Some(v) = general.signed_urls_required {
+                        signed_urls_required = v
+                    }
+                    #[cfg(feature = "form-login")]
+                    if let Some(v) = general.login_path {
+                        login_path = v
+                    }
+                    #[cfg(feature = "form-login")]
+                    if let Some(v) = general.logout_path {
+                        logout_path = v
+                    }
+                    #[cfg(feature = "form-login")]
+                    if let Some(v) = general.login_page {
+                        login_page = Some(v)
+                    }
+                    #[cfg(feature = "form-login")]
+                    if let Some(v) = general.login_session_key {
+                        login_session_key = v
+                    }
+                    #[cfg(feature = "form-login")]
+                    if let Some(v) = general.login_session_ttl {
+                        login_session_ttl = v
+                    }
+                    #[cfg(feature = "forward-auth")]
+                    if let Some(v) = general.forward_auth_url {
+                        forward_auth_url = v
+                    }
+                    #[cfg(feature = "forward-auth")]
+                    if let Some(v) = general.forward_auth_request_headers {
+                        forward_auth_request_headers = v
+                    }
+                    #[cfg(feature = "forward-auth")]
+                    if let Some(v) = general.forward_auth_response_headers {
+                        forward_auth_response_headers = v
+                    }
+                    #[cfg(feature = "forward-auth")]
+                    if let Some(v) = general.forward_auth_timeout {
+                        forward_auth_timeout = v
+                    }
+                    if let Some(v) = general.auth_max_failures {
+                        auth_max_failures = v
+                    }
+                    if let Some(v) = general.auth_ban_duration {
+                        auth_ban_duration = v
+                    }
+                    if let Some(v) = general.auth_max_ban_duration {
+                        auth_max_ban_duration = v
+                    }
+                    if let Some(v) = general.rate_limit {
+                        rate_limit = v
+                    }
+                    if let Some(v) = general.rate_limit_burst {
+                        rate_limit_burst = v
+                    }
+                    if let Some(v) = general.rate_limit_key {
+                        rate_limit_key = v
+                    }
+                    if let Some(v) = general.bandwidth_limit {
+                        bandwidth_limit = v
+                    }
+                    if let Some(v) = general.bandwidth_limit_global {
+                        bandwidth_limit_global = v
+                    }
+                    if let Some(v) = general.bandwidth_limit_after {
+                        bandwidth_limit_after = v
+                    }
+                    if let Some(v) = general.max_connections {
+                        max_connections = v
+                    }
+                    if let Some(v) = general.max_connections_per_ip {
+                        max_connections_per_ip = v
+                    }
+                    if let Some(v) = general.max_inflight_requests {
+                        max_inflight_requests = v
+                    }
+                    if let Some(v) = general.max_blocking_delay {
+                        max_blocking_delay = v
+                    }
+                    if let Some(v) = general.load_shed_retry_after {
+                        load_shed_retry_after = v
+                    }
+                    if let Some(v) = general.header_read_timeout {
+                        header_read_timeout = v
+                    }
+                    if let Some(v) = general.keep_alive {
+                        keep_alive = v
+                    }
+                    if let Some(v) = general.keep_alive_timeout {
+                        keep_alive_timeout = v
+                    }
+                    if let Some(v) = general.response_write_timeout {
+                        response_write_timeout = v
+                    }
+                    if let Some(v) = general.max_uri_length {
+                        max_uri_length = v
+                    }
+                    if let Some(v) = general.max_header_bytes {
+                        max_header_bytes = v
+                    }
+                    if let Some(v) = general.max_headers {
+                        max_headers = v
+                    }
                     if let Some(v) = general.fd {
                         fd = Some(v)
                     }
@@ -364,15 +816,329 @@ impl Settings {
                         _ => None,
                     };
 
+                    // 4. Cache-Control rules assignment
+                    let cache_control_entries = match advanced.cache_control {
+                        Some(cache_control_entries) => {
+                            let mut cache_control_vec: Vec<CacheControl> = Vec::new();
+
+                            for entry in cache_control_entries.iter() {
+                                cache_control_vec.push(cache_control_rule(entry)?);
+                            }
+                            Some(cache_control_vec)
+                        }
+                        _ => None,
+                    };
+
+                    // 5. Security headers assignment
+                    let security_headers_entries = match advanced.security_headers {
+                        Some(security_headers_entry) => {
+                            let mut overrides_vec: Vec<SecurityHeadersOverrides> = Vec::new();
+
+                            // Compile a glob pattern for each override sources entry
+                            for overrides_entry in security_headers_entry.overrides.iter().flatten()
+                            {
+                                let source = Glob::new(&overrides_entry.source)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for security headers source: {}",
+                                            &overrides_entry.source
+                                        )
+                                    })?
+                                    .compile_matcher();
+
+                                overrides_vec.push(SecurityHeadersOverrides {
+                                    source,
+                                    preset: overrides_entry.preset,
+                                    values: overrides_entry.values.to_owned(),
+                                });
+                            }
+                            Some(SecurityHeaders {
+                                values: security_headers_entry.values,
+                                overrides: overrides_vec,
+                            })
+                        }
+                        _ => None,
+                    };
+
+                    // 6. Authentication rules assignment
+                    let auth_entries = match advanced.auth {
+                        Some(auth_entries) => {
+                            let mut auth_vec: Vec<Auth> = Vec::new();
+
+                            // Compile a glob pattern for each auth sources entry
+                            for auth_entry in auth_entries.iter() {
+                                let source = Glob::new(&auth_entry.source)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for auth source: {}",
+                                            &auth_entry.source
+                                        )
+                                    })?
+                                    .compile_matcher();
+
+                                let tokens = auth_entry.tokens.to_owned().unwrap_or_default();
+                                if auth_entry.kind == file::AuthKind::Bearer && tokens.is_empty() {
+                                    bail!(
+                                        "bearer auth rule requires at least one token: {}",
+                                        &auth_entry.source
+                                    );
+                                }
+
+                                let realm = auth_entry
+                                    .realm
+                                    .as_deref()
+                                    .unwrap_or(crate::auth::DEFAULT_REALM);
+                                if realm.contains(|c: char| c == '"' || c == '\\' || c.is_control())
+                                {
+                                    bail!("invalid auth realm: {}", realm);
+                                }
+
+                                auth_vec.push(Auth {
+                                    source,
+                                    kind: auth_entry.kind,
+                                    users: auth_entry.users.to_owned().unwrap_or_default(),
+                                    tokens,
+                                    claims: auth_entry
+                                        .claims
+                                        .to_owned()
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .collect(),
+                                    identities: auth_entry
+                                        .identities
+                                        .to_owned()
+                                        .unwrap_or_default(),
+                                    realm: realm.to_owned(),
+                                });
+                            }
+                            Some(auth_vec)
+                        }
+                        _ => None,
+                    };
+
+                    // 7. Compression dictionaries assignment
+                    #[cfg(feature = "compression")]
+                    let compression_dictionaries_entries = match advanced.compression_dictionaries {
+                        Some(dictionaries_entries) => {
+                            let mut dictionaries_vec: Vec<CompressionDictionaries> = Vec::new();
+
+                            // Compile a glob pattern for each dictionary sources entry
+                            for dictionaries_entry in dictionaries_entries.iter() {
+                                let source = Glob::new(&dictionaries_entry.source)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for compression dictionary source: {}",
+                                            &dictionaries_entry.source
+                                        )
+                                    })?
+                                    .compile_matcher();
+
+                                // Build the `Use-As-Dictionary` structured field value
+                                let mut value = format!(
+                                    "match={}",
+                                    sf_string(&dictionaries_entry.match_pattern)
+                                );
+                                if let Some(id) = &dictionaries_entry.id {
+                                    value.push_str(", id=");
+                                    value.push_str(&sf_string(id));
+                                }
+                                let use_as_dictionary = headers::HeaderValue::from_str(&value)
+                                    .with_context(|| {
+                                        format!(
+                                            "invalid compression dictionary match or id: {value}"
+                                        )
+                                    })?;
+
+                                dictionaries_vec.push(CompressionDictionaries {
+                                    source,
+                                    use_as_dictionary,
+                                });
+                            }
+                            Some(dictionaries_vec)
+                        }
+                        _ => None,
+                    };
+
+                    // 8. Client IP filter rules assignment
+                    let ip_filter_entries = match advanced.ip_filter {
+                        Some(ip_filter_entries) => {
+                            let mut ip_filter_vec: Vec<IpFilterRule> = Vec::new();
+
+                            // Compile a glob pattern for each ip filter sources entry
+                            for ip_filter_entry in ip_filter_entries.iter() {
+                                let source = Glob::new(&ip_filter_entry.source)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for ip filter source: {}",
+                                            &ip_filter_entry.source
+                                        )
+                                    })?
+                                    .compile_matcher();
+
+                                let parse = |list: &Option<Vec<String>>| -> Result<Vec<IpNet>> {
+                                    list.iter().flatten().map(|v| v.parse::<IpNet>()).collect()
+                                };
+                                let filter = IpFilter::new(
+                                    parse(&ip_filter_entry.allow)?,
+                                    parse(&ip_filter_entry.deny)?,
+                                );
+                                if filter.is_empty() {
+                                    bail!(
+                                        "ip filter rule requires at least one `allow` or `deny` entry: {}",
+                                        &ip_filter_entry.source
+                                    );
+                                }
+
+                                ip_filter_vec.push(IpFilterRule { source, filter });
+                            }
+                            Some(ip_filter_vec)
+                        }
+                        _ => None,
+                    };
+
+                    // 9. Request rate limit rules assignment
+                    let rate_limit_entries = match advanced.rate_limit {
+                        Some(rate_limit_entries) => {
+                            let mut rate_limit_vec: Vec<RateLimitRule> = Vec::new();
+
+                            // Compile a glob pattern for each rate limit sources entry
+                            for rate_limit_entry in rate_limit_entries.iter() {
+                                let source = Glob::new(&rate_limit_entry.source)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for rate limit source: {}",
+                                            &rate_limit_entry.source
+                                        )
+                                    })?
+                                    .compile_matcher();
+
+                                let limiter =
+                                    if rate_limit_entry.rate == 0.0 {
+                                        None
+                                    } else {
+                                        Some(
+                                            RateLimiter::new(
+                                                rate_limit_entry.rate,
+                                                rate_limit_entry.burst.unwrap_or_default(),
+                                            )
+                                            .with_context(|| {
+                                                format!(
+                                                    "invalid rate limit rule: {}",
+                                                    &rate_limit_entry.source
+                                                )
+                                            })?,
+                                        )
+                                    };
+
+                                rate_limit_vec.push(RateLimitRule { source, limiter });
+                            }
+                            Some(rate_limit_vec)
+                        }
+                        _ => None,
+                    };
+
+                    // 10. Bandwidth limit rules assignment
+                    let bandwidth_limit_entries = match advanced.bandwidth_limit {
+                        Some(bandwidth_entries) => {
+                            let mut bandwidth_vec: Vec<BandwidthRule> = Vec::new();
+
+                            // Compile a glob pattern for each bandwidth limit sources entry
+                            for bandwidth_entry in bandwidth_entries.iter() {
+                                let source = Glob::new(&bandwidth_entry.source)
+                                    .with_context(|| {
+                                        format!(
+                                            "can not compile glob pattern for bandwidth limit source: {}",
+                                            &bandwidth_entry.source
+                                        )
+                                    })?
+                                    .compile_matcher();
+
+                                bandwidth_vec.push(BandwidthRule {
+                                    source,
+                                    rate: Some(bandwidth_entry.rate).filter(|rate| *rate > 0),
+                                    after: bandwidth_entry.after,
+                                });
+                            }
+                            Some(bandwidth_vec)
+                        }
+                        _ => None,
+                    };
+
+                    // 11. TLS certificates assignment
+                    #[cfg(feature = "http2")]
+                    let tls_certificates_entries = match advanced.tls_certificates {
+                        Some(certificates_entries) => {
+                            let mut certificates_vec: Vec<TlsCertificate> = Vec::new();
+
+                            // Check the server names of each certificate entry
+                            for certificate_entry in certificates_entries {
+                                let hosts = certificate_entry.hosts.unwrap_or_default();
+                                for host in &hosts {
+                                    let name = host.strip_prefix("*.").unwrap_or(host);
+                                    if name.is_empty() || name.contains('*') {
+                                        bail!(
+                                            "invalid tls certificate host: {}, only `*.` prefixed wildcards are supported",
+                                            host
+                                        );
+                                    }
+                                }
+
+                                certificates_vec.push(TlsCertificate {
+                                    hosts,
+                                    cert: certificate_entry.cert,
+                                    key: certificate_entry.key,
+                                    ocsp_response: certificate_entry.ocsp_response,
+                                });
+                            }
+                            Some(certificates_vec)
+                        }
+                        _ => None,
+                    };
+
                     settings_advanced = Some(Advanced {
                         headers: headers_entries,
                         rewrites: rewrites_entries,
                         redirects: redirects_entries,
+                        cache_control: cache_control_entries,
+                        security_headers: security_headers_entries,
+                        auth: auth_entries,
+                        ip_filter: ip_filter_entries,
+                        rate_limit: rate_limit_entries,
+                        bandwidth_limit: bandwidth_limit_entries,
+                        #[cfg(feature = "compression")]
+                        compression_dictionaries: compression_dictionaries_entries,
+                        #[cfg(feature = "http2")]
+                        tls_certificates: tls_certificates_entries,
                     });
                 }
             }
         }
 
+        // Every global authentication scheme rejects the requests lacking its own credentials,
+        // so combining them would make the site unreachable.
+        let global_auth: Vec<&str> = [
+            #[cfg(feature = "basic-auth")]
+            (
+                !basic_auth.trim().is_empty() || basic_auth_file.is_some(),
+                "--basic-auth/--basic-auth-file",
+            ),
+            #[cfg(feature = "jwt-auth")]
+            (jwt_keys_file.is_some(), "--jwt-keys-file"),
+            #[cfg(feature = "signed-urls")]
+            (signed_urls_required, "--signed-urls-required"),
+            #[cfg(feature = "forward-auth")]
+            (!forward_auth_url.trim().is_empty(), "--forward-auth-url"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, option): (bool, &str)| enabled.then_some(option))
+        .collect();
+        if global_auth.len() > 1 {
+            bail!(
+                "the global authentication options {} can not be combined, use path-scoped auth rules instead",
+                global_auth.join(", ")
+            );
+        }
+
         Ok(Settings {
             general: General {
                 host,
@@ -381,10 +1147,18 @@ impl Settings {
                 log_level,
                 config_file,
                 cache_control_headers,
+                cache_control_fingerprint,
+                cache_control_fingerprint_pattern,
                 #[cfg(feature = "compression")]
                 compression,
                 #[cfg(feature = "compression")]
                 compression_static,
+                #[cfg(feature = "compression")]
+                compression_cache,
+                #[cfg(feature = "compression")]
+                compression_cache_max_size,
+                #[cfg(feature = "compression")]
+                compression_cache_dir,
                 page404,
                 page50x,
                 #[cfg(feature = "http2")]
@@ -394,6 +1168,18 @@ impl Settings {
                 #[cfg(feature = "http2")]
                 http2_tls_key,
                 #[cfg(feature = "http2")]
+                http2_tls_client_ca,
+                #[cfg(feature = "http2")]
+                http2_tls_client_auth_optional,
+                #[cfg(feature = "http2")]
+                http2_tls_handshake_timeout,
+                #[cfg(feature = "http2")]
+                http2_tls_reload_interval,
+                #[cfg(feature = "http2")]
+                http2_tls_ocsp_response,
+                #[cfg(feature = "http2")]
+                http2_tls_ocsp_refresh_interval,
+                #[cfg(feature = "http2")]
                 https_redirect,
                 #[cfg(feature = "http2")]
                 https_redirect_host,
@@ -402,9 +1188,25 @@ impl Settings {
                 #[cfg(feature = "http2")]
                 https_redirect_from_hosts,
                 security_headers,
+                security_headers_preset,
+                #[cfg(feature = "csp-nonce")]
+                csp_nonce,
+                #[cfg(feature = "csp-nonce")]
+                csp_nonce_placeholder,
+                #[cfg(feature = "csp-nonce")]
+                csp_nonce_tags,
+                #[cfg(feature = "csp-nonce")]
+                csp_nonce_policy,
+                csp_reports_endpoint,
+                csp_reports_file,
+                csp_reports_max_size,
+                csp_reports_rate_limit,
                 cors_allow_origins,
                 cors_allow_headers,
                 cors_expose_headers,
+                ip_allow,
+                ip_deny,
+                trusted_proxies,
                 #[cfg(feature = "directory-listing")]
                 directory_listing,
                 #[cfg(feature = "directory-listing")]
@@ -413,6 +1215,67 @@ impl Settings {
                 directory_listing_format,
                 #[cfg(feature = "basic-auth")]
                 basic_auth,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_file,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_cache_ttl,
+                #[cfg(feature = "basic-auth")]
+                basic_auth_max_verifications,
+                #[cfg(feature = "jwt-auth")]
+                jwt_keys_file,
+                #[cfg(feature = "jwt-auth")]
+                jwt_issuer,
+                #[cfg(feature = "jwt-auth")]
+                jwt_audience,
+                #[cfg(feature = "jwt-auth")]
+                jwt_cookie,
+                #[cfg(feature = "jwt-auth")]
+                jwt_leeway,
+                #[cfg(feature = "signed-urls")]
+                signed_urls_key,
+                #[cfg(feature = "signed-urls")]
+                signed_urls_key_file,
+                #[cfg(feature = "signed-urls")]
+                signed_urls_required,
+                #[cfg(feature = "form-login")]
+                login_path,
+                #[cfg(feature = "form-login")]
+                logout_path,
+                #[cfg(feature = "form-login")]
+                login_page,
+                #[cfg(feature = "form-login")]
+                login_session_key,
+                #[cfg(feature = "form-login")]
+                login_session_ttl,
+                #[cfg(feature = "forward-auth")]
+                forward_auth_url,
+                #[cfg(feature = "forward-auth")]
+                forward_auth_request_headers,
+                #[cfg(feature = "forward-auth")]
+                forward_auth_response_headers,
+                #[cfg(feature = "forward-auth")]
+                forward_auth_timeout,
+                auth_max_failures,
+                auth_ban_duration,
+                auth_max_ban_duration,
+                rate_limit,
+                rate_limit_burst,
+                rate_limit_key,
+                bandwidth_limit,
+                bandwidth_limit_global,
+                bandwidth_limit_after,
+                max_connections,
+                max_connections_per_ip,
+                max_inflight_requests,
+                max_blocking_delay,
+                load_shed_retry_after,
+                header_read_timeout,
+                keep_alive,
+                keep_alive_timeout,
+                response_write_timeout,
+                max_uri_length,
+                max_header_bytes,
+                max_headers,
                 fd,
                 threads_multiplier,
                 max_blocking_threads,
@@ -423,13 +1286,108 @@ impl Settings {
                 redirect_trailing_slash,
                 ignore_hidden_files,
 
-                // Windows-only options and commands
+                // Windows-only options
                 #[cfg(windows)]
                 windows_service,
-                #[cfg(windows)]
+
+                // Subcommands
+                #[cfg(any(windows, feature = "compression", feature = "signed-urls"))]
                 commands: opts.commands,
             },
             advanced: settings_advanced,
         })
     }
 }
+
+/// Compiles a `Cache-Control` rule entry of the config file.
+fn cache_control_rule(entry: &file::CacheControl) -> Result<CacheControl> {
+    if entry.source.is_none() && entry.extensions.is_none() && entry.mime_types.is_none() {
+        bail!(
+            "cache-control rule requires at least one `source`, `extensions` or `mime-types` entry"
+        );
+    }
+
+    let source = match &entry.source {
+        Some(source) => Some(
+            Glob::new(source)
+                .with_context(|| {
+                    format!("can not compile glob pattern for cache-control source: {source}")
+                })?
+                .compile_matcher(),
+        ),
+        None => None,
+    };
+
+    let lowercase = |v: &Option<Vec<String>>| -> Vec<String> {
+        v.iter()
+            .flatten()
+            .map(|s| s.trim().trim_start_matches('.').to_ascii_lowercase())
+            .collect()
+    };
+
+    let no_cache = entry.no_cache.unwrap_or_default();
+    let no_store = entry.no_store.unwrap_or_default();
+    let mut directives = vec![if entry.private.unwrap_or_default() {
+        "private".to_owned()
+    } else {
+        "public".to_owned()
+    }];
+    if no_cache {
+        directives.push("no-cache".to_owned());
+    }
+    if no_store {
+        directives.push("no-store".to_owned());
+    }
+    if entry.must_revalidate.unwrap_or_default() {
+        directives.push("must-revalidate".to_owned());
+    }
+    if let Some(v) = entry.max_age {
+        directives.push(format!("max-age={v}"));
+    }
+    if let Some(v) = entry.s_maxage {
+        directives.push(format!("s-maxage={v}"));
+    }
+    if let Some(v) = entry.stale_while_revalidate {
+        directives.push(format!("stale-while-revalidate={v}"));
+    }
+    if let Some(v) = entry.stale_if_error {
+        directives.push(format!("stale-if-error={v}"));
+    }
+    if entry.immutable.unwrap_or_default() {
+        directives.push("immutable".to_owned());
+    }
+
+    // Responses which must be revalidated expire right away
+    let expires = if !entry.expires.unwrap_or_default() {
+        None
+    } else if no_cache || no_store {
+        Some(0)
+    } else {
+        Some(entry.max_age.unwrap_or_default())
+    };
+
+    let value = directives.join(", ");
+    Ok(CacheControl {
+        source,
+        extensions: lowercase(&entry.extensions),
+        mime_types: lowercase(&entry.mime_types),
+        value: headers::HeaderValue::from_str(&value)
+            .with_context(|| format!("invalid cache-control header value: {value}"))?,
+        expires,
+    })
+}
+
+/// Serializes a string as a structured field string. E.g `"/app/*.js"`
+#[cfg(feature = "compression")]
+fn sf_string(value: &str) -> String {
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s.push('"');
+    s
+}
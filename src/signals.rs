@@ -11,7 +11,7 @@ use tokio::time::{sleep, Duration};
 #[cfg(unix)]
 use {
     crate::Result, futures_util::stream::StreamExt, signal_hook::consts::signal::*,
-    signal_hook_tokio::Signals,
+    signal_hook_tokio::Signals, std::sync::Arc, tokio::sync::Notify,
 };
 
 #[cfg(windows)]
@@ -26,14 +26,19 @@ pub fn create_signals() -> Result<Signals> {
 
 #[cfg(unix)]
 /// It waits for a specific type of incoming signals included `ctrl+c`.
-pub async fn wait_for_signals(signals: Signals, grace_period_secs: u8) {
+/// `SIGHUP` signals are forwarded to the `reload` notifier if any.
+pub async fn wait_for_signals(
+    signals: Signals,
+    grace_period_secs: u8,
+    reload: Option<Arc<Notify>>,
+) {
     let mut signals = signals.fuse();
     while let Some(signal) = signals.next().await {
         match signal {
-            SIGHUP => {
-                // NOTE: for now we don't do something for SIGHUPs
-                tracing::debug!("SIGHUP caught, nothing to do about")
-            }
+            SIGHUP => match &reload {
+                Some(reload) => reload.notify_one(),
+                None => tracing::debug!("SIGHUP caught, nothing to do about"),
+            },
             SIGTERM | SIGINT | SIGQUIT => {
                 tracing::info!("SIGTERM, SIGINT or SIGQUIT signal caught");
                 break;
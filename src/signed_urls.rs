@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to sign and verify expiring URLs using HMAC-SHA256.
+//!
+//! A signed URL carries its expiration time and the signature in the query string,
+//! optionally bound to the client IP address:
+//! `/file.zip?expires=1700000000&ip=1&signature=...`
+//!
+
+use base64::Engine;
+use ring::hmac;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Context, Result};
+
+/// Query parameter containing the expiration UNIX time.
+pub const EXPIRES_PARAM: &str = "expires";
+/// Query parameter flagging a URL bound to the client IP address.
+pub const IP_PARAM: &str = "ip";
+/// Query parameter containing the signature.
+pub const SIGNATURE_PARAM: &str = "signature";
+
+/// Minimum length in bytes of the signing key.
+const MIN_KEY_LEN: usize = 16;
+
+/// It defines the signer and verifier of expiring URLs.
+pub struct UrlSigner {
+    key: hmac::Key,
+}
+
+impl UrlSigner {
+    /// Creates a new URL signer using the given secret key.
+    pub fn new(secret: &[u8]) -> Result<Self> {
+        if secret.len() < MIN_KEY_LEN {
+            bail!(
+                "signed urls key should be at least {} bytes long",
+                MIN_KEY_LEN
+            );
+        }
+        Ok(Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        })
+    }
+
+    /// Creates a URL signer from a key value or a key file if any of them is provided.
+    /// The surrounding whitespace of the key file content is ignored.
+    pub fn from_options(key: &str, key_file: Option<&Path>) -> Result<Option<Self>> {
+        let key = match key_file {
+            Some(_) if !key.is_empty() => {
+                bail!("signed urls key and key file options can not be used together")
+            }
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read signed urls key file {}", path.display()))?
+                .trim()
+                .to_owned(),
+            None if key.is_empty() => return Ok(None),
+            None => key.to_owned(),
+        };
+        Ok(Some(Self::new(key.as_bytes())?))
+    }
+
+    /// Returns the signed URL (path and query) of a path expiring at the given UNIX time.
+    /// If a client `ip` is provided then the URL is only valid for that address.
+    pub fn sign(&self, path: &str, expires: u64, ip: Option<IpAddr>) -> String {
+        let sig = self.signature(path, expires, ip);
+        match ip {
+            Some(_) => {
+                format!("{path}?{EXPIRES_PARAM}={expires}&{IP_PARAM}=1&{SIGNATURE_PARAM}={sig}")
+            }
+            None => format!("{path}?{EXPIRES_PARAM}={expires}&{SIGNATURE_PARAM}={sig}"),
+        }
+    }
+
+    /// Verifies the signature of a request path and query string at the given UNIX time.
+    pub fn verify_at(
+        &self,
+        path: &str,
+        query: Option<&str>,
+        remote_ip: Option<IpAddr>,
+        now: u64,
+    ) -> Result<()> {
+        let mut expires = None;
+        let mut bound_ip = false;
+        let mut signature = None;
+        for (name, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+            match name.as_ref() {
+                EXPIRES_PARAM => expires = value.parse::<u64>().ok(),
+                IP_PARAM => bound_ip = value == "1",
+                SIGNATURE_PARAM => signature = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        let (expires, signature) = match (expires, signature) {
+            (Some(expires), Some(signature)) => (expires, signature),
+            _ => bail!("missing url signature"),
+        };
+        let signature = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| anyhow!("malformed url signature"))?;
+
+        let ip = if bound_ip {
+            Some(remote_ip.ok_or_else(|| anyhow!("client ip address is unavailable"))?)
+        } else {
+            None
+        };
+        hmac::verify(&self.key, message(path, expires, ip).as_bytes(), &signature)
+            .map_err(|_| anyhow!("invalid url signature"))?;
+
+        // Checked after the signature, so the expiration time is trusted
+        if now > expires {
+            bail!("url signature expired");
+        }
+        Ok(())
+    }
+
+    /// Verifies the signature of a request path and query string at the current time.
+    pub fn verify(&self, path: &str, query: Option<&str>, remote_ip: Option<IpAddr>) -> Result<()> {
+        self.verify_at(path, query, remote_ip, unix_now())
+    }
+
+    fn signature(&self, path: &str, expires: u64, ip: Option<IpAddr>) -> String {
+        let tag = hmac::sign(&self.key, message(path, expires, ip).as_bytes());
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(tag.as_ref())
+    }
+}
+
+/// Signed message of a URL.
+fn message(path: &str, expires: u64, ip: Option<IpAddr>) -> String {
+    let ip = ip.map(|ip| ip.to_string()).unwrap_or_default();
+    format!("{expires}\n{ip}\n{path}")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Prints a signed URL of a path valid for `expires_in` seconds.
+/// It's used by the `sign-url` subcommand.
+pub fn run(
+    signer: Option<UrlSigner>,
+    path: &str,
+    expires_in: u64,
+    ip: Option<IpAddr>,
+    base_url: &str,
+) -> Result {
+    let signer = signer.ok_or_else(|| {
+        anyhow!("a key is required via the `--signed-urls-key` or `--signed-urls-key-file` options")
+    })?;
+    if !path.starts_with('/') || path.contains(['?', '#']) {
+        bail!("the path to sign should be an absolute URI path without query or fragment");
+    }
+    let url = signer.sign(path, unix_now().saturating_add(expires_in), ip);
+    println!("{}{}", base_url.trim_end_matches('/'), url);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn signer() -> UrlSigner {
+        UrlSigner::new(b"0123456789abcdef0123456789abcdef").unwrap()
+    }
+
+    fn split(url: &str) -> (&str, Option<&str>) {
+        let (path, query) = url.split_once('?').unwrap();
+        (path, Some(query))
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let signer = signer();
+        let url = signer.sign("/downloads/report%202023.pdf", NOW + 60, None);
+        let (path, query) = split(&url);
+        assert_eq!(path, "/downloads/report%202023.pdf");
+        assert!(query.unwrap().starts_with("expires=1700000060&signature="));
+
+        assert!(signer.verify_at(path, query, None, NOW).is_ok());
+        assert!(signer.verify_at(path, query, None, NOW + 60).is_ok());
+        let err = signer.verify_at(path, query, None, NOW + 61).unwrap_err();
+        assert_eq!(err.to_string(), "url signature expired");
+
+        // Tampered path, expiration or key
+        let err = signer.verify_at("/downloads/other.pdf", query, None, NOW);
+        assert_eq!(err.unwrap_err().to_string(), "invalid url signature");
+        let tampered = query.unwrap().replace("1700000060", "1800000000");
+        assert!(signer.verify_at(path, Some(&tampered), None, NOW).is_err());
+        let other = UrlSigner::new(b"another-secret-key-value").unwrap();
+        assert!(other.verify_at(path, query, None, NOW).is_err());
+
+        let err = signer.verify_at(path, None, None, NOW).unwrap_err();
+        assert_eq!(err.to_string(), "missing url signature");
+    }
+
+    #[test]
+    fn bound_to_client_ip() {
+        let signer = signer();
+        let ip: IpAddr = "192.168.1.10".parse().unwrap();
+        let other: IpAddr = "192.168.1.11".parse().unwrap();
+        let url = signer.sign("/file.zip", NOW + 60, Some(ip));
+        let (path, query) = split(&url);
+        assert!(query.unwrap().contains("&ip=1&"));
+
+        assert!(signer.verify_at(path, query, Some(ip), NOW).is_ok());
+        assert!(signer.verify_at(path, query, Some(other), NOW).is_err());
+        assert!(signer.verify_at(path, query, None, NOW).is_err());
+
+        // Removing the flag invalidates the signature
+        let unbound = query.unwrap().replace("&ip=1", "");
+        assert!(signer
+            .verify_at(path, Some(&unbound), Some(ip), NOW)
+            .is_err());
+    }
+
+    #[test]
+    fn key_options() {
+        assert!(UrlSigner::new(b"short").is_err());
+        assert!(UrlSigner::from_options("", None).unwrap().is_none());
+        assert!(UrlSigner::from_options("0123456789abcdef", None)
+            .unwrap()
+            .is_some());
+        assert!(UrlSigner::from_options("0123456789abcdef", Some(Path::new("key.txt"))).is_err());
+    }
+}
@@ -68,9 +68,26 @@ pub struct HandleOpts<'a> {
     pub ignore_hidden_files: bool,
 }
 
+/// Defines the static file response returned by the static-files handler.
+pub struct StaticFileResponse {
+    /// The HTTP response.
+    pub resp: Response<Body>,
+    /// If the response body corresponds to a pre-compressed file variant.
+    pub is_precompressed: bool,
+    /// The resolved file path of the current request.
+    pub file_path: PathBuf,
+}
+
 /// The server entry point to handle incoming requests which map to specific files
 /// on file system and return a file response.
 pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<(Response<Body>, bool), StatusCode> {
+    let result = handle_response(opts).await?;
+    Ok((result.resp, result.is_precompressed))
+}
+
+/// Same as [`handle`] but it also returns the resolved file path of the request
+/// which is needed by the response body transformations like the compression cache.
+pub async fn handle_response<'a>(opts: &HandleOpts<'a>) -> Result<StaticFileResponse, StatusCode> {
     let method = opts.method;
     let uri_path = opts.uri_path;
 
@@ -116,7 +133,11 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<(Response<Body>, bool),
             *resp.status_mut() = StatusCode::PERMANENT_REDIRECT;
 
             tracing::trace!("uri doesn't end with a slash so redirecting permanently");
-            return Ok((resp, is_precompressed));
+            return Ok(StaticFileResponse {
+                resp,
+                is_precompressed,
+                file_path: file_path.to_owned(),
+            });
         }
 
         // Respond with the permitted communication options
@@ -127,7 +148,11 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<(Response<Body>, bool),
                 .typed_insert(headers::Allow::from_iter(HTTP_SUPPORTED_METHODS.clone()));
             resp.headers_mut().typed_insert(AcceptRanges::bytes());
 
-            return Ok((resp, is_precompressed));
+            return Ok(StaticFileResponse {
+                resp,
+                is_precompressed,
+                file_path: file_path.to_owned(),
+            });
         }
 
         // Directory listing
@@ -147,7 +172,11 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<(Response<Body>, bool),
             )
             .await?;
 
-            return Ok((resp, is_precompressed));
+            return Ok(StaticFileResponse {
+                resp,
+                is_precompressed,
+                file_path: file_path.to_owned(),
+            });
         }
     }
 
@@ -161,12 +190,20 @@ pub async fn handle<'a>(opts: &HandleOpts<'a>) -> Result<(Response<Body>, bool),
         resp.headers_mut()
             .insert(CONTENT_ENCODING, precomp_ext.parse().unwrap());
 
-        return Ok((resp, is_precompressed));
+        return Ok(StaticFileResponse {
+            resp,
+            is_precompressed,
+            file_path: file_path.to_owned(),
+        });
     }
 
     let resp = file_reply(headers_opt, file_path, &metadata, None).await?;
 
-    Ok((resp, is_precompressed))
+    Ok(StaticFileResponse {
+        resp,
+        is_precompressed,
+        file_path: file_path.to_owned(),
+    })
 }
 
 /// It defines a composed file metadata structure containing the current file
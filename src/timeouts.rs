@@ -0,0 +1,405 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to close the connections which stay idle or take too long to send a response.
+//!
+//! The idle timeout only elapses between the requests of a connection, restarting once
+//! its last request is served, whereas the response write timeout is a deadline for
+//! the whole body of a response. The latter is also enforced by the connection, so
+//! clients which stop reading their responses can't hold it either.
+//!
+
+use bytes::Bytes;
+use futures_util::Stream;
+use hyper::{body::HttpBody, server::accept::Accept, Body};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::transport::{PeerIdentity, Transport};
+
+/// It defines an acceptor closing the connections which stay idle for too long.
+pub struct IdleTimeoutIncoming<A> {
+    incoming: A,
+    timeout: Option<Duration>,
+}
+
+impl<A> IdleTimeoutIncoming<A> {
+    /// Wraps an acceptor to apply the given idle timeout. A zero value disables it.
+    pub fn new(incoming: A, timeout: Duration) -> Self {
+        Self {
+            incoming,
+            timeout: Some(timeout).filter(|v| !v.is_zero()),
+        }
+    }
+}
+
+impl<A> Accept for IdleTimeoutIncoming<A>
+where
+    A: Accept + Unpin,
+{
+    type Conn = IdleTimeoutConn<A::Conn>;
+    type Error = A::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let pin = self.get_mut();
+        match Pin::new(&mut pin.incoming).poll_accept(cx) {
+            Poll::Ready(Some(Ok(conn))) => {
+                Poll::Ready(Some(Ok(IdleTimeoutConn::new(conn, pin.timeout))))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// It defines the requests being served on a connection.
+#[derive(Debug)]
+pub struct RequestsState {
+    active: usize,
+    idle_since: Instant,
+    /// Write deadlines of the responses being sent.
+    write_deadlines: Vec<Instant>,
+}
+
+impl Default for RequestsState {
+    fn default() -> Self {
+        Self {
+            active: 0,
+            idle_since: Instant::now(),
+            write_deadlines: Vec::new(),
+        }
+    }
+}
+
+/// Requests being served on a connection, shared with its idle timeout.
+pub type ActiveRequests = Arc<Mutex<RequestsState>>;
+
+/// It defines a request being served on a connection until it's dropped.
+pub struct ActiveRequest {
+    requests: ActiveRequests,
+    write_deadline: Option<Instant>,
+}
+
+impl ActiveRequest {
+    /// Counts a new request being served on a connection.
+    pub fn start(requests: &ActiveRequests) -> Self {
+        requests.lock().unwrap().active += 1;
+        Self {
+            requests: requests.clone(),
+            write_deadline: None,
+        }
+    }
+
+    /// Sets the deadline to send the response of the request, enforced by its connection.
+    pub fn set_write_deadline(&mut self, deadline: Instant) {
+        let mut state = self.requests.lock().unwrap();
+        if let Some(prev) = self.write_deadline.replace(deadline) {
+            remove_deadline(&mut state.write_deadlines, prev);
+        }
+        state.write_deadlines.push(deadline);
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        let mut state = self.requests.lock().unwrap();
+        if let Some(deadline) = self.write_deadline {
+            remove_deadline(&mut state.write_deadlines, deadline);
+        }
+        state.active -= 1;
+        if state.active == 0 {
+            state.idle_since = Instant::now();
+        }
+    }
+}
+
+/// Removes one occurrence of a write deadline.
+fn remove_deadline(deadlines: &mut Vec<Instant>, deadline: Instant) {
+    if let Some(i) = deadlines.iter().position(|v| *v == deadline) {
+        deadlines.swap_remove(i);
+    }
+}
+
+/// It defines a connection failing once it stays idle for too long
+/// or can't send a response before its write deadline.
+pub struct IdleTimeoutConn<T> {
+    conn: T,
+    timeout: Option<(Duration, Pin<Box<Sleep>>)>,
+    write_sleep: Option<Pin<Box<Sleep>>>,
+    requests: ActiveRequests,
+}
+
+impl<T> IdleTimeoutConn<T> {
+    /// Wraps a connection to apply the given idle timeout if any.
+    pub fn new(conn: T, timeout: Option<Duration>) -> Self {
+        Self {
+            conn,
+            timeout: timeout.map(|v| (v, Box::pin(tokio::time::sleep(v)))),
+            write_sleep: None,
+            requests: ActiveRequests::default(),
+        }
+    }
+
+    /// Checks whether a response write deadline has passed while the connection can't be written.
+    fn check_write(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        let deadline = self
+            .requests
+            .lock()
+            .unwrap()
+            .write_deadlines
+            .iter()
+            .min()
+            .copied();
+        let Some(deadline) = deadline else {
+            self.write_sleep = None;
+            return Ok(());
+        };
+        if deadline <= Instant::now() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "response write timeout",
+            ));
+        }
+        // Wake up the writer once the deadline passes to check it again
+        let sleep = self
+            .write_sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+        if sleep.deadline() != deadline {
+            sleep.as_mut().reset(deadline);
+        }
+        let _ = sleep.as_mut().poll(cx);
+        Ok(())
+    }
+
+    /// Checks whether the idle timeout has elapsed while waiting for the connection.
+    fn check(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        if let Some((timeout, sleep)) = &mut self.timeout {
+            while sleep.as_mut().poll(cx).is_ready() {
+                // The connection isn't idle while some requests are being served
+                let now = Instant::now();
+                let deadline = match &*self.requests.lock().unwrap() {
+                    RequestsState {
+                        active: 0,
+                        idle_since,
+                        ..
+                    } => *idle_since + *timeout,
+                    _ => now + *timeout,
+                };
+                if deadline <= now {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "connection idle timeout",
+                    ));
+                }
+                sleep.as_mut().reset(deadline);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for IdleTimeoutConn<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        match Pin::new(&mut pin.conn).poll_read(cx, buf) {
+            Poll::Ready(res) => Poll::Ready(res),
+            Poll::Pending => {
+                pin.check(cx)?;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for IdleTimeoutConn<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let pin = self.get_mut();
+        match Pin::new(&mut pin.conn).poll_write(cx, buf) {
+            Poll::Ready(res) => Poll::Ready(res),
+            Poll::Pending => {
+                pin.check(cx)?;
+                pin.check_write(cx)?;
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        match Pin::new(&mut pin.conn).poll_flush(cx) {
+            Poll::Ready(res) => Poll::Ready(res),
+            Poll::Pending => {
+                pin.check(cx)?;
+                pin.check_write(cx)?;
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().conn).poll_shutdown(cx)
+    }
+}
+
+impl<T: Transport + Unpin> Transport for IdleTimeoutConn<T> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.conn.remote_addr()
+    }
+
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        self.conn.peer_identity()
+    }
+
+    fn active_requests(&self) -> Option<ActiveRequests> {
+        Some(self.requests.clone())
+    }
+}
+
+/// It defines a response body failing once its write deadline has passed.
+pub struct DeadlineBody {
+    body: Body,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl DeadlineBody {
+    /// Wraps a response body which should be sent within the given timeout.
+    ///
+    /// Bodies already complete are returned as they are.
+    pub fn wrap(body: Body, timeout: Duration) -> Body {
+        if body.is_end_stream() {
+            return body;
+        }
+        Body::wrap_stream(Self {
+            body,
+            sleep: Box::pin(tokio::time::sleep(timeout)),
+        })
+    }
+}
+
+impl Stream for DeadlineBody {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        if this.sleep.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Some(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "response write timeout",
+            ))));
+        }
+        match Pin::new(&mut this.body).poll_data(cx) {
+            Poll::Ready(Some(Ok(chunk))) => Poll::Ready(Some(Ok(chunk))),
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(io::Error::other(err)))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{stream, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_connections() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut conn = IdleTimeoutConn::new(server, Some(Duration::from_secs(5)));
+        let start = Instant::now();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(3)).await;
+            client.write_all(b"ping").await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            drop(client);
+        });
+
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        // The data received at 3s isn't a request so the timeout isn't restarted
+        let err = conn.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn active_connections() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut conn = IdleTimeoutConn::new(server, Some(Duration::from_secs(5)));
+        let request = ActiveRequest::start(&conn.requests);
+        let start = Instant::now();
+
+        tokio::spawn(async move {
+            // A slow request ends after 12s without any data sent meanwhile
+            tokio::time::sleep(Duration::from_secs(12)).await;
+            drop(request);
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            client.write_all(b"ping").await.unwrap();
+        });
+
+        let mut buf = [0u8; 4];
+        let err = conn.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(start.elapsed(), Duration::from_secs(17));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stalled_responses() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut conn = IdleTimeoutConn::new(server, Some(Duration::from_secs(5)));
+        let mut request = ActiveRequest::start(&conn.requests);
+        let start = Instant::now();
+        request.set_write_deadline(start + Duration::from_secs(10));
+
+        // The client stops reading once the connection buffer is full
+        let err = loop {
+            if let Err(err) = conn.write_all(&[0u8; 16]).await {
+                break err;
+            }
+        };
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+        drop(request);
+        assert!(conn.requests.lock().unwrap().write_deadlines.is_empty());
+        drop(client);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn response_deadline() {
+        let chunks = stream::iter(0..3).then(|_| async {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            Ok::<_, io::Error>(Bytes::from_static(b"data"))
+        });
+        let mut body = DeadlineBody::wrap(Body::wrap_stream(chunks), Duration::from_secs(5));
+        assert_eq!(body.next().await.unwrap().unwrap(), "data");
+        assert_eq!(body.next().await.unwrap().unwrap(), "data");
+        assert!(body.next().await.unwrap().is_err());
+
+        let body = DeadlineBody::wrap(Body::empty(), Duration::from_secs(5));
+        assert!(body.is_end_stream());
+    }
+}
@@ -11,21 +11,53 @@
 use futures_util::ready;
 use hyper::server::accept::Accept;
 use hyper::server::conn::{AddrIncoming, AddrStream};
+use ring::signature;
+use std::collections::HashMap;
 use std::fs::File;
 use std::future::Future;
 use std::io::{self, BufReader, Cursor, Read};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
 use tokio_rustls::rustls::{
-    server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth},
-    Certificate, Error as TlsError, PrivateKey, RootCertStore, ServerConfig,
+    server::{
+        AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientHello,
+        NoClientAuth, ResolvesServerCert,
+    },
+    sign::{any_supported_type, CertifiedKey},
+    Certificate, Error as TlsError, PrivateKey, RootCertStore, ServerConfig, SignatureScheme,
 };
 
-use crate::transport::Transport;
+use x509_parser::{
+    der_parser::asn1_rs::{BmpString, Oid, Tag},
+    oid_registry::{
+        OID_DOMAIN_COMPONENT, OID_PKCS9_EMAIL_ADDRESS, OID_USERID, OID_X509_COMMON_NAME,
+        OID_X509_COUNTRY_NAME, OID_X509_LOCALITY_NAME, OID_X509_ORGANIZATIONAL_UNIT,
+        OID_X509_ORGANIZATION_NAME, OID_X509_STATE_OR_PROVINCE_NAME,
+    },
+    prelude::{FromDer, GeneralName, X509Certificate},
+    public_key::PublicKey,
+    x509::AttributeTypeAndValue,
+};
+
+use crate::{
+    ocsp,
+    transport::{ClientIdentity, PeerIdentity, Transport},
+};
+
+/// Signature schemes used to check that a private key matches its certificate.
+const KEY_CHECK_SCHEMES: &[SignatureScheme] = &[
+    SignatureScheme::ECDSA_NISTP256_SHA256,
+    SignatureScheme::ECDSA_NISTP384_SHA384,
+    SignatureScheme::ED25519,
+    SignatureScheme::RSA_PSS_SHA256,
+    SignatureScheme::RSA_PKCS1_SHA256,
+];
 
 /// Represents errors that can occur building the TlsConfig
 #[derive(Debug)]
@@ -42,6 +74,14 @@ pub enum TlsConfigError {
     UnknownPrivateKeyFormat,
     /// An error from an invalid key
     InvalidKey(TlsError),
+    /// The private key doesn't match the certificate
+    KeyMismatch,
+    /// The RSA key of the certificate has an unsupported size in bits
+    UnsupportedKeySize(usize),
+    /// A certificate selected by server name has no names
+    MissingServerName,
+    /// The OCSP response is invalid or doesn't match the certificate
+    InvalidOcspResponse(String),
 }
 
 impl std::fmt::Display for TlsConfigError {
@@ -53,6 +93,15 @@ impl std::fmt::Display for TlsConfigError {
             TlsConfigError::UnknownPrivateKeyFormat => write!(f, "unknown private key format"),
             TlsConfigError::EmptyKey => write!(f, "key contains no private key"),
             TlsConfigError::InvalidKey(err) => write!(f, "key contains an invalid key, {err}"),
+            TlsConfigError::KeyMismatch => write!(f, "key doesn't match the certificate"),
+            TlsConfigError::UnsupportedKeySize(bits) => write!(
+                f,
+                "rsa key of {bits} bits is not supported, 2048 to 8192 bits are required"
+            ),
+            TlsConfigError::MissingServerName => {
+                write!(f, "certificate has no DNS names and no hosts are set")
+            }
+            TlsConfigError::InvalidOcspResponse(err) => write!(f, "invalid ocsp response, {err}"),
         }
     }
 }
@@ -74,7 +123,8 @@ pub struct TlsConfigBuilder {
     cert: Box<dyn Read + Send + Sync>,
     key: Box<dyn Read + Send + Sync>,
     client_auth: TlsClientAuth,
-    ocsp_resp: Vec<u8>,
+    ocsp_resp: Option<Box<dyn Read + Send + Sync>>,
+    sni_certs: Vec<SniCert>,
 }
 
 impl std::fmt::Debug for TlsConfigBuilder {
@@ -90,7 +140,8 @@ impl TlsConfigBuilder {
             key: Box::new(io::empty()),
             cert: Box::new(io::empty()),
             client_auth: TlsClientAuth::Off,
-            ocsp_resp: Vec::new(),
+            ocsp_resp: None,
+            sni_certs: Vec::new(),
         }
     }
 
@@ -172,45 +223,86 @@ impl TlsConfigBuilder {
 
     /// sets the DER-encoded OCSP response
     pub fn ocsp_resp(mut self, ocsp_resp: &[u8]) -> Self {
-        self.ocsp_resp = Vec::from(ocsp_resp);
+        self.ocsp_resp = Some(Box::new(Cursor::new(Vec::from(ocsp_resp))));
         self
     }
 
-    /// Builds TLS configuration.
-    pub fn build(mut self) -> Result<ServerConfig, TlsConfigError> {
-        let mut cert_rdr = BufReader::new(self.cert);
-        let cert = rustls_pemfile::certs(&mut cert_rdr)
-            .map_err(|_e| TlsConfigError::CertParseError)?
-            .into_iter()
-            .map(Certificate)
-            .collect();
-
-        // convert it to Vec<u8> to allow reading it again if key is RSA
-        let mut key_vec = Vec::new();
-        self.key
-            .read_to_end(&mut key_vec)
-            .map_err(TlsConfigError::Io)?;
+    /// Sets the DER-encoded OCSP response of the default certificate via file path.
+    ///
+    /// The response is only stapled if it matches the certificate and it's not expired.
+    pub fn ocsp_resp_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.ocsp_resp = Some(Box::new(LazyFile {
+            path: path.as_ref().into(),
+            file: None,
+        }));
+        self
+    }
 
-        if key_vec.is_empty() {
-            return Err(TlsConfigError::EmptyKey);
-        }
+    /// Adds a certificate selected by the server name (SNI) of the clients via file paths.
+    ///
+    /// Names starting with `*.` match a single level of subdomains. If `hosts` is empty then
+    /// the DNS names of the certificate are used. The certificate set via `cert` or `cert_path`
+    /// is the default one, sent when the server name of a client matches no other certificate.
+    /// An OCSP response file can be stapled to the certificate as well.
+    pub fn sni_cert_path(
+        mut self,
+        hosts: Vec<String>,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+        ocsp_resp: Option<&Path>,
+    ) -> Self {
+        self.sni_certs.push(SniCert {
+            hosts,
+            cert: Box::new(LazyFile {
+                path: cert.as_ref().into(),
+                file: None,
+            }),
+            key: Box::new(LazyFile {
+                path: key.as_ref().into(),
+                file: None,
+            }),
+            ocsp_resp: ocsp_resp.map(|path| -> Box<dyn Read + Send + Sync> {
+                Box::new(LazyFile {
+                    path: path.into(),
+                    file: None,
+                })
+            }),
+        });
+        self
+    }
 
-        let mut key = None;
-        let mut reader = std::io::Cursor::new(key_vec);
-        for item in rustls_pemfile::read_all(&mut reader)
-            .map_err(|_e| TlsConfigError::InvalidIdentityPem)?
-        {
-            match item {
-                rustls_pemfile::Item::RSAKey(k) => key = Some(PrivateKey(k)),
-                rustls_pemfile::Item::PKCS8Key(k) => key = Some(PrivateKey(k)),
-                rustls_pemfile::Item::ECKey(k) => key = Some(PrivateKey(k)),
-                _ => return Err(TlsConfigError::UnknownPrivateKeyFormat),
+    /// Adds a certificate selected by the server name (SNI) of the clients via bytes slices.
+    pub fn sni_cert(mut self, hosts: Vec<String>, cert: &[u8], key: &[u8]) -> Self {
+        self.sni_certs.push(SniCert {
+            hosts,
+            cert: Box::new(Cursor::new(Vec::from(cert))),
+            key: Box::new(Cursor::new(Vec::from(key))),
+            ocsp_resp: None,
+        });
+        self
+    }
+
+    /// Builds TLS configuration.
+    pub fn build(self) -> Result<ServerConfig, TlsConfigError> {
+        let mut default = read_certified_key(self.cert, self.key)?;
+        staple_ocsp_resp(&mut default, self.ocsp_resp)?;
+        let mut resolver = SniResolver::new(default);
+        for sni_cert in self.sni_certs {
+            let mut certified_key = read_certified_key(sni_cert.cert, sni_cert.key)?;
+            staple_ocsp_resp(&mut certified_key, sni_cert.ocsp_resp)?;
+            let certified_key = Arc::new(certified_key);
+            let hosts = if sni_cert.hosts.is_empty() {
+                cert_dns_names(&certified_key.cert[0])
+            } else {
+                sni_cert.hosts
+            };
+            if hosts.is_empty() {
+                return Err(TlsConfigError::MissingServerName);
+            }
+            for host in hosts {
+                resolver.add(&host, certified_key.clone());
             }
         }
-        let key = match key {
-            Some(k) => k,
-            _ => return Err(TlsConfigError::EmptyKey),
-        };
 
         fn read_trust_anchor(
             trust_anchor: Box<dyn Read + Send + Sync>,
@@ -242,13 +334,181 @@ impl TlsConfigBuilder {
         let mut config = ServerConfig::builder()
             .with_safe_defaults()
             .with_client_cert_verifier(client_auth)
-            .with_single_cert_with_ocsp_and_sct(cert, key, self.ocsp_resp, Vec::new())
-            .map_err(TlsConfigError::InvalidKey)?;
+            .with_cert_resolver(Arc::new(resolver));
         config.alpn_protocols = vec!["h2".into(), "http/1.1".into()];
         Ok(config)
     }
 }
 
+/// Reads a PEM encoded certificate chain and its private key.
+fn read_certified_key(
+    cert: Box<dyn Read + Send + Sync>,
+    mut key: Box<dyn Read + Send + Sync>,
+) -> Result<CertifiedKey, TlsConfigError> {
+    let mut cert_rdr = BufReader::new(cert);
+    let cert: Vec<Certificate> = rustls_pemfile::certs(&mut cert_rdr)
+        .map_err(|_e| TlsConfigError::CertParseError)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+
+    // convert it to Vec<u8> to allow reading it again if key is RSA
+    let mut key_vec = Vec::new();
+    key.read_to_end(&mut key_vec).map_err(TlsConfigError::Io)?;
+
+    if key_vec.is_empty() {
+        return Err(TlsConfigError::EmptyKey);
+    }
+
+    let mut key = None;
+    let mut reader = std::io::Cursor::new(key_vec);
+    for item in
+        rustls_pemfile::read_all(&mut reader).map_err(|_e| TlsConfigError::InvalidIdentityPem)?
+    {
+        match item {
+            rustls_pemfile::Item::RSAKey(k) => key = Some(PrivateKey(k)),
+            rustls_pemfile::Item::PKCS8Key(k) => key = Some(PrivateKey(k)),
+            rustls_pemfile::Item::ECKey(k) => key = Some(PrivateKey(k)),
+            _ => return Err(TlsConfigError::UnknownPrivateKeyFormat),
+        }
+    }
+    let key = match key {
+        Some(k) => k,
+        _ => return Err(TlsConfigError::EmptyKey),
+    };
+    check_key_pair(&cert, &key)?;
+
+    let signing_key =
+        any_supported_type(&key).map_err(|_| TlsConfigError::UnknownPrivateKeyFormat)?;
+    Ok(CertifiedKey::new(cert, signing_key))
+}
+
+/// Staples an OCSP response to a certificate once it's checked.
+///
+/// Expired responses aren't stapled since clients would reject them.
+fn staple_ocsp_resp(
+    certified_key: &mut CertifiedKey,
+    ocsp_resp: Option<Box<dyn Read + Send + Sync>>,
+) -> Result<(), TlsConfigError> {
+    let mut der = Vec::new();
+    if let Some(mut ocsp_resp) = ocsp_resp {
+        ocsp_resp
+            .read_to_end(&mut der)
+            .map_err(TlsConfigError::Io)?;
+    }
+    if der.is_empty() {
+        return Ok(());
+    }
+
+    let validity = ocsp::check_response(&der, &certified_key.cert)
+        .map_err(TlsConfigError::InvalidOcspResponse)?;
+    let now = SystemTime::now();
+    if validity.is_expired(now) {
+        tracing::warn!("ocsp response is expired so it's not stapled, it should be renewed");
+        return Ok(());
+    }
+    if let Some(remaining) = validity.expires_soon(now) {
+        tracing::warn!(
+            "ocsp response expires in {}m, it should be renewed",
+            remaining.as_secs() / 60
+        );
+    }
+    certified_key.ocsp = Some(der);
+    Ok(())
+}
+
+/// Returns the DNS names of a DER encoded X.509 certificate.
+fn cert_dns_names(cert: &Certificate) -> Vec<String> {
+    client_identity(&cert.0)
+        .map(|identity| {
+            identity
+                .sans
+                .iter()
+                .filter_map(|san| san.strip_prefix("DNS:"))
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Certificate selected by the server name of the clients.
+struct SniCert {
+    hosts: Vec<String>,
+    cert: Box<dyn Read + Send + Sync>,
+    key: Box<dyn Read + Send + Sync>,
+    ocsp_resp: Option<Box<dyn Read + Send + Sync>>,
+}
+
+/// Resolves the certificate of a connection from the server name (SNI) sent by the client.
+struct SniResolver {
+    names: HashMap<String, Arc<CertifiedKey>>,
+    default: Arc<CertifiedKey>,
+}
+
+impl SniResolver {
+    fn new(default: CertifiedKey) -> Self {
+        Self {
+            names: HashMap::new(),
+            default: Arc::new(default),
+        }
+    }
+
+    /// Adds a certificate for a server name. The first certificate added for a name wins.
+    fn add(&mut self, host: &str, certified_key: Arc<CertifiedKey>) {
+        let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+        if !host.is_empty() {
+            self.names.entry(host).or_insert(certified_key);
+        }
+    }
+
+    /// Returns the certificate of a server name, trying the wildcard of its parent domain
+    /// when no certificate matches the exact name.
+    fn lookup(&self, server_name: &str) -> Option<Arc<CertifiedKey>> {
+        let name = server_name.trim_end_matches('.').to_ascii_lowercase();
+        if let Some(certified_key) = self.names.get(&name) {
+            return Some(certified_key.clone());
+        }
+        let (_, parent) = name.split_once('.')?;
+        self.names.get(&format!("*.{parent}")).cloned()
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certified_key = client_hello
+            .server_name()
+            .and_then(|name| self.lookup(name));
+        Some(certified_key.unwrap_or_else(|| self.default.clone()))
+    }
+}
+
+/// Checks that a private key matches the public key of the end-entity certificate
+/// by verifying a signature made with the key.
+fn check_key_pair(certs: &[Certificate], key: &PrivateKey) -> Result<(), TlsConfigError> {
+    let cert = certs.first().ok_or(TlsConfigError::CertParseError)?;
+    let public_key = cert_public_key(&cert.0).ok_or(TlsConfigError::CertParseError)?;
+    // RSA keys out of the supported sizes can't be used, whether they match or not
+    if let Some(bits) = cert_rsa_key_bits(&cert.0).filter(|v| !(2048..=8192).contains(v)) {
+        return Err(TlsConfigError::UnsupportedKeySize(bits));
+    }
+    let signer = any_supported_type(key)
+        .ok()
+        .and_then(|key| key.choose_scheme(KEY_CHECK_SCHEMES))
+        .ok_or(TlsConfigError::UnknownPrivateKeyFormat)?;
+    let alg: &dyn signature::VerificationAlgorithm = match signer.scheme() {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => &signature::ECDSA_P256_SHA256_ASN1,
+        SignatureScheme::ECDSA_NISTP384_SHA384 => &signature::ECDSA_P384_SHA384_ASN1,
+        SignatureScheme::ED25519 => &signature::ED25519,
+        SignatureScheme::RSA_PSS_SHA256 => &signature::RSA_PSS_2048_8192_SHA256,
+        _ => &signature::RSA_PKCS1_2048_8192_SHA256,
+    };
+    let message = b"static-web-server key check";
+    let sig = signer.sign(message).map_err(TlsConfigError::InvalidKey)?;
+    signature::UnparsedPublicKey::new(alg, &public_key)
+        .verify(message, &sig)
+        .map_err(|_| TlsConfigError::KeyMismatch)
+}
+
 impl Default for TlsConfigBuilder {
     fn default() -> Self {
         Self::new()
@@ -286,10 +546,14 @@ impl Transport for TlsStream {
     fn remote_addr(&self) -> Option<SocketAddr> {
         Some(self.remote_addr)
     }
+
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        Some(self.identity.clone())
+    }
 }
 
 enum State {
-    Handshaking(tokio_rustls::Accept<AddrStream>),
+    Handshaking(tokio_rustls::Accept<AddrStream>, Option<Pin<Box<Sleep>>>),
     Streaming(tokio_rustls::server::TlsStream<AddrStream>),
 }
 
@@ -300,15 +564,39 @@ enum State {
 pub struct TlsStream {
     state: State,
     remote_addr: SocketAddr,
+    identity: PeerIdentity,
 }
 
 impl TlsStream {
-    fn new(stream: AddrStream, config: Arc<ServerConfig>) -> TlsStream {
+    fn new(
+        stream: AddrStream,
+        config: Arc<ServerConfig>,
+        handshake_timeout: Option<Duration>,
+    ) -> TlsStream {
         let remote_addr = stream.remote_addr();
         let accept = tokio_rustls::TlsAcceptor::from(config).accept(stream);
+        let deadline = handshake_timeout.map(|timeout| Box::pin(tokio::time::sleep(timeout)));
         TlsStream {
-            state: State::Handshaking(accept),
+            state: State::Handshaking(accept, deadline),
             remote_addr,
+            identity: PeerIdentity::default(),
+        }
+    }
+
+    /// Stores the identity of the verified client certificate once the handshake completes.
+    fn set_identity(&self, stream: &tokio_rustls::server::TlsStream<AddrStream>) {
+        let cert = match stream.get_ref().1.peer_certificates() {
+            Some([cert, ..]) => cert,
+            _ => return,
+        };
+        match client_identity(&cert.0) {
+            Some(identity) => {
+                let _ = self.identity.set(identity);
+            }
+            None => tracing::warn!(
+                "unable to parse the client certificate of {}",
+                self.remote_addr
+            ),
         }
     }
 }
@@ -321,14 +609,18 @@ impl AsyncRead for TlsStream {
     ) -> Poll<io::Result<()>> {
         let pin = self.get_mut();
         match pin.state {
-            State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
-                Ok(mut stream) => {
-                    let result = Pin::new(&mut stream).poll_read(cx, buf);
-                    pin.state = State::Streaming(stream);
-                    result
+            State::Handshaking(ref mut accept, ref mut deadline) => {
+                check_handshake_deadline(deadline, pin.remote_addr, cx)?;
+                match ready!(Pin::new(accept).poll(cx)) {
+                    Ok(mut stream) => {
+                        pin.set_identity(&stream);
+                        let result = Pin::new(&mut stream).poll_read(cx, buf);
+                        pin.state = State::Streaming(stream);
+                        result
+                    }
+                    Err(err) => Poll::Ready(Err(err)),
                 }
-                Err(err) => Poll::Ready(Err(err)),
-            },
+            }
             State::Streaming(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
         }
     }
@@ -342,47 +634,107 @@ impl AsyncWrite for TlsStream {
     ) -> Poll<io::Result<usize>> {
         let pin = self.get_mut();
         match pin.state {
-            State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
-                Ok(mut stream) => {
-                    let result = Pin::new(&mut stream).poll_write(cx, buf);
-                    pin.state = State::Streaming(stream);
-                    result
+            State::Handshaking(ref mut accept, ref mut deadline) => {
+                check_handshake_deadline(deadline, pin.remote_addr, cx)?;
+                match ready!(Pin::new(accept).poll(cx)) {
+                    Ok(mut stream) => {
+                        pin.set_identity(&stream);
+                        let result = Pin::new(&mut stream).poll_write(cx, buf);
+                        pin.state = State::Streaming(stream);
+                        result
+                    }
+                    Err(err) => Poll::Ready(Err(err)),
                 }
-                Err(err) => Poll::Ready(Err(err)),
-            },
+            }
             State::Streaming(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
         }
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         match self.state {
-            State::Handshaking(_) => Poll::Ready(Ok(())),
+            State::Handshaking(..) => Poll::Ready(Ok(())),
             State::Streaming(ref mut stream) => Pin::new(stream).poll_flush(cx),
         }
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         match self.state {
-            State::Handshaking(_) => Poll::Ready(Ok(())),
+            State::Handshaking(..) => Poll::Ready(Ok(())),
             State::Streaming(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
         }
     }
 }
 
+/// Fails the handshake of a connection once its deadline is reached.
+fn check_handshake_deadline(
+    deadline: &mut Option<Pin<Box<Sleep>>>,
+    remote_addr: SocketAddr,
+    cx: &mut Context<'_>,
+) -> io::Result<()> {
+    if let Some(deadline) = deadline {
+        if deadline.as_mut().poll(cx).is_ready() {
+            tracing::debug!("tls handshake timed out for {}", remote_addr);
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "tls handshake timed out",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Tls server configuration which can be swapped while the server is running.
+///
+/// Connections keep the configuration they were accepted with.
+#[derive(Clone)]
+pub struct SharedTlsConfig(Arc<RwLock<Arc<ServerConfig>>>);
+
+impl SharedTlsConfig {
+    /// Creates a new shared configuration.
+    pub fn new(config: ServerConfig) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// Returns the current configuration.
+    pub fn load(&self) -> Arc<ServerConfig> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Replaces the configuration used by the new connections.
+    pub fn store(&self, config: ServerConfig) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
+
+impl From<ServerConfig> for SharedTlsConfig {
+    fn from(config: ServerConfig) -> Self {
+        Self::new(config)
+    }
+}
+
 /// Type to intercept Tls incoming connections.
 pub struct TlsAcceptor {
-    config: Arc<ServerConfig>,
+    config: SharedTlsConfig,
     incoming: AddrIncoming,
+    handshake_timeout: Option<Duration>,
 }
 
 impl TlsAcceptor {
     /// Creates a new Tls interceptor.
-    pub fn new(config: ServerConfig, incoming: AddrIncoming) -> TlsAcceptor {
+    pub fn new(config: impl Into<SharedTlsConfig>, incoming: AddrIncoming) -> TlsAcceptor {
         TlsAcceptor {
-            config: Arc::new(config),
+            config: config.into(),
             incoming,
+            handshake_timeout: None,
         }
     }
+
+    /// Closes the connections not completing their handshake within the given timeout.
+    /// A zero value disables the timeout.
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout).filter(|timeout| !timeout.is_zero());
+        self
+    }
 }
 
 impl Accept for TlsAcceptor {
@@ -395,13 +747,127 @@ impl Accept for TlsAcceptor {
     ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
         let pin = self.get_mut();
         match ready!(Pin::new(&mut pin.incoming).poll_accept(cx)) {
-            Some(Ok(sock)) => Poll::Ready(Some(Ok(TlsStream::new(sock, pin.config.clone())))),
+            Some(Ok(sock)) => Poll::Ready(Some(Ok(TlsStream::new(
+                sock,
+                pin.config.load(),
+                pin.handshake_timeout,
+            )))),
             Some(Err(e)) => Poll::Ready(Some(Err(e))),
             None => Poll::Ready(None),
         }
     }
 }
 
+/// Returns the subject public key of a DER encoded X.509 certificate.
+pub fn cert_public_key(der: &[u8]) -> Option<Vec<u8>> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let key = &cert.public_key().subject_public_key;
+    // The key is a bit string without unused bits
+    (key.unused_bits == 0).then(|| key.data.to_vec())
+}
+
+/// Returns the size in bits of the RSA public key of a DER encoded X.509 certificate if any.
+fn cert_rsa_key_bits(der: &[u8]) -> Option<usize> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    match cert.public_key().parsed().ok()? {
+        PublicKey::RSA(rsa) => {
+            let first = rsa.modulus.iter().position(|b| *b != 0)?;
+            let modulus = &rsa.modulus[first..];
+            Some(modulus.len() * 8 - modulus[0].leading_zeros() as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Parses the subject and the subject alternative names of a DER encoded X.509 certificate.
+pub fn client_identity(der: &[u8]) -> Option<ClientIdentity> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+
+    let attributes = cert
+        .subject()
+        .iter_attributes()
+        .map(|attr| {
+            let value = attribute_value(attr)?;
+            Some(format!("{}={}", attribute_name(attr.attr_type()), value))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut sans = Vec::new();
+    if let Some(ext) = cert.subject_alternative_name().ok()? {
+        for name in &ext.value.general_names {
+            let name = match name {
+                GeneralName::RFC822Name(v) => format!("email:{v}"),
+                GeneralName::DNSName(v) => format!("DNS:{v}"),
+                GeneralName::URI(v) => format!("URI:{v}"),
+                GeneralName::IPAddress(v) => match v.len() {
+                    4 => format!("IP:{}", IpAddr::from(<[u8; 4]>::try_from(*v).ok()?)),
+                    16 => format!("IP:{}", IpAddr::from(<[u8; 16]>::try_from(*v).ok()?)),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            sans.push(name);
+        }
+    }
+
+    let subject = attributes
+        .iter()
+        .rev()
+        .map(|attr| escape_dn_value(attr))
+        .collect::<Vec<_>>()
+        .join(",");
+    Some(ClientIdentity {
+        subject,
+        attributes,
+        sans,
+    })
+}
+
+/// Returns the value of a distinguished name attribute as a string.
+fn attribute_value(attr: &AttributeTypeAndValue<'_>) -> Option<String> {
+    let value = attr.attr_value();
+    match value.tag() {
+        Tag::BmpString => BmpString::try_from(value).ok().map(|v| v.string()),
+        _ => Some(String::from_utf8_lossy(value.data).into_owned()),
+    }
+}
+
+/// Short names of the usual distinguished name attributes.
+const ATTRIBUTE_NAMES: [(Oid<'static>, &str); 9] = [
+    (OID_X509_COMMON_NAME, "CN"),
+    (OID_X509_COUNTRY_NAME, "C"),
+    (OID_X509_LOCALITY_NAME, "L"),
+    (OID_X509_STATE_OR_PROVINCE_NAME, "ST"),
+    (OID_X509_ORGANIZATION_NAME, "O"),
+    (OID_X509_ORGANIZATIONAL_UNIT, "OU"),
+    (OID_PKCS9_EMAIL_ADDRESS, "emailAddress"),
+    (OID_DOMAIN_COMPONENT, "DC"),
+    (OID_USERID, "UID"),
+];
+
+/// Returns the short name of a distinguished name attribute or its dotted identifier.
+fn attribute_name(oid: &Oid<'_>) -> String {
+    ATTRIBUTE_NAMES
+        .iter()
+        .find(|(v, _)| v == oid)
+        .map_or_else(|| oid.to_id_string(), |(_, name)| (*name).to_owned())
+}
+
+/// Escapes the value of a `NAME=value` attribute following RFC 4514.
+fn escape_dn_value(attr: &str) -> String {
+    let (name, value) = attr.split_once('=').unwrap_or(("", attr));
+    let mut escaped = String::with_capacity(attr.len());
+    for (i, c) in value.char_indices() {
+        let edge =
+            (i == 0 && (c == ' ' || c == '#')) || (i + c.len_utf8() == value.len() && c == ' ');
+        if edge || matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    format!("{name}={escaped}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -447,4 +913,152 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn client_certificate_identity() {
+        let pem = include_bytes!("../tests/tls/local.client_cert.pem");
+        let certs = rustls_pemfile::certs(&mut &pem[..]).unwrap();
+        let identity = client_identity(&certs[0]).unwrap();
+
+        assert_eq!(identity.subject, "CN=alice,OU=QA,O=Static Web Server,C=US");
+        assert_eq!(identity.common_name(), Some("alice"));
+        assert_eq!(
+            identity.sans,
+            vec![
+                "DNS:alice.local",
+                "email:alice@example.com",
+                "IP:10.0.0.7",
+                "URI:spiffe://local/alice"
+            ]
+        );
+        assert!(identity.matches("CN=alice"));
+        assert!(identity.matches("O=Static Web Server"));
+        assert!(identity.matches("DNS:alice.local"));
+        assert!(identity.matches("CN=alice,OU=QA,O=Static Web Server,C=US"));
+        assert!(!identity.matches("CN=bob"));
+        assert!(!identity.matches("alice"));
+
+        assert!(client_identity(b"not a certificate").is_none());
+        assert_eq!(escape_dn_value("O=Example, Inc."), "O=Example\\, Inc.");
+        assert_eq!(escape_dn_value("CN=Zoë "), "CN=Zoë\\ ");
+    }
+
+    #[test]
+    fn key_mismatch() {
+        let err = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.pem")
+            .key_path("tests/tls/local.dev_key.ecc.pem")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::KeyMismatch));
+
+        let err = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.rsa1024_cert.pem")
+            .key_path("tests/tls/local.rsa1024_key.pem")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::UnsupportedKeySize(1024)));
+    }
+
+    #[test]
+    fn sni_certificates() {
+        let rsa = || {
+            read_certified_key(
+                Box::new(File::open("tests/tls/local.dev_cert.pem").unwrap()),
+                Box::new(File::open("tests/tls/local.dev_key.pem").unwrap()),
+            )
+            .unwrap()
+        };
+        let ecc = Arc::new(
+            read_certified_key(
+                Box::new(File::open("tests/tls/local.dev_cert.ecc.pem").unwrap()),
+                Box::new(File::open("tests/tls/local.dev_key.ecc.pem").unwrap()),
+            )
+            .unwrap(),
+        );
+        let dev = Arc::new(rsa());
+        assert_eq!(
+            cert_dns_names(&dev.cert[0]),
+            vec!["local.dev", "*.local.dev", "localhost"]
+        );
+
+        let mut resolver = SniResolver::new(rsa());
+        for host in cert_dns_names(&dev.cert[0]) {
+            resolver.add(&host, dev.clone());
+        }
+        resolver.add("Example.COM", ecc.clone());
+        resolver.add("local.dev", ecc.clone());
+
+        let lookup = |name| resolver.lookup(name);
+        assert!(Arc::ptr_eq(&lookup("local.dev").unwrap(), &dev));
+        assert!(Arc::ptr_eq(&lookup("www.local.dev").unwrap(), &dev));
+        assert!(Arc::ptr_eq(&lookup("example.com.").unwrap(), &ecc));
+        assert!(lookup("a.b.local.dev").is_none());
+        assert!(lookup("www.example.com").is_none());
+
+        // Certificates without names need explicit hosts
+        let err = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.pem")
+            .key_path("tests/tls/local.dev_key.pem")
+            .sni_cert_path(
+                vec![],
+                "tests/tls/local.dev_cert.ecc.pem",
+                "tests/tls/local.dev_key.ecc.pem",
+                None,
+            )
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::MissingServerName));
+    }
+
+    #[test]
+    fn ocsp_stapling() {
+        let ocsp_key = || {
+            read_certified_key(
+                Box::new(File::open("tests/tls/local.ocsp_cert.pem").unwrap()),
+                Box::new(File::open("tests/tls/local.ocsp_key.pem").unwrap()),
+            )
+            .unwrap()
+        };
+        let ocsp_resp = |path| -> Option<Box<dyn Read + Send + Sync>> {
+            Some(Box::new(File::open(path).unwrap()))
+        };
+
+        let mut certified_key = ocsp_key();
+        staple_ocsp_resp(
+            &mut certified_key,
+            ocsp_resp("tests/tls/local.ocsp_good.der"),
+        )
+        .unwrap();
+        assert_eq!(
+            certified_key.ocsp.unwrap(),
+            std::fs::read("tests/tls/local.ocsp_good.der").unwrap()
+        );
+
+        let err = staple_ocsp_resp(
+            &mut ocsp_key(),
+            ocsp_resp("tests/tls/local.ocsp_revoked.der"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, TlsConfigError::InvalidOcspResponse(_)));
+
+        // A response of another certificate is rejected
+        let err = TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.pem")
+            .key_path("tests/tls/local.dev_key.pem")
+            .ocsp_resp_path("tests/tls/local.ocsp_good.der")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::InvalidOcspResponse(_)));
+    }
+
+    #[test]
+    fn client_auth_required() {
+        TlsConfigBuilder::new()
+            .cert_path("tests/tls/local.dev_cert.pem")
+            .key_path("tests/tls/local.dev_key.pem")
+            .client_auth_required_path("tests/tls/local.client_ca.pem")
+            .build()
+            .unwrap();
+    }
 }
@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// This file is part of Static Web Server.
+// See https://static-web-server.net/ for more information
+// Copyright (C) 2019-present Jose Quintana <joseluisq.net>
+
+//! Module to reload the TLS certificates while the server is running.
+//!
+//! The certificate files are checked regularly and reloaded when they change
+//! or when a `SIGHUP` signal is received. A new configuration which fails to build
+//! is discarded and the current one is kept.
+//!
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+use tokio_rustls::rustls::ServerConfig;
+
+use crate::tls::{SharedTlsConfig, TlsConfigError};
+
+/// Time to wait after a change for the files to be completely written.
+const SETTLE_DELAY: Duration = Duration::from_secs(1);
+
+/// Modification time and size of a file.
+type FileStamp = Option<(SystemTime, u64)>;
+
+/// Function building a new TLS configuration from the certificate files.
+type BuildFn = dyn Fn() -> Result<ServerConfig, TlsConfigError> + Send + Sync;
+
+/// It defines a TLS configuration rebuilt from its files when they change.
+pub struct TlsReloader {
+    config: SharedTlsConfig,
+    paths: Vec<PathBuf>,
+    stamps: Mutex<Vec<FileStamp>>,
+    build: Box<BuildFn>,
+}
+
+impl TlsReloader {
+    /// Builds the initial TLS configuration from the given files.
+    pub fn new<F>(paths: Vec<PathBuf>, build: F) -> Result<Self, TlsConfigError>
+    where
+        F: Fn() -> Result<ServerConfig, TlsConfigError> + Send + Sync + 'static,
+    {
+        let stamps = paths.iter().map(file_stamp).collect();
+        let config = SharedTlsConfig::new(build()?);
+        Ok(Self {
+            config,
+            paths,
+            stamps: Mutex::new(stamps),
+            build: Box::new(build),
+        })
+    }
+
+    /// Returns the shared configuration updated on every reload.
+    pub fn config(&self) -> SharedTlsConfig {
+        self.config.clone()
+    }
+
+    /// Rebuilds the configuration, keeping the current one if it fails.
+    pub fn reload(&self) -> bool {
+        match self.rebuild() {
+            Ok(()) => {
+                tracing::info!("tls certificates reloaded");
+                true
+            }
+            Err(err) => {
+                tracing::error!(
+                    "failed to reload the tls certificates, keeping the current ones: {}",
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    /// Rebuilds the configuration without logging, keeping the current one if it fails.
+    fn rebuild(&self) -> Result<(), TlsConfigError> {
+        self.config.store((self.build)()?);
+        Ok(())
+    }
+
+    /// Checks whether any of the files changed since the last check.
+    pub fn has_changed(&self) -> bool {
+        let stamps: Vec<_> = self.paths.iter().map(file_stamp).collect();
+        let mut current = self.stamps.lock().unwrap();
+        let changed = *current != stamps;
+        *current = stamps;
+        changed
+    }
+
+    /// Reloads the configuration when the files change, checking them every `interval`,
+    /// or when `sighup` is notified. A zero interval disables the file checks.
+    pub async fn watch(self: Arc<Self>, interval: Duration, sighup: Arc<Notify>) {
+        loop {
+            tokio::select! {
+                _ = sighup.notified() => {
+                    tracing::info!("SIGHUP caught, reloading the tls certificates");
+                    self.has_changed();
+                }
+                _ = tokio::time::sleep(interval), if !interval.is_zero() => {
+                    if !self.has_changed() {
+                        continue;
+                    }
+                    tokio::time::sleep(SETTLE_DELAY).await;
+                    self.has_changed();
+                    tracing::info!("tls certificate files changed, reloading them");
+                }
+            }
+            let reloader = self.clone();
+            if tokio::task::spawn_blocking(move || reloader.reload())
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Rebuilds the configuration every `interval` to re-read the OCSP responses,
+    /// whether their files changed or not, so expired responses stop being stapled.
+    pub async fn refresh(self: Arc<Self>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let reloader = self.clone();
+            match tokio::task::spawn_blocking(move || reloader.rebuild()).await {
+                Ok(Ok(())) => tracing::debug!("tls ocsp responses refreshed"),
+                Ok(Err(err)) => tracing::error!(
+                    "failed to refresh the tls ocsp responses, keeping the current ones: {}",
+                    err
+                ),
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// Returns the modification time and the size of a file if it exists.
+fn file_stamp(path: &PathBuf) -> FileStamp {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::TlsConfigBuilder;
+
+    #[test]
+    fn reload_keeps_valid_config() {
+        let dir = std::env::temp_dir().join(format!("sws-tls-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+        fs::copy("tests/tls/local.dev_cert.pem", &cert).unwrap();
+        fs::copy("tests/tls/local.dev_key.pem", &key).unwrap();
+
+        let (cert_path, key_path) = (cert.clone(), key.clone());
+        let reloader = TlsReloader::new(vec![cert.clone(), key.clone()], move || {
+            TlsConfigBuilder::new()
+                .cert_path(&cert_path)
+                .key_path(&key_path)
+                .build()
+        })
+        .unwrap();
+        let shared = reloader.config();
+        let initial = shared.load();
+        assert!(!reloader.has_changed());
+
+        // A certificate not matching the key is rejected
+        fs::copy("tests/tls/local.dev_cert.ecc.pem", &cert).unwrap();
+        assert!(reloader.has_changed());
+        assert!(!reloader.reload());
+        assert!(Arc::ptr_eq(&initial, &shared.load()));
+
+        fs::copy("tests/tls/local.dev_key.ecc.pem", &key).unwrap();
+        assert!(reloader.reload());
+        assert!(!Arc::ptr_eq(&initial, &shared.load()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
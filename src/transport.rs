@@ -11,15 +11,62 @@
 use std::io;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll};
 
 use hyper::server::conn::AddrStream;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
+use crate::timeouts::ActiveRequests;
+
+/// It defines the identity of a verified TLS client certificate.
+/// It's available to the request handler as a request extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientIdentity {
+    /// Subject distinguished name in RFC 4514 format (e.g. `CN=alice,O=Example`).
+    pub subject: String,
+    /// Subject attributes in certificate order (e.g. `O=Example` and `CN=alice`).
+    pub attributes: Vec<String>,
+    /// Subject alternative names prefixed by their type
+    /// (e.g. `DNS:alice.local`, `email:alice@example.com`, `IP:10.0.0.7` or `URI:spiffe://example/alice`).
+    pub sans: Vec<String>,
+}
+
+impl ClientIdentity {
+    /// Returns the common name (`CN`) of the subject if any.
+    pub fn common_name(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .rev()
+            .find_map(|attr| attr.strip_prefix("CN="))
+    }
+
+    /// Checks whether the identity matches the given subject distinguished name,
+    /// subject attribute (e.g. `CN=alice`) or subject alternative name (e.g. `DNS:alice.local`).
+    pub fn matches(&self, identity: &str) -> bool {
+        self.subject == identity
+            || self.attributes.iter().any(|v| v == identity)
+            || self.sans.iter().any(|v| v == identity)
+    }
+}
+
+/// Slot holding the client identity of a connection once its TLS handshake completes.
+pub type PeerIdentity = Arc<OnceLock<ClientIdentity>>;
+
 /// Transport trait that supports the remote (peer) address.
 pub trait Transport: AsyncRead + AsyncWrite {
     /// Returns the remote (peer) address of this connection.
     fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Returns the client identity slot of this connection if it supports client certificates.
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        None
+    }
+
+    /// Returns the counter of the requests being served on this connection if it tracks them.
+    fn active_requests(&self) -> Option<ActiveRequests> {
+        None
+    }
 }
 
 impl Transport for AddrStream {
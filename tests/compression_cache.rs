@@ -0,0 +1,161 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(feature = "compression")]
+#[cfg(test)]
+mod tests {
+    use async_compression::tokio::bufread::GzipDecoder;
+    use headers::HeaderMap;
+    use http::Method;
+    use std::path::PathBuf;
+    use tokio::io::AsyncReadExt;
+
+    #[cfg(feature = "directory-listing")]
+    use static_web_server::directory_listing::DirListFmt;
+    use static_web_server::{
+        compression_cache::CompressionCache,
+        static_files::{self, HandleOpts},
+    };
+
+    fn public_dir() -> PathBuf {
+        PathBuf::from("docker/public/")
+    }
+
+    #[tokio::test]
+    async fn compression_cache_reuses_body() {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::ACCEPT_ENCODING, "gzip".parse().unwrap());
+
+        let cache_dir = std::env::temp_dir().join("sws-compression-cache-test");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        std::fs::create_dir_all(&cache_dir).unwrap();
+
+        let cache = CompressionCache::new(1024 * 1024, Some(cache_dir.clone()));
+        let index = std::fs::read(public_dir().join("index.html")).unwrap();
+
+        for _ in 0..2 {
+            let result = static_files::handle_response(&HandleOpts {
+                method: &Method::GET,
+                headers: &headers,
+                base_path: &public_dir(),
+                uri_path: "index.html",
+                uri_query: None,
+                #[cfg(feature = "directory-listing")]
+                dir_listing: false,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_order: 6,
+                #[cfg(feature = "directory-listing")]
+                dir_listing_format: &DirListFmt::Html,
+                redirect_trailing_slash: true,
+                compression_static: false,
+                ignore_hidden_files: false,
+            })
+            .await
+            .expect("unexpected error response on `handle` function");
+
+            let mut resp = cache
+                .auto(&Method::GET, &headers, &result.file_path, result.resp)
+                .await
+                .expect("unexpected error during body compression");
+
+            assert_eq!(resp.status(), 200);
+            assert_eq!(resp.headers()["content-encoding"], "gzip");
+
+            let body = hyper::body::to_bytes(resp.body_mut())
+                .await
+                .expect("unexpected bytes error during `body` conversion");
+            assert_eq!(
+                resp.headers()["content-length"],
+                body.len().to_string(),
+                "content-length does not match the compressed body"
+            );
+
+            let mut decoded = Vec::new();
+            GzipDecoder::new(&body[..])
+                .read_to_end(&mut decoded)
+                .await
+                .unwrap();
+            assert_eq!(decoded, index);
+        }
+
+        // The compressed entry was also persisted on disk
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn compression_cache_file_replaced_by_older_one() {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::ACCEPT_ENCODING, "gzip".parse().unwrap());
+
+        let root = std::env::temp_dir().join("sws-compression-cache-rollback-test");
+        let _ = std::fs::remove_dir_all(&root);
+        let cache_dir = root.join("cache");
+        let public = root.join("public");
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        std::fs::create_dir_all(&public).unwrap();
+
+        let file_path = public.join("app.js");
+        let compressed_body = |cache: CompressionCache| {
+            let headers = headers.clone();
+            let public = public.clone();
+            async move {
+                let result = static_files::handle_response(&HandleOpts {
+                    method: &Method::GET,
+                    headers: &headers,
+                    base_path: &public,
+                    uri_path: "app.js",
+                    uri_query: None,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing: false,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_order: 6,
+                    #[cfg(feature = "directory-listing")]
+                    dir_listing_format: &DirListFmt::Html,
+                    redirect_trailing_slash: true,
+                    compression_static: false,
+                    ignore_hidden_files: false,
+                })
+                .await
+                .expect("unexpected error response on `handle_response` function");
+                let resp = cache
+                    .auto(&Method::GET, &headers, &result.file_path, result.resp)
+                    .await
+                    .expect("unexpected error during body compression");
+                let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                let mut decoded = Vec::new();
+                GzipDecoder::new(&body[..])
+                    .read_to_end(&mut decoded)
+                    .await
+                    .unwrap();
+                decoded
+            }
+        };
+
+        let now = std::time::SystemTime::now();
+        std::fs::write(&file_path, "console.log('new');").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(now)
+            .unwrap();
+        let cache = CompressionCache::new(1024 * 1024, Some(cache_dir.clone()));
+        assert_eq!(compressed_body(cache).await, b"console.log('new');");
+
+        // Roll the file back to an older version with the same size, then "restart"
+        std::fs::write(&file_path, "console.log('old');").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(now - std::time::Duration::from_secs(3600))
+            .unwrap();
+        let cache = CompressionCache::new(1024 * 1024, Some(cache_dir.clone()));
+        assert_eq!(compressed_body(cache).await, b"console.log('old');");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
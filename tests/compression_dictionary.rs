@@ -0,0 +1,228 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(feature = "compression")]
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use globset::Glob;
+    use headers::{HeaderMap, HeaderValue};
+    use http::Method;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    #[cfg(feature = "directory-listing")]
+    use static_web_server::directory_listing::DirListFmt;
+    use static_web_server::{
+        compression::{DCB_MAGIC, DCZ_MAGIC},
+        compression_cache::CompressionCache,
+        compression_dictionary::DictionaryStore,
+        settings::CompressionDictionaries,
+        static_files::{self, HandleOpts},
+    };
+
+    const APP_V1: &str =
+        "function greet(name) { return `Hello, ${name}!`; }\nconsole.log(greet('v1'));\n";
+    const APP_V2: &str =
+        "function greet(name) { return `Hello, ${name}!`; }\nconsole.log(greet('v2'));\n";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("js")).unwrap();
+        std::fs::write(dir.join("js/app.v1.js"), APP_V1).unwrap();
+        std::fs::write(dir.join("js/app.v2.js"), APP_V2).unwrap();
+        dir
+    }
+
+    fn store(root: &Path) -> DictionaryStore {
+        let store = DictionaryStore::new(vec![CompressionDictionaries {
+            source: Glob::new("/js/app.*.js").unwrap().compile_matcher(),
+            use_as_dictionary: HeaderValue::from_static("match=\"/js/app.*.js\", id=\"app\""),
+        }]);
+        assert_eq!(store.load_dir(root).unwrap(), 2);
+        store
+    }
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, data).as_ref());
+        hash
+    }
+
+    fn request_headers(accept_encoding: &'static str, hash: &[u8; 32]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::ACCEPT_ENCODING,
+            HeaderValue::from_static(accept_encoding),
+        );
+        let value = format!(
+            ":{}:",
+            base64::engine::general_purpose::STANDARD.encode(hash)
+        );
+        headers.insert("available-dictionary", value.parse().unwrap());
+        headers
+    }
+
+    async fn dictionary_response(
+        root: &Path,
+        store: &DictionaryStore,
+        headers: &HeaderMap,
+        compression_static: bool,
+        cache: Option<&CompressionCache>,
+    ) -> (http::response::Parts, Vec<u8>, bool) {
+        let result = static_files::handle_response(&HandleOpts {
+            method: &Method::GET,
+            headers,
+            base_path: &root.to_owned(),
+            uri_path: "/js/app.v2.js",
+            uri_query: None,
+            #[cfg(feature = "directory-listing")]
+            dir_listing: false,
+            #[cfg(feature = "directory-listing")]
+            dir_listing_order: 6,
+            #[cfg(feature = "directory-listing")]
+            dir_listing_format: &DirListFmt::Html,
+            redirect_trailing_slash: true,
+            compression_static: false,
+            ignore_hidden_files: false,
+        })
+        .await
+        .expect("unexpected error response on `handle` function");
+
+        let (resp, compressed) = store
+            .auto(
+                &Method::GET,
+                headers,
+                "/js/app.v2.js",
+                &result.file_path,
+                result.resp,
+                true,
+                compression_static,
+                cache,
+            )
+            .await
+            .expect("unexpected error during dictionary compression");
+
+        let (head, body) = resp.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap().to_vec();
+        (head, body, compressed)
+    }
+
+    #[tokio::test]
+    async fn dictionary_compression_dcz() {
+        let root = temp_dir("sws-compression-dictionary-dcz");
+        let store = store(&root);
+        let hash = sha256(APP_V1.as_bytes());
+        let headers = request_headers("gzip, br, zstd, dcb, dcz", &hash);
+
+        let (head, body, compressed) =
+            dictionary_response(&root, &store, &headers, false, None).await;
+        assert!(compressed);
+        assert_eq!(head.headers["content-encoding"], "dcz");
+        assert_eq!(
+            head.headers["use-as-dictionary"],
+            "match=\"/js/app.*.js\", id=\"app\""
+        );
+        assert_eq!(head.headers["content-length"], body.len().to_string());
+
+        assert_eq!(body[..8], DCZ_MAGIC);
+        assert_eq!(body[8..40], hash);
+        let mut decompressor =
+            zstd::bulk::Decompressor::with_dictionary(APP_V1.as_bytes()).unwrap();
+        let decoded = decompressor.decompress(&body[40..], APP_V2.len()).unwrap();
+        assert_eq!(decoded, APP_V2.as_bytes());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dictionary_compression_dcb() {
+        let root = temp_dir("sws-compression-dictionary-dcb");
+        let store = store(&root);
+        let hash = sha256(APP_V1.as_bytes());
+        let headers = request_headers("br, dcb", &hash);
+
+        let (head, body, compressed) =
+            dictionary_response(&root, &store, &headers, false, None).await;
+        assert!(compressed);
+        assert_eq!(head.headers["content-encoding"], "dcb");
+
+        assert_eq!(body[..4], DCB_MAGIC);
+        assert_eq!(body[4..36], hash);
+        let mut decoded = String::new();
+        brotli::Decompressor::new_with_custom_dict(
+            &body[36..],
+            4096,
+            APP_V1.as_bytes().to_vec().into(),
+        )
+        .read_to_string(&mut decoded)
+        .unwrap();
+        assert_eq!(decoded, APP_V2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dictionary_compression_precompressed() {
+        let root = temp_dir("sws-compression-dictionary-static");
+        let store = store(&root);
+        let hash = sha256(APP_V1.as_bytes());
+        let headers = request_headers("dcb", &hash);
+
+        let hash_hex: String = hash.iter().map(|b| format!("{b:02x}")).collect();
+        let variant = root.join(format!("js/app.v2.js.{hash_hex}.dcb"));
+        std::fs::write(&variant, b"precompressed").unwrap();
+
+        let (head, body, compressed) =
+            dictionary_response(&root, &store, &headers, true, None).await;
+        assert!(compressed);
+        assert_eq!(head.headers["content-encoding"], "dcb");
+        assert_eq!(body, b"precompressed");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dictionary_compression_unknown_dictionary() {
+        let root = temp_dir("sws-compression-dictionary-unknown");
+        let store = store(&root);
+        let headers = request_headers("dcb, dcz", &sha256(b"unknown"));
+
+        let (head, body, compressed) =
+            dictionary_response(&root, &store, &headers, true, None).await;
+        assert!(!compressed);
+        assert!(head.headers.get("content-encoding").is_none());
+        assert_eq!(body, APP_V2.as_bytes());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dictionary_compression_cached() {
+        let root = temp_dir("sws-compression-dictionary-cached");
+        let cache_dir = root.join("cache");
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let cache = CompressionCache::new(1024 * 1024, Some(cache_dir.clone()));
+        let store = store(&root);
+        let hash = sha256(APP_V1.as_bytes());
+        let headers = request_headers("zstd, dcz", &hash);
+
+        let (head, body, compressed) =
+            dictionary_response(&root, &store, &headers, false, Some(&cache)).await;
+        assert!(compressed);
+        assert_eq!(head.headers["content-encoding"], "dcz");
+        assert_eq!(head.headers["content-length"], body.len().to_string());
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+        let (_, cached_body, compressed) =
+            dictionary_response(&root, &store, &headers, false, Some(&cache)).await;
+        assert!(compressed);
+        assert_eq!(cached_body, body);
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
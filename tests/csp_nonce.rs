@@ -0,0 +1,82 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(feature = "csp-nonce")]
+#[cfg(test)]
+mod tests {
+    use headers::HeaderMap;
+    use http::Method;
+    use std::path::PathBuf;
+
+    #[cfg(feature = "directory-listing")]
+    use static_web_server::directory_listing::DirListFmt;
+    use static_web_server::{
+        csp_nonce::{CspNonce, DEFAULT_PLACEHOLDER, DEFAULT_POLICY},
+        static_files::{self, HandleOpts},
+    };
+
+    async fn nonce_response(uri_path: &str) -> (http::response::Parts, String, Option<String>) {
+        let csp_nonce = CspNonce::new(DEFAULT_PLACEHOLDER, DEFAULT_POLICY, true).unwrap();
+        let result = static_files::handle_response(&HandleOpts {
+            method: &Method::GET,
+            headers: &HeaderMap::new(),
+            base_path: &PathBuf::from("docker/public/"),
+            uri_path,
+            uri_query: None,
+            #[cfg(feature = "directory-listing")]
+            dir_listing: false,
+            #[cfg(feature = "directory-listing")]
+            dir_listing_order: 6,
+            #[cfg(feature = "directory-listing")]
+            dir_listing_format: &DirListFmt::Html,
+            redirect_trailing_slash: true,
+            compression_static: false,
+            ignore_hidden_files: false,
+        })
+        .await
+        .expect("unexpected error response on `handle` function");
+
+        let (resp, policy) = csp_nonce
+            .rewrite(&Method::GET, result.resp)
+            .await
+            .expect("unexpected error during csp nonce injection");
+
+        let (head, body) = resp.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (
+            head,
+            String::from_utf8(body.to_vec()).unwrap(),
+            policy.map(|v| v.to_str().unwrap().to_owned()),
+        )
+    }
+
+    #[tokio::test]
+    async fn csp_nonce_html_document() {
+        let (head, body, policy) = nonce_response("/index.html").await;
+        let policy = policy.expect("html documents should get a csp policy");
+
+        let nonce = policy
+            .strip_prefix("script-src 'nonce-")
+            .and_then(|s| s.split('\'').next())
+            .unwrap();
+        assert!(body.contains(&format!("<script nonce=\"{nonce}\"")));
+        assert_eq!(head.headers["content-length"], body.len().to_string());
+        assert!(head.headers.get("last-modified").is_none());
+
+        let (_, _, other_policy) = nonce_response("/index.html").await;
+        assert_ne!(Some(policy), other_policy);
+    }
+
+    #[tokio::test]
+    async fn csp_nonce_non_html_file() {
+        let (head, body, policy) = nonce_response("/assets/main.css").await;
+        assert!(policy.is_none());
+        assert!(head.headers.get("last-modified").is_some());
+        assert_eq!(
+            body,
+            std::fs::read_to_string("docker/public/assets/main.css").unwrap()
+        );
+    }
+}
@@ -0,0 +1,349 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use static_web_server::handler::{RequestHandler, RequestHandlerOpts};
+
+    #[cfg(any(
+        feature = "compression",
+        all(feature = "csp-nonce", feature = "compression-gzip")
+    ))]
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(all(feature = "csp-nonce", feature = "compression-gzip"))]
+    #[tokio::test]
+    async fn csp_nonce_skips_precompressed_html() {
+        use async_compression::tokio::write::GzipEncoder;
+        use static_web_server::csp_nonce::{CspNonce, DEFAULT_PLACEHOLDER, DEFAULT_POLICY};
+        use tokio::io::AsyncWriteExt;
+
+        let root = temp_dir("sws-handler-csp-nonce-precompressed");
+        let html = format!("<script nonce=\"{DEFAULT_PLACEHOLDER}\">run()</script>");
+        std::fs::write(root.join("index.html"), &html).unwrap();
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(html.as_bytes()).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        std::fs::write(root.join("index.html.gz"), encoder.into_inner()).unwrap();
+
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: root.clone(),
+                compression: false,
+                compression_static: true,
+                csp_nonce: Some(CspNonce::new(DEFAULT_PLACEHOLDER, DEFAULT_POLICY, false).unwrap()),
+                ..Default::default()
+            }),
+        };
+        let mut req = Request::get("/index.html")
+            .header("accept-encoding", "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let resp = handler.handle(&mut req, None).await.unwrap();
+
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get("content-encoding").is_none());
+        let policy = resp.headers()["content-security-policy"]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(!body.contains(DEFAULT_PLACEHOLDER));
+        let nonce = body
+            .strip_prefix("<script nonce=\"")
+            .and_then(|s| s.split('"').next())
+            .unwrap();
+        assert!(policy.contains(&format!("'nonce-{nonce}'")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rate_limit_ignores_rejected_oversized_requests() {
+        use static_web_server::{rate_limit::RateLimiter, request_limits::RequestLimits};
+
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: PathBuf::from("docker/public/"),
+                rate_limit: Some(RateLimiter::new(0.001, 1).unwrap()),
+                request_limits: RequestLimits {
+                    max_uri_length: 32,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        };
+        let remote_addr = Some("10.0.0.1:1234".parse().unwrap());
+
+        let long_uri = format!("/{}", "a".repeat(64));
+        for _ in 0..3 {
+            let mut req = Request::get(long_uri.as_str()).body(Body::empty()).unwrap();
+            let resp = handler.handle(&mut req, remote_addr).await.unwrap();
+            assert_eq!(resp.status(), 414);
+        }
+
+        // The only token of the client is still available
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let resp = handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["ratelimit-remaining"], "0");
+
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let resp = handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(resp.status(), 429);
+    }
+
+    #[tokio::test]
+    async fn auth_bans_only_apply_to_protected_paths() {
+        use static_web_server::{
+            auth_throttle::AuthThrottle,
+            settings::{file::AuthKind, Advanced, Auth},
+        };
+        use std::time::Duration;
+
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: PathBuf::from("docker/public/"),
+                auth_throttle: Some(AuthThrottle::new(
+                    1,
+                    Duration::from_secs(60),
+                    Duration::from_secs(60),
+                )),
+                advanced_opts: Some(Advanced {
+                    headers: None,
+                    rewrites: None,
+                    redirects: None,
+                    cache_control: None,
+                    security_headers: None,
+                    auth: Some(vec![Auth {
+                        source: globset::Glob::new("/api/**").unwrap().compile_matcher(),
+                        kind: AuthKind::Bearer,
+                        users: vec![],
+                        tokens: vec!["secret".to_owned()],
+                        claims: vec![],
+                        identities: vec![],
+                        realm: "Static Web Server".to_owned(),
+                    }]),
+                    ip_filter: None,
+                    rate_limit: None,
+                    bandwidth_limit: None,
+                    #[cfg(feature = "compression")]
+                    compression_dictionaries: None,
+                    #[cfg(feature = "http2")]
+                    tls_certificates: None,
+                }),
+                ..Default::default()
+            }),
+        };
+        let remote_addr = Some("10.0.0.1:1234".parse().unwrap());
+
+        let mut req = Request::get("/api/data.json")
+            .header("authorization", "Bearer wrong")
+            .body(Body::empty())
+            .unwrap();
+        let resp = handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let mut req = Request::get("/api/data.json")
+            .header("authorization", "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let resp = handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(resp.status(), 429);
+
+        // Public paths are still served to the banned client
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let resp = handler.handle(&mut req, remote_addr).await.unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn ip_filter_rules_keep_the_global_lists() {
+        use static_web_server::{
+            ip_filter::{parse_list, IpFilter},
+            settings::{Advanced, IpFilterRule},
+        };
+
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: PathBuf::from("docker/public/"),
+                ip_filter: IpFilter::new(vec![], parse_list("192.168.1.66").unwrap()),
+                advanced_opts: Some(Advanced {
+                    headers: None,
+                    rewrites: None,
+                    redirects: None,
+                    cache_control: None,
+                    security_headers: None,
+                    auth: None,
+                    ip_filter: Some(vec![IpFilterRule {
+                        source: globset::Glob::new("/*.html").unwrap().compile_matcher(),
+                        filter: IpFilter::new(parse_list("192.168.0.0/16").unwrap(), vec![]),
+                    }]),
+                    rate_limit: None,
+                    bandwidth_limit: None,
+                    #[cfg(feature = "compression")]
+                    compression_dictionaries: None,
+                    #[cfg(feature = "http2")]
+                    tls_certificates: None,
+                }),
+                ..Default::default()
+            }),
+        };
+
+        let status = |addr: &str| {
+            let handler = &handler;
+            let remote_addr = Some(addr.parse().unwrap());
+            async move {
+                let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+                handler
+                    .handle(&mut req, remote_addr)
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+        assert_eq!(status("192.168.1.7:1234").await, 200);
+        assert_eq!(status("10.0.0.1:1234").await, 403);
+        // Globally denied addresses stay denied inside the range allowed by the rule
+        assert_eq!(status("192.168.1.66:1234").await, 403);
+    }
+
+    #[cfg(feature = "forward-auth")]
+    #[tokio::test]
+    async fn forward_auth_copies_response_headers() {
+        use hyper::{
+            service::{make_service_fn, service_fn},
+            Response, Server, StatusCode,
+        };
+        use static_web_server::forward_auth::ForwardAuth;
+        use std::{convert::Infallible, time::Duration};
+
+        // Stand-in authorization service identifying the `alice` token
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                let mut resp = Response::new(Body::empty());
+                if req.headers()["authorization"] == "Bearer alice" {
+                    resp.headers_mut()
+                        .insert("x-user", "alice".parse().unwrap());
+                    resp.headers_mut()
+                        .insert("x-secret", "hidden".parse().unwrap());
+                } else {
+                    *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                }
+                Ok::<_, Infallible>(resp)
+            }))
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+        let url = format!("http://{}/auth", server.local_addr());
+        tokio::spawn(server);
+
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: PathBuf::from("docker/public/"),
+                forward_auth: Some(
+                    ForwardAuth::new(&url, "authorization", "x-user", Duration::from_secs(5))
+                        .unwrap(),
+                ),
+                ..Default::default()
+            }),
+        };
+
+        let mut req = Request::get("/index.html")
+            .header("authorization", "Bearer alice")
+            .body(Body::empty())
+            .unwrap();
+        let resp = handler.handle(&mut req, None).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["x-user"], "alice");
+        assert!(resp.headers().get("x-secret").is_none());
+
+        let mut req = Request::get("/index.html")
+            .header("authorization", "Bearer eve")
+            .body(Body::empty())
+            .unwrap();
+        let resp = handler.handle(&mut req, None).await.unwrap();
+        assert_eq!(resp.status(), 401);
+        assert!(resp.headers().get("x-user").is_none());
+    }
+
+    #[cfg(feature = "signed-urls")]
+    #[tokio::test]
+    async fn signed_urls_opt_in() {
+        use static_web_server::signed_urls::UrlSigner;
+
+        let handler = |required: bool| RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: PathBuf::from("docker/public/"),
+                signed_urls: Some(UrlSigner::new(b"0123456789abcdef").unwrap()),
+                signed_urls_required: required,
+                ..Default::default()
+            }),
+        };
+
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let resp = handler(false).handle(&mut req, None).await.unwrap();
+        assert_eq!(resp.status(), 200);
+
+        let mut req = Request::get("/index.html").body(Body::empty()).unwrap();
+        let resp = handler(true).handle(&mut req, None).await.unwrap();
+        assert_eq!(resp.status(), 403);
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test(start_paused = true)]
+    async fn bandwidth_limit_compressed_cached_responses() {
+        use static_web_server::{bandwidth::Bandwidth, compression_cache::CompressionCache};
+        use tokio::time::Instant;
+
+        // Incompressible text so the gzip body is as large as the file
+        let root = temp_dir("sws-handler-bandwidth-compression-cache");
+        let mut seed = 0x2545_f491_u32;
+        let text: String = (0..8_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                char::from(b'!' + ((seed >> 16) % 94) as u8)
+            })
+            .collect();
+        std::fs::write(root.join("data.txt"), &text).unwrap();
+
+        let handler = RequestHandler {
+            opts: Arc::new(RequestHandlerOpts {
+                root_dir: root.clone(),
+                compression: true,
+                compression_cache: Some(CompressionCache::new(1024 * 1024, None)),
+                bandwidth_limit_global: Some(Arc::new(Bandwidth::new(2_000))),
+                ..Default::default()
+            }),
+        };
+
+        // Compressed on the first request then served from the cache
+        for _ in 0..2 {
+            let mut req = Request::get("/data.txt")
+                .header("accept-encoding", "gzip")
+                .body(Body::empty())
+                .unwrap();
+            let resp = handler.handle(&mut req, None).await.unwrap();
+            assert_eq!(resp.status(), 200);
+            assert_eq!(resp.headers()["content-encoding"], "gzip");
+
+            let start = Instant::now();
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            assert!(body.len() > 6_000);
+            assert!(start.elapsed().as_secs() >= 2);
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+#![forbid(unsafe_code)]
+#![deny(warnings)]
+#![deny(rust_2018_idioms)]
+#![deny(dead_code)]
+
+#[cfg(feature = "compression")]
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use static_web_server::precompress::{self, PrecompressStats};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::copy("docker/public/index.html", dir.join("index.html")).unwrap();
+        std::fs::copy("docker/public/assets/main.css", dir.join("assets/main.css")).unwrap();
+        std::fs::copy(
+            "docker/public/assets/favicon.ico",
+            dir.join("assets/favicon.ico"),
+        )
+        .unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn precompress_dir_variants() {
+        let dir = temp_dir("sws-precompress-variants");
+        let encodings = precompress::parse_encodings("gzip,br").unwrap();
+
+        let stats = precompress::precompress_dir(&dir, &encodings)
+            .await
+            .expect("unexpected error during pre-compression");
+        assert_eq!(
+            stats,
+            PrecompressStats {
+                compressed: 4,
+                up_to_date: 0,
+                skipped: 0,
+            }
+        );
+        assert!(dir.join("index.html.gz").is_file());
+        assert!(dir.join("index.html.br").is_file());
+        assert!(dir.join("assets/main.css.gz").is_file());
+        assert!(dir.join("assets/main.css.br").is_file());
+        // Non-text-based files are never pre-compressed
+        assert!(!dir.join("assets/favicon.ico.gz").exists());
+
+        // Up-to-date variants are not rewritten
+        let stats = precompress::precompress_dir(&dir, &encodings)
+            .await
+            .expect("unexpected error during pre-compression");
+        assert_eq!(
+            stats,
+            PrecompressStats {
+                compressed: 0,
+                up_to_date: 4,
+                skipped: 0,
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn precompress_dir_no_gain() {
+        let dir = temp_dir("sws-precompress-no-gain");
+        std::fs::write(dir.join("tiny.txt"), "a").unwrap();
+        let encodings = precompress::parse_encodings("gzip").unwrap();
+
+        let stats = precompress::precompress_dir(&dir, &encodings)
+            .await
+            .expect("unexpected error during pre-compression");
+        assert_eq!(stats.skipped, 1);
+        assert!(!dir.join("tiny.txt.gz").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}